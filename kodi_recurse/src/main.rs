@@ -1,11 +1,15 @@
 //TODO: use env_logger
 use clap::{App, Arg, SubCommand};
 use kodi_recurse::AppArgument;
-use kodi_rust::{Kodi, PathAccessData, Setting};
+use kodi_rust::{
+    check_setting, AddonPolicy, Kodi, KodiParameters, PathAccessData, SettingLoader,
+    SettingOverrides,
+};
 
 use console::style;
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
+use std::error::Error;
+use std::path::PathBuf;
 
 use kodi_recurse::do_check;
 use kodi_recurse::do_mirror;
@@ -19,7 +23,7 @@ fn main() /* -> ExitCode */ { //TODO: use once stabilized
             Arg::with_name("config")
                 .short("c")
                 .long("config")
-                .help("path to the setting file")
+                .help("path to the setting file, in JSON or TOML (if its extension is .toml)")
                 .takes_value(true),
         )
         .arg(
@@ -91,27 +95,39 @@ fn main() /* -> ExitCode */ { //TODO: use once stabilized
             )
         .get_matches();
 
-    let mut setting = match app_m.value_of("config") {
-        Some(setting_path) => match File::open(setting_path) {
-            Ok(file) => match serde_json::from_reader(file) {
-                Ok(v) => v,
-                Err(err) => {
-                    println!(
-                        "can't parse the setting file at {} due to {:?}",
-                        setting_path, err
-                    );
-                    return
-                }
-            },
-            Err(err) => {
-                println!(
-                    "can't open the setting file at {} due to {:?}",
-                    setting_path, err
-                );
-                return
-            }
+    let loader = SettingLoader {
+        file: app_m.value_of("config").map(PathBuf::from),
+        overrides: SettingOverrides {
+            kodi_path: app_m.value_of("kodi-path").map(str::to_string),
+            ..SettingOverrides::default()
         },
-        None => Setting::default(),
+        ..SettingLoader::default()
+    };
+    let setting = match loader.load() {
+        Ok(v) => v,
+        Err(err) => {
+            match err.source() {
+                Some(source) => eprintln!("{}: {}", err, source),
+                None => eprintln!("{}", err),
+            };
+            return
+        }
+    };
+    for name in loader.unknown_env_variables(std::env::vars()) {
+        eprintln!(
+            "warning: the environment variable {} isn't a known setting, and is ignored",
+            name
+        );
+    }
+    let problems = check_setting(&setting);
+    for warning in &problems.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    for error in &problems.errors {
+        eprintln!("error: {}", error);
+    }
+    if !problems.errors.is_empty() {
+        return
     };
 
     let app_argument = AppArgument {
@@ -195,10 +211,6 @@ fn main() /* -> ExitCode */ { //TODO: use once stabilized
         },
     };
 
-    if let Some(kodi_path) = app_argument.value_of("kodi-path") {
-        setting.kodi_path = kodi_path.to_string();
-    };
-
     let jobs = match app_argument.value_of("jobs") {
         Some(jobs_str) => match jobs_str.parse() {
             Ok(v) => v,
@@ -217,7 +229,14 @@ fn main() /* -> ExitCode */ { //TODO: use once stabilized
     let kodi = {
         let mut k = Kodi::new(&setting.kodi_path, u64::MAX, 200);
         k.set_catch_stdout(!no_catch_output);
-        k.allowed_path = setting.allowed_path.clone();
+        // the plugins are called with the settings of the server, but every result is kept in the cache and every addon can be browsed
+        let mut parameters = KodiParameters::from_setting(&setting);
+        parameters.cache_time = u64::MAX;
+        parameters.addon_policy = AddonPolicy::default();
+        for plugin_setting in parameters.plugin_settings.values_mut() {
+            plugin_setting.cache_time = None;
+        }
+        k.reconfigure(parameters);
         k.sandbox_call(app_argument.is_present("use-sandbox"));
         k
     };
@@ -0,0 +1,70 @@
+use std::collections::HashSet;
+
+use crate::{get_addon_id, KodiError, Setting};
+
+/// Which addons can be called by [`crate::Kodi`]. Paths that aren't ``plugin://`` paths are always refused.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddonPolicy {
+    /// if set, only these addons can be called
+    pub allowed: Option<HashSet<String>>,
+    /// these addons can't be called, even if they are in ``allowed``
+    pub denied: HashSet<String>,
+}
+
+impl AddonPolicy {
+    /// create the [`AddonPolicy`] described by [`Setting::allowed_addons`], [`Setting::denied_addons`] and [`Setting::only_shown_plugins`]
+    pub fn from_setting(setting: &Setting) -> Self {
+        let mut allowed = setting
+            .allowed_addons
+            .as_ref()
+            .map(|addons| addons.iter().cloned().collect::<HashSet<_>>());
+        if setting.only_shown_plugins {
+            let allowed = allowed.get_or_insert_with(HashSet::new);
+            for (_, path) in setting
+                .plugins_to_show
+                .iter()
+                .chain(setting.search_entry_points.iter())
+            {
+                if let Some(addon_id) = get_addon_id(path) {
+                    allowed.insert(addon_id.to_string());
+                };
+            }
+        };
+        Self {
+            allowed,
+            denied: setting.denied_addons.iter().cloned().collect(),
+        }
+    }
+
+    /// check that the plugin at ``path`` can be called
+    ///
+    /// # Errors
+    ///
+    /// return [`KodiError::NotAPlugin`] if ``path`` isn't a ``plugin://`` path, and [`KodiError::AddonNotAllowed`] if its addon is refused
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{AddonPolicy, KodiError};
+    ///
+    /// let policy = AddonPolicy {
+    ///     allowed: Some(vec!["plugin.video.a".to_string(), "plugin.video.b".to_string()].into_iter().collect()),
+    ///     denied: vec!["plugin.video.b".to_string()].into_iter().collect(),
+    /// };
+    /// assert!(policy.check("plugin://plugin.video.a/show").is_ok());
+    /// assert!(matches!(policy.check("plugin://plugin.video.b/"), Err(KodiError::AddonNotAllowed(_))));
+    /// assert!(matches!(policy.check("plugin://plugin.video.c/"), Err(KodiError::AddonNotAllowed(_))));
+    /// assert!(matches!(AddonPolicy::default().check("/etc/passwd"), Err(KodiError::NotAPlugin(_))));
+    /// ```
+    pub fn check(&self, path: &str) -> Result<(), KodiError> {
+        let addon_id = get_addon_id(path).ok_or_else(|| KodiError::NotAPlugin(path.to_string()))?;
+        let allowed = match &self.allowed {
+            Some(allowed) => allowed.contains(addon_id),
+            None => true,
+        };
+        if !allowed || self.denied.contains(addon_id) {
+            return Err(KodiError::AddonNotAllowed(addon_id.to_string()));
+        };
+        Ok(())
+    }
+}
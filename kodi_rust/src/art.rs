@@ -0,0 +1,201 @@
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fs;
+use std::path::Path;
+
+/// The place an art is displayed at. Each context use the art categories of Kodi that fit it best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ArtContext {
+    /// the small illustration of an element in the list of a folder
+    Tile,
+    /// the image behind the whole page
+    Background,
+    /// the logo displayed above the title of a page
+    Logo,
+    /// the large illustration of a media, like the poster of a film
+    Poster,
+}
+
+impl ArtContext {
+    /// return the art categories usable in this context, from the most to the least fitting
+    pub fn categories(self) -> &'static [&'static str] {
+        match self {
+            Self::Tile => &["thumb", "poster", "landscape", "icon"],
+            Self::Background => &["fanart", "landscape"],
+            Self::Logo => &["clearlogo", "banner"],
+            Self::Poster => &["poster", "thumb", "landscape", "icon"],
+        }
+    }
+
+    /// return the art of the addon used when a page has none for this context. A tile has no fallback, so a folder doesn't show the same icon on each of its element.
+    pub fn addon_fallback(self) -> Option<&'static str> {
+        match self {
+            Self::Tile | Self::Logo => None,
+            Self::Background => Some("fanart"),
+            Self::Poster => Some("icon"),
+        }
+    }
+}
+
+/// return the id of the addon of a plugin path
+///
+/// # Example
+///
+/// ```
+/// use kodi_rust::get_addon_id;
+///
+/// assert_eq!(get_addon_id("plugin://plugin.video.test/show/1"), Some("plugin.video.test"));
+/// assert_eq!(get_addon_id("plugin://plugin.audio.test"), Some("plugin.audio.test"));
+/// assert_eq!(get_addon_id("plugin://../../etc"), None);
+/// assert_eq!(get_addon_id("/home/user/video.mkv"), None);
+/// ```
+pub fn get_addon_id(path: &str) -> Option<&str> {
+    let id = path.strip_prefix("plugin://")?.split('/').next()?;
+    if id.is_empty()
+        || id.starts_with('.')
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return None;
+    };
+    Some(id)
+}
+
+/// The arts of an addon, as the path of local files
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddonArt {
+    pub icon: Option<String>,
+    pub fanart: Option<String>,
+}
+
+impl AddonArt {
+    /// read the arts of the addon installed in ``addon_directory``. They are declared in the ``assets`` of its ``addon.xml``, or are the ``icon.png`` and ``fanart.jpg`` files of older addons. Only the files that exist inside ``addon_directory`` (once the symbolic links are resolved) are kept, as their canonical path.
+    pub fn read(addon_directory: &Path) -> Self {
+        let mut result = Self::default();
+        let addon_directory = match addon_directory.canonicalize() {
+            Ok(value) => value,
+            Err(_) => return result,
+        };
+        if let Ok(xml) = fs::read_to_string(addon_directory.join("addon.xml")) {
+            let mut reader = Reader::from_str(&xml);
+            let mut in_assets = false;
+            let mut current: Option<&str> = None;
+            loop {
+                match reader.read_event() {
+                    Ok(Event::Start(element)) => match element.name().as_ref() {
+                        b"assets" => in_assets = true,
+                        b"icon" if in_assets => current = Some("icon"),
+                        b"fanart" if in_assets => current = Some("fanart"),
+                        _ => (),
+                    },
+                    Ok(Event::Text(text)) => {
+                        if let (Some(category), Ok(text)) = (current.take(), text.unescape()) {
+                            let text = text.trim();
+                            if !text.is_empty() {
+                                result.set(category, &addon_directory, &addon_directory.join(text));
+                            };
+                        };
+                    }
+                    Ok(Event::End(element)) => match element.name().as_ref() {
+                        b"assets" => in_assets = false,
+                        _ => current = None,
+                    },
+                    Ok(Event::Eof) | Err(_) => break,
+                    _ => (),
+                }
+            }
+        };
+        if result.icon.is_none() {
+            result.set("icon", &addon_directory, &addon_directory.join("icon.png"));
+        };
+        if result.fanart.is_none() {
+            result.set(
+                "fanart",
+                &addon_directory,
+                &addon_directory.join("fanart.jpg"),
+            );
+        };
+        result
+    }
+
+    /// set the art of ``category`` to ``path``, if the file exist and is inside ``addon_directory`` (that must be canonical). An absolute path, a ``..`` or a symbolic link can otherwise lead outside of it.
+    fn set(&mut self, category: &str, addon_directory: &Path, path: &Path) {
+        let path = match path.canonicalize() {
+            Ok(path) if path.starts_with(addon_directory) && path.is_file() => path,
+            _ => return,
+        };
+        let path = Some(path.to_string_lossy().to_string());
+        match category {
+            "icon" => self.icon = path,
+            "fanart" => self.fanart = path,
+            _ => (),
+        };
+    }
+
+    /// return the path of the art of ``category`` (``icon`` or ``fanart``), if the addon has one
+    pub fn get(&self, category: &str) -> Option<&str> {
+        match category {
+            "icon" => self.icon.as_deref(),
+            "fanart" => self.fanart.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_read_addon_art() {
+    let addon_directory = tempfile::tempdir().unwrap();
+    let path = &addon_directory.path().canonicalize().unwrap();
+    fs::create_dir(path.join("resources")).unwrap();
+    fs::write(path.join("resources/icon.png"), b"").unwrap();
+    fs::write(path.join("fanart.jpg"), b"").unwrap();
+    fs::write(
+        path.join("addon.xml"),
+        r#"<addon id="plugin.video.test"><extension point="xbmc.addon.metadata">
+            <assets><icon>resources/icon.png</icon><fanart>resources/missing.jpg</fanart></assets>
+        </extension></addon>"#,
+    )
+    .unwrap();
+    let art = AddonArt::read(path);
+    assert_eq!(
+        art.get("icon"),
+        Some(path.join("resources/icon.png").to_str().unwrap())
+    );
+    // the fanart declared doesn't exist, so the one of older addons is used
+    assert_eq!(
+        art.get("fanart"),
+        Some(path.join("fanart.jpg").to_str().unwrap())
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_addon_art_outside() {
+    let root = tempfile::tempdir().unwrap();
+    let root = &root.path().canonicalize().unwrap();
+    let outside = root.join("outside.png");
+    fs::write(&outside, b"").unwrap();
+    let path = &root.join("plugin.video.test");
+    fs::create_dir(path).unwrap();
+    std::os::unix::fs::symlink(&outside, path.join("fanart.jpg")).unwrap();
+    fs::write(
+        path.join("addon.xml"),
+        format!(
+            "<addon><extension><assets><icon>{}</icon><fanart>../outside.png</fanart></assets></extension></addon>",
+            outside.display()
+        ),
+    )
+    .unwrap();
+    // the absolute path, the ``..`` and the symbolic link all lead outside of the addon
+    assert_eq!(AddonArt::read(path), AddonArt::default());
+
+    // a symbolic link inside the addon is followed
+    fs::remove_file(path.join("fanart.jpg")).unwrap();
+    fs::write(path.join("real.jpg"), b"").unwrap();
+    std::os::unix::fs::symlink(path.join("real.jpg"), path.join("fanart.jpg")).unwrap();
+    assert_eq!(
+        AddonArt::read(path).get("fanart"),
+        Some(path.join("real.jpg").to_str().unwrap())
+    );
+}
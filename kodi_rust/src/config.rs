@@ -0,0 +1,541 @@
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::{get_addon_id, AuthMode, Setting};
+
+/// the cache time (in seconds) used in the development mode, so changes to plugins are seen quickly
+pub const DEV_CACHE_TIME: u64 = 2;
+
+#[derive(Debug)]
+/// represent error that can happen while loading a [`Setting`] with a [`SettingLoader`]
+pub enum ConfigError {
+    CantReadFile(PathBuf, io::Error),
+    CantParseJson(PathBuf, serde_json::Error),
+    CantParseToml(PathBuf, toml::de::Error),
+    /// the file doesn't contain a JSON or TOML table
+    NotATable(PathBuf),
+    /// the file contain a key (whose name is contained) that doesn't correspond to a setting
+    UnknownKey(PathBuf, String),
+    /// the value of the environment variable (whose name is contained) isn't valid for its setting
+    InvalidEnvVariable(String, serde_json::Error),
+    /// the ``users_file`` of the setting can't be read
+    CantReadUsersFile(PathBuf, io::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CantReadFile(path, _) => {
+                write!(f, "can't read the setting file {}", path.display())
+            }
+            Self::CantParseJson(path, _) | Self::CantParseToml(path, _) => {
+                write!(f, "can't parse the setting file {}", path.display())
+            }
+            Self::NotATable(path) => write!(
+                f,
+                "the setting file {} doesn't contain a table of settings",
+                path.display()
+            ),
+            Self::UnknownKey(path, key) => write!(
+                f,
+                "the key {} of the setting file {} isn't a known setting",
+                key,
+                path.display()
+            ),
+            Self::InvalidEnvVariable(name, _) => write!(
+                f,
+                "the value of the environment variable {} is invalid",
+                name
+            ),
+            Self::CantReadUsersFile(path, _) => {
+                write!(f, "can't read the users file {}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CantReadFile(_, err) => Some(err),
+            Self::CantParseJson(_, err) => Some(err),
+            Self::CantParseToml(_, err) => Some(err),
+            Self::InvalidEnvVariable(_, err) => Some(err),
+            Self::CantReadUsersFile(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The settings that can be overridden from the command line. They have the highest priority.
+#[derive(Debug, Clone, Default)]
+pub struct SettingOverrides {
+    pub kodi_path: Option<String>,
+    pub listen_address: Option<IpAddr>,
+    pub port: Option<u16>,
+    pub cache_time: Option<u64>,
+    /// cache the results of every plugin for only [`DEV_CACHE_TIME`] seconds (unless ``cache_time`` is set)
+    pub dev_mode: bool,
+}
+
+impl SettingOverrides {
+    /// apply the overrides to ``setting``
+    pub fn apply(&self, setting: &mut Setting) {
+        if let Some(kodi_path) = &self.kodi_path {
+            setting.kodi_path = kodi_path.clone();
+        };
+        if let Some(listen_address) = self.listen_address {
+            setting.listen_address = Some(listen_address);
+        };
+        if let Some(port) = self.port {
+            setting.port = Some(port);
+        };
+        let cache_time = match (self.cache_time, self.dev_mode) {
+            (Some(cache_time), _) => Some(cache_time),
+            (None, true) => Some(DEV_CACHE_TIME),
+            (None, false) => None,
+        };
+        // the cache time of the command line apply to every plugin
+        if let Some(cache_time) = cache_time {
+            setting.cache_time = cache_time;
+            for plugin_setting in setting.plugins.values_mut() {
+                plugin_setting.cache_time = None;
+            }
+        };
+    }
+}
+
+/// Load a [`Setting`] by merging, from the lowest to the highest priority, the default setting, a JSON or TOML file, the environment variables and the overrides of the command line. The users of its ``users_file`` are then read (see [`Setting::load_users`]).
+///
+/// Each environment variable starting with ``env_prefix`` set the setting with the same name in upper case, like ``KODIONLINE_CACHE_TIME`` for ``cache_time``. Its value is read as JSON (for numbers, booleans, lists and maps) or as a plain string. The variables with this prefix that don't correspond to a setting are ignored (see [`SettingLoader::unknown_env_variables`]).
+#[derive(Debug, Clone)]
+pub struct SettingLoader {
+    /// the setting file. It is read as TOML if its extension is ``.toml``, and as JSON otherwise
+    pub file: Option<PathBuf>,
+    pub env_prefix: String,
+    pub overrides: SettingOverrides,
+}
+
+impl Default for SettingLoader {
+    fn default() -> Self {
+        Self {
+            file: None,
+            env_prefix: "KODIONLINE_".into(),
+            overrides: SettingOverrides::default(),
+        }
+    }
+}
+
+/// return the content of the setting file at ``path``, as a JSON value
+fn read_setting_file(path: &Path) -> Result<Value, ConfigError> {
+    let content =
+        fs::read_to_string(path).map_err(|err| ConfigError::CantReadFile(path.into(), err))?;
+    if path.extension() == Some(OsStr::new("toml")) {
+        toml::from_str(&content).map_err(|err| ConfigError::CantParseToml(path.into(), err))
+    } else {
+        serde_json::from_str(&content).map_err(|err| ConfigError::CantParseJson(path.into(), err))
+    }
+}
+
+/// return the default setting as a JSON table
+fn default_table() -> Map<String, Value> {
+    match serde_json::to_value(Setting::default()) {
+        Ok(Value::Object(table)) => table,
+        _ => Map::new(),
+    }
+}
+
+impl SettingLoader {
+    /// return the setting key set by the environment variable ``name``, if it start with ``env_prefix``
+    fn get_env_key(&self, name: &str) -> Option<String> {
+        name.strip_prefix(&self.env_prefix)
+            .map(|key| key.to_lowercase())
+    }
+
+    /// return the names of the variables of ``env`` that start with ``env_prefix`` but don't correspond to a setting. They are ignored by [`SettingLoader::load_with_env`], as they may be intended for another program.
+    pub fn unknown_env_variables(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Vec<String> {
+        let table = default_table();
+        let mut result: Vec<String> = env
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                self.get_env_key(name)
+                    .is_some_and(|key| !table.contains_key(&key))
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    /// load the setting, with the environment variables of this process
+    ///
+    /// # Errors
+    ///
+    /// return a [`ConfigError`] if the file (or the ``users_file`` it name) can't be read or parsed, or if an environment variable is invalid
+    pub fn load(&self) -> Result<Setting, ConfigError> {
+        self.load_with_env(std::env::vars())
+    }
+
+    /// load the setting, with ``env`` as the environment variables
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::SettingLoader;
+    ///
+    /// let loader = SettingLoader::default();
+    /// let setting = loader
+    ///     .load_with_env(vec![
+    ///         ("KODIONLINE_CACHE_TIME".to_string(), "60".to_string()),
+    ///         ("KODIONLINE_ADMIN_PASSWORD".to_string(), "1234".to_string()),
+    ///         ("HOME".to_string(), "/root".to_string()),
+    ///     ])
+    ///     .unwrap();
+    /// assert_eq!(setting.cache_time, 60);
+    /// assert_eq!(setting.admin_password.as_deref(), Some("1234"));
+    ///
+    /// // a variable that isn't a setting is ignored
+    /// let env = vec![("KODIONLINE_CACHE_TIM".to_string(), "60".to_string())];
+    /// assert_eq!(loader.load_with_env(env.clone()).unwrap().cache_time, 3600);
+    /// assert_eq!(loader.unknown_env_variables(env), vec!["KODIONLINE_CACHE_TIM".to_string()]);
+    /// ```
+    pub fn load_with_env(
+        &self,
+        env: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Setting, ConfigError> {
+        let mut table = default_table();
+        let known_keys: Vec<String> = table.keys().cloned().collect();
+
+        if let Some(path) = &self.file {
+            let file_table = match read_setting_file(path)? {
+                Value::Object(file_table) => file_table,
+                _ => return Err(ConfigError::NotATable(path.clone())),
+            };
+            // a misspelled key would otherwise be silently ignored
+            if let Some(key) = file_table.keys().find(|key| !known_keys.contains(key)) {
+                return Err(ConfigError::UnknownKey(path.clone(), key.clone()));
+            };
+            table.extend(file_table);
+        };
+        let mut setting: Setting =
+            serde_json::from_value(Value::Object(table.clone())).map_err(|err| {
+                ConfigError::CantParseJson(self.file.clone().unwrap_or_default(), err)
+            })?;
+
+        let mut env: Vec<(String, String, String)> = env
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = self.get_env_key(&name)?;
+                if known_keys.contains(&key) {
+                    Some((name, key, value))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        // apply them in a stable order
+        env.sort();
+        for (name, key, value) in env {
+            // try as JSON first, then as a string (so a password like ``1234`` stays a string). ``null`` is tried as a string first, so it doesn't unset a password.
+            let mut candidates = Vec::new();
+            match serde_json::from_str::<Value>(&value) {
+                Ok(Value::Null) => {
+                    candidates.push(Value::String(value));
+                    candidates.push(Value::Null);
+                }
+                Ok(parsed) => {
+                    candidates.push(parsed);
+                    candidates.push(Value::String(value));
+                }
+                Err(_) => candidates.push(Value::String(value)),
+            };
+            let mut first_error = None;
+            for candidate in candidates {
+                let mut new_table = table.clone();
+                new_table.insert(key.clone(), candidate);
+                match serde_json::from_value::<Setting>(Value::Object(new_table.clone())) {
+                    Ok(new_setting) => {
+                        table = new_table;
+                        setting = new_setting;
+                        first_error = None;
+                        break;
+                    }
+                    Err(err) => {
+                        first_error.get_or_insert(err);
+                    }
+                }
+            }
+            if let Some(err) = first_error {
+                return Err(ConfigError::InvalidEnvVariable(name, err));
+            };
+        }
+
+        self.overrides.apply(&mut setting);
+        if let Err(err) = setting.load_users() {
+            return Err(ConfigError::CantReadUsersFile(
+                setting.users_file.unwrap_or_default().into(),
+                err,
+            ));
+        };
+        Ok(setting)
+    }
+}
+
+/// return ``true`` if ``command`` is a path to an existing file, or the name of a program in the ``PATH``
+fn is_command_available(command: &str) -> bool {
+    if command.contains('/') {
+        return Path::new(command).is_file();
+    };
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|directory| directory.join(command).is_file())
+    })
+}
+
+/// The problems found in a [`Setting`] by [`check_setting`], as readable descriptions
+#[derive(Debug, Default)]
+pub struct SettingProblems {
+    /// the problems that prevent the setting from being used
+    pub errors: Vec<String>,
+    /// the problems that may be temporary or intended, like a plugin that isn't installed yet. The setting can still be used
+    pub warnings: Vec<String>,
+}
+
+/// check that ``setting`` is coherent with itself and with the system, returning every problem found
+///
+/// # Example
+///
+/// ```
+/// use kodi_rust::{check_setting, Setting};
+///
+/// let mut setting = Setting::default();
+/// setting.kodi_path = "/nonexistent".into();
+/// setting.allowed_path.push("/nonexistent/path".into());
+/// setting.plugins_to_show.push(("test".into(), "plugin://plugin.video.test/".into()));
+///
+/// let problems = check_setting(&setting);
+/// assert!(problems.errors.contains(&"kodi_path /nonexistent does not exist".to_string()));
+/// assert!(problems.warnings.contains(&"allowed_path /nonexistent/path does not exist".to_string()));
+/// assert!(problems.warnings.contains(&"plugin plugin.video.test in plugins_to_show is not installed".to_string()));
+/// ```
+pub fn check_setting(setting: &Setting) -> SettingProblems {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    let kodi_path = PathBuf::from(shellexpand::tilde(&setting.kodi_path).as_ref());
+    let addons_path = kodi_path.join("addons");
+    if !kodi_path.is_dir() {
+        errors.push(format!("kodi_path {} does not exist", setting.kodi_path));
+    };
+
+    for (list_name, list) in &[
+        ("plugins_to_show", &setting.plugins_to_show),
+        ("search_entry_points", &setting.search_entry_points),
+    ] {
+        for (label, path) in list.iter() {
+            match get_addon_id(path) {
+                Some(addon_id) => {
+                    if !addons_path.join(addon_id).is_dir() {
+                        warnings.push(format!(
+                            "plugin {} in {} is not installed",
+                            addon_id, list_name
+                        ));
+                    }
+                }
+                None => errors.push(format!(
+                    "the path {} of {} in {} isn't a plugin path",
+                    path, label, list_name
+                )),
+            };
+        }
+    }
+    for addon_id in setting.plugins.keys() {
+        if !addons_path.join(addon_id).is_dir() {
+            warnings.push(format!("plugin {} in plugins is not installed", addon_id));
+        };
+    }
+
+    let plugin_allowed_path = setting
+        .plugins
+        .values()
+        .filter_map(|plugin_setting| plugin_setting.allowed_path.as_ref());
+    for path in setting
+        .allowed_path
+        .iter()
+        .chain(plugin_allowed_path.flatten())
+    {
+        if !Path::new(path).exists() {
+            warnings.push(format!("allowed_path {} does not exist", path));
+        };
+    }
+    for directory in &setting.locale_directories {
+        if !Path::new(directory).is_dir() {
+            errors.push(format!("locale_directories {} does not exist", directory));
+        };
+    }
+    if let Some(database_path) = &setting.database_path {
+        let parent = Path::new(database_path)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        if matches!(parent, Some(parent) if !parent.is_dir()) {
+            errors.push(format!(
+                "the directory of database_path {} does not exist",
+                database_path
+            ));
+        };
+    }
+
+    let python_commands = std::iter::once(&setting.python_command).chain(
+        setting
+            .plugins
+            .values()
+            .filter_map(|plugin_setting| plugin_setting.python_command.as_ref()),
+    );
+    for python_command in python_commands {
+        if !is_command_available(python_command) {
+            errors.push(format!("python_command {} is not found", python_command));
+        };
+    }
+
+    if setting.require_state_token && setting.state_token_secret.is_none() {
+        errors.push("require_state_token is set, but not state_token_secret".to_string());
+    };
+    if setting.rate_limit_calls == Some(0) {
+        errors.push("rate_limit_calls must be at least 1".to_string());
+    };
+    if setting.max_running_plugins == Some(0) {
+        errors.push("max_running_plugins must be at least 1".to_string());
+    };
+    if let Some(users_file) = &setting.users_file {
+        if !Path::new(users_file).is_file() {
+            errors.push(format!("users_file {} does not exist", users_file));
+        };
+    };
+    if setting.auth_mode == AuthMode::Password
+        && setting.users_file.is_none()
+        && setting.database_path.is_none()
+    {
+        warnings.push(
+            "auth_mode is password, but neither users_file nor database_path is set, so the users would be lost at each restart"
+                .to_string(),
+        );
+    };
+    if setting.auth_mode == AuthMode::Proxy && setting.auth_proxies.is_empty() {
+        errors.push("auth_mode is proxy but auth_proxies is empty".to_string());
+    };
+    if setting.port == Some(0) {
+        errors.push("port must be at least 1".to_string());
+    };
+
+    SettingProblems { errors, warnings }
+}
+
+#[test]
+fn test_load_toml() {
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    writeln!(
+        file,
+        "kodi_path = \"/srv/kodi\"\nplugins_to_show = [[\"test\", \"plugin://plugin.video.test/\"]]\n\n[plugins.\"plugin.video.test\"]\ncache_time = 60"
+    )
+    .unwrap();
+    let loader = SettingLoader {
+        file: Some(file.path().into()),
+        overrides: SettingOverrides {
+            port: Some(8080),
+            ..SettingOverrides::default()
+        },
+        ..SettingLoader::default()
+    };
+
+    let setting = loader
+        .load_with_env(vec![(
+            "KODIONLINE_KODI_PATH".to_string(),
+            "/opt/kodi".to_string(),
+        )])
+        .unwrap();
+    assert_eq!(setting.kodi_path, "/opt/kodi");
+    assert_eq!(setting.plugins_to_show.len(), 1);
+    assert_eq!(setting.plugins["plugin.video.test"].cache_time, Some(60));
+    assert_eq!(setting.port, Some(8080));
+
+    // the development mode apply to every plugin
+    let dev_loader = SettingLoader {
+        overrides: SettingOverrides {
+            dev_mode: true,
+            ..SettingOverrides::default()
+        },
+        ..loader
+    };
+    let setting = dev_loader.load_with_env(Vec::new()).unwrap();
+    assert_eq!(setting.cache_time, DEV_CACHE_TIME);
+    assert_eq!(setting.plugins["plugin.video.test"].cache_time, None);
+}
+
+#[test]
+fn test_unknown_key() {
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    write!(file, "{{\"kodi_path\": \"/srv/kodi\", \"cache_tim\": 60}}").unwrap();
+    let loader = SettingLoader {
+        file: Some(file.path().into()),
+        ..SettingLoader::default()
+    };
+    assert!(matches!(
+        loader.load_with_env(Vec::new()),
+        Err(ConfigError::UnknownKey(_, key)) if key == "cache_tim"
+    ));
+
+    let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    write!(file, "[plugins.\"plugin.video.test\"]\ncache_tim = 60").unwrap();
+    let loader = SettingLoader {
+        file: Some(file.path().into()),
+        ..SettingLoader::default()
+    };
+    assert!(matches!(
+        loader.load_with_env(Vec::new()),
+        Err(ConfigError::CantParseToml(..)) | Err(ConfigError::CantParseJson(..))
+    ));
+}
+
+#[test]
+fn test_env_variables() {
+    let loader = SettingLoader::default();
+    let get_env = |values: &[(&str, &str)]| -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    };
+
+    // ``null`` is a valid password, but unset the other optional settings
+    let setting = loader
+        .load_with_env(get_env(&[
+            ("KODIONLINE_ADMIN_PASSWORD", "null"),
+            ("KODIONLINE_PORT", "null"),
+            ("KODIONLINE_SOMETHING_ELSE", "1"),
+        ]))
+        .unwrap();
+    assert_eq!(setting.admin_password.as_deref(), Some("null"));
+    assert_eq!(setting.port, None);
+
+    assert!(matches!(
+        loader.load_with_env(get_env(&[("KODIONLINE_CACHE_TIME", "soon")])),
+        Err(ConfigError::InvalidEnvVariable(name, _)) if name == "KODIONLINE_CACHE_TIME"
+    ));
+    assert!(matches!(
+        loader.load_with_env(get_env(&[("KODIONLINE_USERS_FILE", "/nonexistent/users")])),
+        Err(ConfigError::CantReadUsersFile(..))
+    ));
+}
@@ -1,9 +1,9 @@
-use crate::{extend_option, format_to_string};
+use crate::{extend_option, format_to_string, ArtContext};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(tag = "type")]
 pub enum KodiResult {
     Content(Page),
@@ -20,7 +20,9 @@ pub struct Keyboard {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Page {
     pub sub_content: Vec<SubContent>,
-    //pub sort_methods: Vec<u32>,
+    /// the ``SORT_METHOD_*`` constants declared by the plugin with ``xbmcplugin.addSortMethod``
+    #[serde(default)]
+    pub sort_methods: Vec<u32>,
     pub resolved_listitem: Option<ListItem>,
 }
 
@@ -48,14 +50,31 @@ pub struct ListItem {
 impl ListItem {
     #[must_use]
     pub fn get_thumb_category(&self) -> Option<&'static str> {
-        for key in &["thumb", "poster"] {
-            if let Some(art_url_option) = self.arts.get(*key) {
-                if art_url_option.is_some() {
-                    return Some(key);
-                }
-            }
-        }
-        None
+        self.get_art_category(ArtContext::Tile)
+    }
+
+    /// return the art category that fit ``context`` best among the arts of this [`ListItem`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{data::ListItem, ArtContext};
+    ///
+    /// let mut listitem = ListItem::default();
+    /// listitem.arts.insert("landscape".into(), Some("landscape.jpg".into()));
+    /// listitem.arts.insert("clearlogo".into(), None);
+    /// assert_eq!(listitem.get_art_category(ArtContext::Background), Some("landscape"));
+    /// assert_eq!(listitem.get_art_category(ArtContext::Logo), None);
+    /// listitem.arts.insert("fanart".into(), Some("fanart.jpg".into()));
+    /// assert_eq!(listitem.get_art_category(ArtContext::Background), Some("fanart"));
+    /// ```
+    #[must_use]
+    pub fn get_art_category(&self, context: ArtContext) -> Option<&'static str> {
+        context
+            .categories()
+            .iter()
+            .find(|category| matches!(self.arts.get(**category), Some(Some(_))))
+            .copied()
     }
 
     #[must_use]
@@ -106,6 +125,41 @@ impl ListItem {
         self.x_avalaible_languages.dedup();
         self.stream_info.extend(other.stream_info);
     }
+
+    /// fill the missing metadata of this [`ListItem`] with the one of a folder containing it higher in the hierarchy (like a show containing an episode).
+    ///
+    /// Only the metadata that are shared with the content of the folder (like arts, genre or season) are used, not the one specific to the folder (like its label or title).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::data::ListItem;
+    ///
+    /// let mut episode = ListItem::default();
+    /// episode.info.title = Some("episode 1".into());
+    /// let mut show = ListItem::default();
+    /// show.label = Some("the show".into());
+    /// show.info.title = Some("the show".into());
+    /// show.info.genre = Some("comedy".into());
+    /// show.arts.insert("poster".into(), Some("http://example.com/poster.png".into()));
+    ///
+    /// episode.extend_from_ancestor(show);
+    /// assert_eq!(episode.label, None);
+    /// assert_eq!(episode.info.title.as_deref(), Some("episode 1"));
+    /// assert_eq!(episode.info.genre.as_deref(), Some("comedy"));
+    /// assert!(episode.arts.contains_key("poster"));
+    /// ```
+    pub fn extend_from_ancestor(&mut self, ancestor: Self) {
+        for (category, art) in ancestor.arts {
+            self.arts.entry(category).or_insert(art);
+        }
+        extend_option(&mut self.info.plot, ancestor.info.plot);
+        extend_option(&mut self.info.genre, ancestor.info.genre);
+        extend_option(&mut self.info.season, ancestor.info.season);
+        extend_option(&mut self.info.album, ancestor.info.album);
+        extend_option(&mut self.info.artist, ancestor.info.artist);
+        extend_option(&mut self.info.year, ancestor.info.year);
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -134,9 +188,34 @@ pub struct Info {
     pub year: Option<i64>,
     #[serde(default)]
     pub duration: Option<u64>,
+    /// the date the element was added, as ``YYYY-MM-DD HH:MM:SS``
+    #[serde(default)]
+    pub dateadded: Option<String>,
 }
 
 impl Info {
+    /// return the season and episode number, formatted like ``S01E02``, or only the episode number (like ``E02``) if the season is unknown
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::data::Info;
+    ///
+    /// let mut info = Info::default();
+    /// assert_eq!(info.episode_code(), None);
+    /// info.episode = Some(2);
+    /// assert_eq!(info.episode_code().as_deref(), Some("E02"));
+    /// info.season = Some(1);
+    /// assert_eq!(info.episode_code().as_deref(), Some("S01E02"));
+    /// ```
+    pub fn episode_code(&self) -> Option<String> {
+        let episode = self.episode?;
+        Some(match self.season {
+            Some(season) => format!("S{:02}E{:02}", season, episode),
+            None => format!("E{:02}", episode),
+        })
+    }
+
     pub fn extend(&mut self, other: Self) {
         extend_option(&mut self.plot, other.plot);
         extend_option(&mut self.genre, other.genre);
@@ -150,6 +229,7 @@ impl Info {
         extend_option(&mut self.comment, other.comment);
         extend_option(&mut self.year, other.year);
         extend_option(&mut self.duration, other.duration);
+        extend_option(&mut self.dateadded, other.dateadded);
     }
 }
 
@@ -1,68 +1,121 @@
-use crate::{data::SubContent, input::encode_input, should_serve_file, PathAccessData, HTMLENCODE};
+use crate::{
+    data::SubContent, input::encode_input, should_serve_file, LinkState, PathAccessData, HTMLENCODE,
+};
 
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 
-pub fn get_media_link_subcontent(content: &SubContent, parent: &PathAccessData) -> String {
+/// return the [`LinkState`] of ``content``, a child of the page described by ``parent``
+fn get_sub_content_state(content: &SubContent, parent: &LinkState) -> LinkState {
+    parent.child(PathAccessData {
+        path: content.url.clone(),
+        input: Vec::new(),
+        config: parent.access.config.clone(),
+    })
+}
+
+pub fn get_media_link_subcontent(
+    content: &SubContent,
+    parent: &LinkState,
+    token_secret: Option<&str>,
+) -> String {
     let prefix = "/get_media?".to_string();
+    let state = get_sub_content_state(content, parent);
 
     if let Some(media_true_url) = &content.listitem.path {
-        get_data_link_resolved_url(&media_true_url, &content.url, Vec::new(), prefix, parent)
+        get_data_link_resolved_url(media_true_url, &state, prefix, token_secret)
     } else {
-        get_served_data_url(prefix, &content.url, Vec::new(), parent)
+        get_served_data_url(prefix, &state, token_secret)
     }
 }
 
+/// return the link to the media ``media_url``, found on the page described by ``current``
 pub fn get_media_link_resolved_url(
     media_url: &str,
-    media_path: &str,
-    input: Vec<String>,
-    parent: &PathAccessData,
+    current: &LinkState,
+    token_secret: Option<&str>,
 ) -> String {
     let prefix = "/get_media?".to_string();
-    get_data_link_resolved_url(media_url, media_path, input, prefix, parent)
+    get_data_link_resolved_url(media_url, current, prefix, token_secret)
 }
 
 pub fn get_art_link_subcontent(
     content: &SubContent,
     category: &str,
-    parent: &PathAccessData,
+    parent: &LinkState,
+    token_secret: Option<&str>,
 ) -> String {
     let prefix = format!("/get_art?category={}&", category);
+    let state = get_sub_content_state(content, parent);
 
     if let Some(Some(art_true_url)) = &content.listitem.arts.get(category) {
-        get_data_link_resolved_url(art_true_url, &content.url, Vec::new(), prefix, parent)
+        get_data_link_resolved_url(art_true_url, &state, prefix, token_secret)
     } else {
-        get_served_data_url(prefix, &content.url, Vec::new(), parent)
+        get_served_data_url(prefix, &state, token_secret)
     }
 }
 
+/// return the url of an art of ``content`` always served by kodionline, even if it is hosted on another site, optionally resized to ``width`` pixels
+pub fn get_proxied_art_link_subcontent(
+    content: &SubContent,
+    category: &str,
+    width: Option<u32>,
+    parent: &LinkState,
+    token_secret: Option<&str>,
+) -> String {
+    let prefix = match width {
+        Some(width) => format!("/get_art?category={}&width={}&", category, width),
+        None => format!("/get_art?category={}&", category),
+    };
+    get_served_data_url(
+        prefix,
+        &get_sub_content_state(content, parent),
+        token_secret,
+    )
+}
+
 pub fn get_data_link_resolved_url(
     media_url: &str,
-    media_path: &str,
-    input: Vec<String>,
+    state: &LinkState,
     prefix: String,
-    parent: &PathAccessData,
+    token_secret: Option<&str>,
 ) -> String {
     if should_serve_file(media_url) {
-        get_served_data_url(prefix, media_path, input, parent)
+        get_served_data_url(prefix, state, token_secret)
     } else {
         utf8_percent_encode(&media_url.to_string(), &HTMLENCODE).to_string()
     }
 }
 
+/// return the url of a data served by kodionline (a media or an art), found on the page described by ``state``.
+///
+/// If ``token_secret`` is set, the state is passed as a signed token in the ``s`` parameter (see [`LinkState::encode_token`]), otherwise it is passed as plain parameters.
 pub fn get_served_data_url(
     prefix: String,
-    path: &str,
-    input: Vec<String>,
-    parent: &PathAccessData,
+    state: &LinkState,
+    token_secret: Option<&str>,
 ) -> String {
+    if let Some(secret) = token_secret {
+        return format!("{}s={}", prefix, state.encode_token(secret));
+    };
     format!(
-        "{}path={}&input={}&parent_path={}&parent_input={}&c={}",
+        "{}path={}&input={}{}&c={}",
         prefix,
-        utf8_percent_encode(path, NON_ALPHANUMERIC),
-        utf8_percent_encode(&encode_input(&input), NON_ALPHANUMERIC),
-        utf8_percent_encode(&parent.path, NON_ALPHANUMERIC),
-        utf8_percent_encode(&encode_input(&parent.input), NON_ALPHANUMERIC),
-        &parent.config.encode_to_uri(),
+        utf8_percent_encode(&state.access.path, NON_ALPHANUMERIC),
+        utf8_percent_encode(&encode_input(&state.access.input), NON_ALPHANUMERIC),
+        if let Some(parent) = &state.parent {
+            format!(
+                "&parent_path={}&parent_input={}{}",
+                utf8_percent_encode(&parent.path, NON_ALPHANUMERIC),
+                utf8_percent_encode(&encode_input(&parent.input), NON_ALPHANUMERIC),
+                if !state.ancestors.is_empty() {
+                    format!("&a={}", LinkState::encode_ancestors(&state.ancestors))
+                } else {
+                    String::new()
+                }
+            )
+        } else {
+            String::new()
+        },
+        &state.access.config.encode_to_uri(),
     )
 }
@@ -1,22 +1,26 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
 use std::include_bytes;
 use std::io;
 use std::io::Write;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
 
-use subprocess::{Exec, ExitStatus, PopenError, Redirection};
+use subprocess::{Exec, ExitStatus, Popen, PopenError, Redirection};
 
 use tempfile::{tempdir, TempDir};
 
 use cached::Cached;
 use cached::TimedCache;
 
-use log::error;
-
-use crate::{data::KodiResult, PathAccessData};
+use crate::{
+    data::KodiResult, get_addon_id, AddonArt, AddonPolicy, CacheEntry, CallAdmission,
+    ConcurrencyLimit, InvocationRecord, Monitor, PathAccessData, PluginSetting, PluginStats,
+    RunningInvocation, SandboxProfile, Setting,
+};
 
 static KODI_INTEFACE_BIN: &[u8; 2728] = include_bytes!("../kodi_interface.py");
 
@@ -28,6 +32,26 @@ pub enum KodiError {
     CantCreateTemporyDir(io::Error),
     CantOpenResultFile(io::Error),
     CantParseResultFile(serde_json::Error),
+    /// too many plugins are running, and the queue is full or the call waited too long. Contain the time after which it can be retried
+    Busy(Duration),
+    /// the client made too many calls, as decided by a [`CallAdmission`]. Contain the time after which it can be retried
+    RateLimited(Duration),
+    /// the path isn't a ``plugin://`` path
+    NotAPlugin(String),
+    /// the addon (whose id is contained) is refused by the [`AddonPolicy`]
+    AddonNotAllowed(String),
+    /// the plugin was killed because it ran longer than the contained timeout
+    Timeout(Duration),
+}
+
+impl KodiError {
+    /// return the time after which the call can be retried, if it was refused because the server is overloaded
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Busy(retry_after) | Self::RateLimited(retry_after) => Some(*retry_after),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for KodiError {
@@ -46,6 +70,17 @@ impl fmt::Display for KodiError {
             Self::CantParseResultFile(_) => {
                 write!(f, "internal error: can't parse the result file")
             }
+            Self::Busy(_) => write!(f, "too many plugins are running"),
+            Self::RateLimited(_) => write!(f, "too many plugins were called by this client"),
+            Self::NotAPlugin(path) => write!(f, "{} isn't a plugin path", path),
+            Self::AddonNotAllowed(addon_id) => {
+                write!(f, "the addon {} isn't allowed on this server", addon_id)
+            }
+            Self::Timeout(timeout) => write!(
+                f,
+                "the plugin was killed after running for {} seconds",
+                timeout.as_secs()
+            ),
         }
     }
 }
@@ -62,6 +97,97 @@ impl Error for KodiError {
     }
 }
 
+/// The details of a call to [`Kodi::invoke_debug`]
+#[derive(Debug)]
+pub struct InvocationReport {
+    /// the command line python was started with, ``None`` if it wasn't started
+    pub command_line: Option<String>,
+    /// ``true`` if the result was taken from the cache, without calling the plugin
+    pub from_cache: bool,
+    pub duration: Duration,
+    pub exit_status: Option<ExitStatus>,
+    /// the merged stdout and stderr of python
+    pub log: Option<String>,
+    pub result: Result<KodiResult, KodiError>,
+}
+
+impl InvocationReport {
+    /// the report of an invocation that failed before python returned
+    fn failed(command_line: Option<String>, err: KodiError) -> Self {
+        Self {
+            command_line,
+            from_cache: false,
+            duration: Duration::default(),
+            exit_status: None,
+            log: None,
+            result: Err(err),
+        }
+    }
+}
+
+/// The settings a plugin is run with, once the [`PluginSetting`] of its addon is applied. See [`Kodi::get_invocation_setting`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvocationSetting {
+    /// the time (in seconds) its results are cached
+    pub cache_time: u64,
+    pub python_command: String,
+    /// the time after which it is killed, unlimited if ``None``
+    pub timeout: Option<Duration>,
+    pub sandbox: SandboxProfile,
+    /// the paths accessible in the sandbox, absolute
+    pub allowed_path: Vec<String>,
+}
+
+/// The parameters of a [`Kodi`] that can be changed while it is used, with [`Kodi::reconfigure`] or a [`KodiReloader`]
+#[derive(Debug, Clone)]
+pub struct KodiParameters {
+    pub python_command: String,
+    /// the time (in seconds) the results are cached
+    pub cache_time: u64,
+    /// the time after which a plugin is killed, unlimited if ``None``
+    pub timeout: Option<Duration>,
+    /// list of allowed path in the sandbox, absolute
+    pub allowed_path: Vec<String>,
+    pub addon_policy: AddonPolicy,
+    /// override of the parameters above for some addons, by addon id
+    pub plugin_settings: HashMap<String, PluginSetting>,
+}
+
+impl KodiParameters {
+    /// create the [`KodiParameters`] described by ``setting``
+    pub fn from_setting(setting: &Setting) -> Self {
+        Self {
+            python_command: setting.python_command.clone(),
+            cache_time: setting.cache_time,
+            timeout: setting.plugin_timeout.map(Duration::from_secs),
+            allowed_path: setting.allowed_path.clone(),
+            addon_policy: AddonPolicy::from_setting(setting),
+            plugin_settings: setting.plugins.clone(),
+        }
+    }
+
+    fn get_plugin_setting(&self, path: &str) -> Option<&PluginSetting> {
+        self.plugin_settings.get(get_addon_id(path)?)
+    }
+
+    /// return the time (in seconds) the results of the plugin ``path`` are cached
+    fn get_cache_time(&self, path: &str) -> u64 {
+        self.get_plugin_setting(path)
+            .and_then(|plugin_setting| plugin_setting.cache_time)
+            .unwrap_or(self.cache_time)
+    }
+
+    /// return the longest time a result can be cached, for the lifespan of the cache
+    fn longest_cache_time(&self) -> u64 {
+        self.plugin_settings
+            .values()
+            .filter_map(|plugin_setting| plugin_setting.cache_time)
+            .fold(self.cache_time, u64::max)
+    }
+}
+
+type KodiCache = TimedCache<PathAccessData, KodiResult>;
+
 #[derive(Debug)]
 /// represent a kodi/xbmc instance. Each [`Kodi`] instance have a configuration file associated,
 /// where kodi store various data, including plugin.
@@ -69,15 +195,124 @@ impl Error for KodiError {
 /// It also have a cache to store recent results. It can be called by multiple threads.
 pub struct Kodi {
     kodi_config_path: String,
-    cache: Mutex<TimedCache<PathAccessData, KodiResult>>,
-    python_command: String,
-    cache_time: u64,
-    cache_size: usize,
+    cache: Arc<Mutex<KodiCache>>,
+    /// the arts of the addons, by addon id, read once from their ``addon.xml`` (see [`Kodi::get_addon_art`])
+    addon_arts: Arc<Mutex<HashMap<String, AddonArt>>>,
+    parameters: Arc<RwLock<KodiParameters>>,
     catch_stdout: bool,
     sandbox_call: bool,
     global_tempdir: TempDir,
-    /// list of allowed path in the sandbox, absolute
-    pub allowed_path: Vec<String>,
+    monitor: Mutex<Monitor>,
+    concurrency: ConcurrencyLimit,
+}
+
+/// A handle to change the [`KodiParameters`] of a [`Kodi`] from another thread, obtained with [`Kodi::reloader`]
+#[derive(Debug, Clone)]
+pub struct KodiReloader {
+    cache: Arc<Mutex<KodiCache>>,
+    addon_arts: Arc<Mutex<HashMap<String, AddonArt>>>,
+    parameters: Arc<RwLock<KodiParameters>>,
+}
+
+impl KodiReloader {
+    /// replace the parameters of the [`Kodi`]. The cached results that can't be used anymore, because their addon isn't allowed or they are older than their new cache time, are removed and their number returned. The others are kept.
+    ///
+    /// The arts of the addons are read again, in case an addon was installed or updated.
+    pub fn reconfigure(&self, parameters: KodiParameters) -> usize {
+        // always lock the parameters before the cache
+        let mut current = write(&self.parameters);
+        let mut cache = lock(&self.cache);
+        cache.cache_set_lifespan(parameters.longest_cache_time());
+        let to_remove: Vec<PathAccessData> = cache
+            .get_store()
+            .iter()
+            .filter(|(access, (stored, _))| {
+                parameters.addon_policy.check(&access.path).is_err()
+                    || stored.elapsed().as_secs() >= parameters.get_cache_time(&access.path)
+            })
+            .map(|(access, _)| access.clone())
+            .collect();
+        for access in &to_remove {
+            cache.cache_remove(access);
+        }
+        *current = parameters;
+        lock(&self.addon_arts).clear();
+        to_remove.len()
+    }
+}
+
+/// lock ``mutex``, even if another thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// lock ``rwlock`` for reading, even if another thread panicked while holding it
+fn read<T>(rwlock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    rwlock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// lock ``rwlock`` for writing, even if another thread panicked while holding it
+fn write<T>(rwlock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    rwlock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// wait for the end of ``process``, returning its captured output, its exit status and whether it was killed because it ran longer than ``timeout``. The lock of ``process`` is only held for short durations, so it can be killed by another thread.
+fn wait_process(
+    process: &Mutex<Popen>,
+    timeout: Option<Duration>,
+) -> Result<(Option<String>, ExitStatus, bool), PopenError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut communicator = lock(process).communicate_start(None);
+    if let Some(timeout) = timeout {
+        communicator = communicator.limit_time(timeout);
+    };
+    let stdout = match communicator.read_string() {
+        Ok((stdout, _)) => stdout,
+        // keep what was written before the timeout, the process is killed below
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => err
+            .capture
+            .0
+            .map(|stdout| String::from_utf8_lossy(&stdout).into_owned()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut timed_out = false;
+    loop {
+        if let Some(exit_status) = lock(process).wait_timeout(Duration::from_millis(100))? {
+            return Ok((stdout, exit_status, timed_out));
+        }
+        if !timed_out && matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+            lock(process).kill()?;
+            timed_out = true;
+        };
+    }
+}
+
+/// wait for the end of ``process`` (killing it after ``timeout``), then read the result it wrote in ``result_file``. The exit status and the captured output of the process are also returned.
+fn wait_for_result(
+    process: &Mutex<Popen>,
+    result_file: &Path,
+    timeout: Option<Duration>,
+) -> (
+    Result<KodiResult, KodiError>,
+    Option<ExitStatus>,
+    Option<String>,
+) {
+    let (stdout, exit_status, timed_out) = match wait_process(process, timeout) {
+        Ok(value) => value,
+        Err(err) => return (Err(KodiError::CantCreateProcess(err)), None, None),
+    };
+    let result = if timed_out {
+        Err(KodiError::Timeout(timeout.unwrap_or_default()))
+    } else if exit_status == ExitStatus::Exited(0) {
+        File::open(result_file)
+            .map_err(KodiError::CantOpenResultFile)
+            .and_then(|json_file| {
+                serde_json::from_reader(json_file).map_err(KodiError::CantParseResultFile)
+            })
+    } else {
+        Err(KodiError::NonZeroResult(stdout.clone(), exit_status))
+    };
+    (result, Some(exit_status), stdout)
 }
 
 impl Kodi {
@@ -102,22 +337,215 @@ impl Kodi {
 
         Self {
             kodi_config_path: shellexpand::tilde(path).into(),
-            cache: Mutex::new(TimedCache::with_lifespan_and_capacity(
+            cache: Arc::new(Mutex::new(TimedCache::with_lifespan_and_capacity(
                 cache_time, cache_size,
-            )),
-            python_command: "python3".into(),
-            cache_time,
-            cache_size,
+            ))),
+            addon_arts: Arc::new(Mutex::new(HashMap::new())),
+            parameters: Arc::new(RwLock::new(KodiParameters {
+                python_command: "python3".into(),
+                cache_time,
+                timeout: None,
+                allowed_path: Vec::new(),
+                addon_policy: AddonPolicy::default(),
+                plugin_settings: HashMap::new(),
+            })),
             catch_stdout: true,
             sandbox_call: true,
             global_tempdir,
-            allowed_path: Vec::new(),
+            monitor: Mutex::new(Monitor::default()),
+            concurrency: ConcurrencyLimit::default(),
         }
     }
 
+    /// return a handle to change the parameters of this [`Kodi`] from another thread
+    pub fn reloader(&self) -> KodiReloader {
+        KodiReloader {
+            cache: self.cache.clone(),
+            addon_arts: self.addon_arts.clone(),
+            parameters: self.parameters.clone(),
+        }
+    }
+
+    /// replace the parameters, keeping the cached results that can still be used. See [`KodiReloader::reconfigure`].
+    pub fn reconfigure(&self, parameters: KodiParameters) -> usize {
+        self.reloader().reconfigure(parameters)
+    }
+
+    /// return a copy of the current parameters
+    pub fn parameters(&self) -> KodiParameters {
+        read(&self.parameters).clone()
+    }
+
+    /// set the time after which a plugin is killed (unlimited by default)
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        write(&self.parameters).timeout = timeout;
+    }
+
+    /// set the settings overriding the global ones for the plugins of some addons, by addon id
+    pub fn set_plugin_settings(&mut self, plugin_settings: HashMap<String, PluginSetting>) {
+        let mut parameters = self.parameters();
+        parameters.plugin_settings = plugin_settings;
+        self.reconfigure(parameters);
+    }
+
+    /// set the list of absolute paths accessible in the sandbox
+    pub fn set_allowed_path(&mut self, allowed_path: Vec<String>) {
+        write(&self.parameters).allowed_path = allowed_path;
+    }
+
+    /// return the settings the plugin ``path`` is run with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{Kodi, PluginSetting, SandboxProfile};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut kodi = Kodi::new("~/.kodi", 3600, 500);
+    /// let mut plugin_settings = HashMap::new();
+    /// plugin_settings.insert("plugin.video.a".to_string(), PluginSetting {
+    ///     cache_time: Some(60),
+    ///     sandbox: Some(SandboxProfile::Offline),
+    ///     ..PluginSetting::default()
+    /// });
+    /// kodi.set_plugin_settings(plugin_settings);
+    ///
+    /// let overriden = kodi.get_invocation_setting("plugin://plugin.video.a/");
+    /// assert_eq!(overriden.cache_time, 60);
+    /// assert_eq!(overriden.sandbox, SandboxProfile::Offline);
+    /// let global = kodi.get_invocation_setting("plugin://plugin.video.b/");
+    /// assert_eq!(global.cache_time, 3600);
+    /// assert_eq!(global.sandbox, SandboxProfile::Default);
+    /// ```
+    pub fn get_invocation_setting(&self, path: &str) -> InvocationSetting {
+        let parameters = read(&self.parameters);
+        let plugin_setting = parameters
+            .get_plugin_setting(path)
+            .cloned()
+            .unwrap_or_default();
+        InvocationSetting {
+            cache_time: plugin_setting.cache_time.unwrap_or(parameters.cache_time),
+            python_command: plugin_setting
+                .python_command
+                .unwrap_or_else(|| parameters.python_command.clone()),
+            timeout: plugin_setting
+                .timeout
+                .map(Duration::from_secs)
+                .or(parameters.timeout),
+            sandbox: plugin_setting.sandbox.unwrap_or(if self.sandbox_call {
+                SandboxProfile::Default
+            } else {
+                SandboxProfile::Disabled
+            }),
+            allowed_path: plugin_setting
+                .allowed_path
+                .unwrap_or_else(|| parameters.allowed_path.clone()),
+        }
+    }
+
+    /// return the result of ``access`` from the cache, if it is younger than the cache time of its plugin
+    fn get_cached(&self, access: &PathAccessData, cache_time: u64) -> Option<KodiResult> {
+        match lock(&self.cache).get_store().get(access) {
+            Some((stored, result)) if stored.elapsed().as_secs() < cache_time => {
+                Some(result.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// return the result of ``access`` if it is in the cache, without ever calling the plugin
+    pub fn get_from_cache(&self, access: &PathAccessData) -> Option<KodiResult> {
+        self.check_addon(&access.path).ok()?;
+        let setting = self.get_invocation_setting(&access.path);
+        self.get_cached(access, setting.cache_time)
+    }
+
+    /// set the addons that can be called (every addon by default)
+    pub fn set_addon_policy(&mut self, addon_policy: AddonPolicy) {
+        write(&self.parameters).addon_policy = addon_policy;
+    }
+
+    /// check that the plugin at ``path`` can be called, according to the [`AddonPolicy`]
+    pub fn check_addon(&self, path: &str) -> Result<(), KodiError> {
+        read(&self.parameters).addon_policy.check(path)
+    }
+
+    /// set the limit on the number of plugins running at the same time (unlimited by default)
+    pub fn set_concurrency_limit(&mut self, concurrency: ConcurrencyLimit) {
+        self.concurrency = concurrency;
+    }
+
+    /// set the number of finished invocations kept for [`Kodi::invocation_history`] (default to [`crate::DEFAULT_HISTORY_SIZE`])
+    pub fn set_history_size(&mut self, history_size: usize) {
+        lock(&self.monitor).set_history_size(history_size);
+    }
+
+    /// return the last finished invocations of plugins, the most recent first
+    pub fn invocation_history(&self) -> Vec<InvocationRecord> {
+        lock(&self.monitor).history()
+    }
+
+    /// return the invocations of plugins that are currently running
+    pub fn running_invocations(&self) -> Vec<RunningInvocation> {
+        lock(&self.monitor).running()
+    }
+
+    /// return the number of successful and failed invocations of each plugin
+    pub fn plugin_stats(&self) -> Vec<(String, PluginStats)> {
+        lock(&self.monitor).plugin_stats()
+    }
+
+    /// kill the python process of the running invocation ``id``. Return ``false`` if it isn't running anymore or can't be killed.
+    pub fn kill_invocation(&self, id: u64) -> bool {
+        let process = lock(&self.monitor).get_process(id);
+        match process {
+            Some(process) => lock(&process).kill().is_ok(),
+            None => false,
+        }
+    }
+
+    /// return every result currently in the cache, the most recent first
+    pub fn cache_entries(&self) -> Vec<CacheEntry> {
+        // always lock the parameters before the cache
+        let parameters = read(&self.parameters);
+        let cache = lock(&self.cache);
+        let mut result: Vec<CacheEntry> = cache
+            .get_store()
+            .iter()
+            .map(|(access, (stored, result))| CacheEntry {
+                access: access.clone(),
+                age: stored.elapsed(),
+                result: result.clone(),
+            })
+            .filter(|entry| entry.age.as_secs() < parameters.get_cache_time(&entry.access.path))
+            .collect();
+        result.sort_by_key(|entry| entry.age);
+        result
+    }
+
+    /// remove the result of ``access`` from the cache. Return ``false`` if it wasn't cached.
+    pub fn evict_cache_entry(&self, access: &PathAccessData) -> bool {
+        lock(&self.cache).cache_remove(access).is_some()
+    }
+
+    /// remove every result of the addon ``addon_id`` from the cache, returning the number of removed results
+    pub fn evict_plugin_cache(&self, addon_id: &str) -> usize {
+        let mut cache = lock(&self.cache);
+        let to_remove: Vec<PathAccessData> = cache
+            .get_store()
+            .keys()
+            .filter(|access| get_addon_id(&access.path) == Some(addon_id))
+            .cloned()
+            .collect();
+        for access in &to_remove {
+            cache.cache_remove(access);
+        }
+        to_remove.len()
+    }
+
     /// set the command this program will use to call python. Common value should include ``python2`` and ``python3`` (default to ``python2`` until kodi 19)
     pub fn set_python_command(&mut self, command: String) {
-        self.python_command = command;
+        write(&self.parameters).python_command = command;
     }
 
     /// set to ``false`` to display stdout of called python as they are computed in the terminal, or ``true`` to not display them.
@@ -127,6 +555,39 @@ impl Kodi {
         self.catch_stdout = catch_stdout;
     }
 
+    /// return the directory of kodi, containing the addons
+    pub fn get_kodi_config_path(&self) -> &str {
+        &self.kodi_config_path
+    }
+
+    /// return the icon and the fanart of the addon of the plugin ``path``, as declared in its ``addon.xml``. The ``addon.xml`` of each installed addon is only read once.
+    pub fn get_addon_art(&self, path: &str) -> AddonArt {
+        let addon_id = match get_addon_id(path) {
+            Some(addon_id) => addon_id,
+            None => return AddonArt::default(),
+        };
+        if let Some(art) = lock(&self.addon_arts).get(addon_id) {
+            return art.clone();
+        };
+        // the ids of the addons that aren't installed aren't kept, as any id can be asked for
+        if !self.is_addon_installed(addon_id) {
+            return AddonArt::default();
+        };
+        let art = AddonArt::read(&self.get_addon_directory(addon_id));
+        lock(&self.addon_arts).insert(addon_id.to_string(), art.clone());
+        art
+    }
+
+    fn get_addon_directory(&self, addon_id: &str) -> PathBuf {
+        PathBuf::from(&self.kodi_config_path)
+            .join("addons")
+            .join(addon_id)
+    }
+
+    fn is_addon_installed(&self, addon_id: &str) -> bool {
+        self.get_addon_directory(addon_id).is_dir()
+    }
+
     pub fn sandbox_call(&mut self, sandbox_call: bool) {
         self.sandbox_call = sandbox_call;
     }
@@ -165,7 +626,7 @@ impl Kodi {
     ///
     /// It will use the kodi-dl library to do this, and will sandbox the call (not actually implemented)
     ///
-    /// this function also use a timed cache, that will remove element older than the time specified at initialisation.
+    /// this function also use a timed cache, that will remove element older than the time specified at initialisation, or than the cache time of the [`PluginSetting`] of the addon.
     ///
     /// If the plugin want to get user input, you can pass a vec to expected_input that contain all the input (in the form of a string)
     ///
@@ -173,19 +634,101 @@ impl Kodi {
     ///
     /// this function return a [`KodiError`] when an error occur. there may be multiple kind of error, the most important one [`KodiError::CallError`] for when the addon crashed.
     pub fn invoke_sandbox(&self, access: &PathAccessData) -> Result<KodiResult, KodiError> {
-        match self.cache.lock() {
-            Ok(mut cache) => {
-                if let Some(cached_value) = cache.cache_get(&access) {
-                    return Ok(cached_value.clone());
+        self.invoke(access, true, self.catch_stdout, None).result
+    }
+
+    /// Get the data for a kodi addon path like [`Kodi::invoke_sandbox`], on behalf of a client. If the result isn't cached, the plugin is only run if ``admission`` accept it.
+    pub fn invoke_sandbox_for(
+        &self,
+        access: &PathAccessData,
+        admission: &dyn CallAdmission,
+    ) -> Result<KodiResult, KodiError> {
+        self.invoke(access, true, self.catch_stdout, Some(admission))
+            .result
+    }
+
+    /// Get the data for a kodi addon path like [`Kodi::invoke_sandbox`], but return the details of the invocation too. The output of python is always captured.
+    ///
+    /// If ``bypass_cache`` is true, the plugin is called even if its result is in the cache. The new result then replace the cached one. As with [`Kodi::invoke_sandbox_for`], the plugin is only run if ``admission`` (if any) accept it.
+    pub fn invoke_debug(
+        &self,
+        access: &PathAccessData,
+        bypass_cache: bool,
+        admission: Option<&dyn CallAdmission>,
+    ) -> InvocationReport {
+        self.invoke(access, !bypass_cache, true, admission)
+    }
+
+    fn invoke(
+        &self,
+        access: &PathAccessData,
+        use_cache: bool,
+        catch_stdout: bool,
+        admission: Option<&dyn CallAdmission>,
+    ) -> InvocationReport {
+        let started = Instant::now();
+        if let Err(err) = self.check_addon(&access.path).and_then(|()| {
+            admission.map_or(Ok(()), |admission| admission.check_addon(&access.path))
+        }) {
+            return InvocationReport::failed(None, err);
+        };
+        let setting = self.get_invocation_setting(&access.path);
+        if use_cache {
+            if let Some(cached_value) = self.get_cached(access, setting.cache_time) {
+                return InvocationReport {
+                    command_line: None,
+                    from_cache: true,
+                    duration: started.elapsed(),
+                    exit_status: None,
+                    log: None,
+                    result: Ok(cached_value),
+                };
+            }
+        };
+
+        let mut report = match admission.map_or(Ok(()), |admission| admission.admit()) {
+            Ok(()) => {
+                let mut run = || self.call_plugin(access, &setting, catch_stdout);
+                match admission {
+                    Some(admission) => admission.run_blocking(&mut run),
+                    None => run(),
                 }
             }
-            Err(err) => error!("the cache lock is poisoned: {:?}", err),
+            Err(err) => InvocationReport::failed(None, err),
         };
+        report.duration = started.elapsed();
+        match &report.result {
+            Ok(result) => {
+                lock(&self.cache).cache_set(access.clone(), result.clone());
+            }
+            Err(err) if err.retry_after().is_some() => {
+                if let Some(admission) = admission {
+                    admission.refused(err);
+                };
+            }
+            Err(_) => (),
+        };
+        report
+    }
 
+    /// run the plugin for ``access`` in a new python process with ``setting``, without using the cache
+    fn call_plugin(
+        &self,
+        access: &PathAccessData,
+        setting: &InvocationSetting,
+        catch_stdout: bool,
+    ) -> InvocationReport {
+        // released when the plugin end
+        let _permit = match self.concurrency.acquire() {
+            Ok(permit) => permit,
+            Err(err) => return InvocationReport::failed(None, err),
+        };
         //TODO: make this use the sandbox
         let tempory_folder = match tempdir() {
             Ok(value) => value,
-            Err(err) => return Err(KodiError::CantCreateTemporyDir(err)),
+            Err(err) => {
+                return InvocationReport::failed(None, KodiError::CantCreateTemporyDir(err))
+            }
         };
 
         let result_dir: PathBuf = tempory_folder.path().into(); // don't use into_path() to don't persist it
@@ -194,7 +737,7 @@ impl Kodi {
 
         let arguments = self.get_arguments(&result_file.to_string_lossy(), &access);
 
-        let mut to_invoke = if self.sandbox_call {
+        let mut to_invoke = if setting.sandbox != SandboxProfile::Disabled {
             let mut bwrap_invoke = Exec::cmd("bwrap");
             let mut kodi_addon_folder = self.kodi_config_path.clone();
             kodi_addon_folder.push_str("/addons");
@@ -216,7 +759,7 @@ impl Kodi {
             for folder in python_path_splited {
                 bwrap_invoke = bwrap_invoke.arg("--ro-bind-try").arg(folder).arg(folder);
             }
-            for folder in &self.allowed_path {
+            for folder in &setting.allowed_path {
                 bwrap_invoke = bwrap_invoke.arg("--ro-bind-try").arg(folder).arg(folder);
             }
             let result_dir_str = result_dir.to_str().unwrap();
@@ -224,15 +767,19 @@ impl Kodi {
                 .arg("--bind")
                 .arg(result_dir_str)
                 .arg(result_dir_str);
-            bwrap_invoke = bwrap_invoke.arg("--unshare-all").arg("--share-net");
-            bwrap_invoke.arg(&self.python_command)
+            bwrap_invoke = bwrap_invoke.arg("--unshare-all");
+            if setting.sandbox != SandboxProfile::Offline {
+                bwrap_invoke = bwrap_invoke.arg("--share-net");
+            };
+            bwrap_invoke = bwrap_invoke.arg("--die-with-parent");
+            bwrap_invoke.arg(&setting.python_command)
         } else {
-            Exec::cmd(&self.python_command)
+            Exec::cmd(&setting.python_command)
         };
 
         println!("{:?}", to_invoke);
 
-        if self.catch_stdout {
+        if catch_stdout {
             to_invoke = to_invoke
                 .stdout(Redirection::Pipe)
                 .stderr(Redirection::Merge);
@@ -241,38 +788,123 @@ impl Kodi {
         for arg in arguments {
             to_invoke = to_invoke.arg(arg);
         }
+        let command_line = to_invoke.to_cmdline_lossy();
 
-        let (stdout, exit_status) = if self.catch_stdout {
-            let captured = to_invoke.capture().map_err(KodiError::CantCreateProcess)?;
-            (Some(captured.stdout_str()), captured.exit_status)
-        } else {
-            (
-                None,
-                to_invoke.join().map_err(KodiError::CantCreateProcess)?,
-            )
+        let process = match to_invoke.popen() {
+            Ok(process) => Arc::new(Mutex::new(process)),
+            Err(err) => {
+                return InvocationReport::failed(
+                    Some(command_line),
+                    KodiError::CantCreateProcess(err),
+                )
+            }
         };
+        let invocation_id = lock(&self.monitor).start(access, process.clone());
+        let (result, exit_status, log) = wait_for_result(&process, &result_file, setting.timeout);
+        // the statistics are only kept for the installed addons, as any id can be asked for
+        let record_stats =
+            get_addon_id(&access.path).is_some_and(|addon_id| self.is_addon_installed(addon_id));
+        lock(&self.monitor).finish(
+            invocation_id,
+            exit_status.map(|exit_status| format!("{:?}", exit_status)),
+            result.is_ok(),
+            log.clone(),
+            record_stats,
+        );
+        InvocationReport {
+            command_line: Some(command_line),
+            from_cache: false,
+            duration: Duration::default(),
+            exit_status,
+            log,
+            result,
+        }
+    }
+}
 
-        if exit_status != ExitStatus::Exited(0) {
-            return Err(KodiError::NonZeroResult(stdout, exit_status));
-        };
+#[test]
+fn test_reconfigure_keep_cache() {
+    use crate::UserConfig;
 
-        let json_file = match File::open(&result_file) {
-            Ok(value) => value,
-            Err(err) => return Err(KodiError::CantOpenResultFile(err)),
-        };
+    let kodi = Kodi::new("/nonexistent", 3600, 100);
+    let access = |addon_id: &str| {
+        PathAccessData::new(
+            format!("plugin://{}/", addon_id),
+            None,
+            UserConfig::new_empty(),
+        )
+    };
+    let result = KodiResult::Keyboard(crate::data::Keyboard {
+        default: None,
+        heading: None,
+        hidden: false,
+    });
+    for addon_id in &[
+        "plugin.video.kept",
+        "plugin.video.denied",
+        "plugin.video.expired",
+    ] {
+        lock(&kodi.cache).cache_set(access(addon_id), result.clone());
+    }
 
-        let result: KodiResult = match serde_json::from_reader(json_file) {
-            Ok(value) => value,
-            Err(err) => return Err(KodiError::CantParseResultFile(err)),
-        };
+    let mut parameters = kodi.parameters();
+    parameters
+        .addon_policy
+        .denied
+        .insert("plugin.video.denied".into());
+    parameters.plugin_settings.insert(
+        "plugin.video.expired".into(),
+        PluginSetting {
+            cache_time: Some(0),
+            ..PluginSetting::default()
+        },
+    );
+    assert_eq!(kodi.reloader().reconfigure(parameters), 2);
 
-        match self.cache.lock() {
-            Ok(mut cache) => {
-                cache.cache_set(access.clone(), result.clone());
-            }
-            Err(err) => error!("the cache lock is poisoned: {:?}", err),
-        };
+    let cached: Vec<_> = kodi
+        .cache_entries()
+        .into_iter()
+        .map(|entry| entry.access.path)
+        .collect();
+    assert_eq!(cached, vec!["plugin://plugin.video.kept/".to_string()]);
+    assert!(kodi.get_from_cache(&access("plugin.video.kept")).is_some());
+}
 
-        Ok(result)
-    }
+#[test]
+fn test_addon_art_cache() {
+    let kodi_directory = tempdir().unwrap();
+    let addon_directory = kodi_directory
+        .path()
+        .join("addons")
+        .join("plugin.video.art");
+    std::fs::create_dir_all(&addon_directory).unwrap();
+    let icon_path = addon_directory.join("icon.png");
+    File::create(&icon_path).unwrap();
+
+    let kodi = Kodi::new(kodi_directory.path().to_str().unwrap(), 3600, 10);
+    let expected = Some(
+        icon_path
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+    );
+    assert_eq!(
+        kodi.get_addon_art("plugin://plugin.video.art/").icon,
+        expected
+    );
+    assert_eq!(
+        kodi.get_addon_art("plugin://plugin.video.missing/"),
+        AddonArt::default()
+    );
+    assert_eq!(lock(&kodi.addon_arts).len(), 1);
+
+    // the art is read from the cache until the next reconfiguration
+    std::fs::remove_file(&icon_path).unwrap();
+    assert_eq!(
+        kodi.get_addon_art("plugin://plugin.video.art/").icon,
+        expected
+    );
+    kodi.reconfigure(kodi.parameters());
+    assert_eq!(kodi.get_addon_art("plugin://plugin.video.art/").icon, None);
 }
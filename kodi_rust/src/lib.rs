@@ -1,7 +1,9 @@
 #![allow(clippy::module_name_repetitions)]
 
 mod kodi;
-pub use kodi::{Kodi, KodiError};
+pub use kodi::{
+    InvocationReport, InvocationSetting, Kodi, KodiError, KodiParameters, KodiReloader,
+};
 
 pub mod data;
 
@@ -11,7 +13,12 @@ pub use format::format_to_string;
 pub mod input;
 
 mod setting;
-pub use setting::Setting;
+pub use setting::{ArtMode, AuthMode, PluginSetting, SandboxProfile, Setting};
+
+mod config;
+pub use config::{
+    check_setting, ConfigError, SettingLoader, SettingOverrides, SettingProblems, DEV_CACHE_TIME,
+};
 
 mod pathaccessdata;
 pub use pathaccessdata::{PathAccessData, PathAccessFormat};
@@ -19,8 +26,28 @@ pub use pathaccessdata::{PathAccessData, PathAccessFormat};
 mod getlink;
 pub use getlink::*;
 
+mod link_state;
+pub use link_state::{LinkQuery, LinkState, LinkStateError, MAX_ANCESTORS};
+
+mod listing;
+pub use listing::{filter_sub_content, group_by_season, is_episode_list, SortMethod};
+
+mod monitor;
+pub use monitor::{
+    CacheEntry, InvocationRecord, Monitor, PluginStats, RunningInvocation, DEFAULT_HISTORY_SIZE,
+};
+
+mod limit;
+pub use limit::{CallAdmission, ConcurrencyLimit, ConcurrencyPermit};
+
+mod addon_policy;
+pub use addon_policy::AddonPolicy;
+
+mod art;
+pub use art::{get_addon_id, AddonArt, ArtContext};
+
 mod user_config;
-pub use user_config::{OverridableVec, UserConfig};
+pub use user_config::{MergeMode, OverridableVec, UserConfig};
 
 // local use
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
@@ -92,22 +119,24 @@ fn test_encode_url() {
 use data::{KodiResult, SubContent};
 use log::error;
 
-pub fn get_sub_content_from_parent(
+/// return the content of the page at ``parent_access``, logging the error if it isn't a folder
+fn get_parent_sub_content(
     kodi: &Kodi,
+    admission: &dyn CallAdmission,
     parent_access: &PathAccessData,
-    child_path: &str,
-) -> Option<SubContent> {
-    match kodi.invoke_sandbox(&parent_access) {
-        Ok(KodiResult::Content(parent_page)) => {
-            let mut result = None;
-            for sub_content in parent_page.sub_content {
-                if sub_content.url == child_path {
-                    result = Some(sub_content);
-                    break;
-                };
-            }
-            result
-        }
+) -> Option<Vec<SubContent>> {
+    parse_parent_result(
+        kodi.invoke_sandbox_for(parent_access, admission),
+        parent_access,
+    )
+}
+
+fn parse_parent_result(
+    result: Result<KodiResult, KodiError>,
+    parent_access: &PathAccessData,
+) -> Option<Vec<SubContent>> {
+    match result {
+        Ok(KodiResult::Content(parent_page)) => Some(parent_page.sub_content),
         Ok(result) => {
             error!(
                 "an input was asked while asking for the parent path {} (result: {:?})",
@@ -124,3 +153,126 @@ pub fn get_sub_content_from_parent(
         }
     }
 }
+
+pub fn get_sub_content_from_parent(
+    kodi: &Kodi,
+    admission: &dyn CallAdmission,
+    parent_access: &PathAccessData,
+    child_path: &str,
+) -> Option<SubContent> {
+    get_parent_sub_content(kodi, admission, parent_access)?
+        .into_iter()
+        .find(|sub_content| sub_content.url == child_path)
+}
+
+/// The playable elements just before and after an element of a folder
+#[derive(Debug, Default)]
+pub struct Siblings {
+    pub previous: Option<SubContent>,
+    pub next: Option<SubContent>,
+}
+
+impl Siblings {
+    /// find the playable siblings of ``child_path`` in ``sub_content``, the content of its parent. Folders and other non playable elements are skipped.
+    pub fn find(sub_content: Vec<SubContent>, child_path: &str) -> Self {
+        let mut playable = sub_content.into_iter().filter(|sub_content| {
+            sub_content.listitem.is_playable() || sub_content.url == child_path
+        });
+        let mut previous = None;
+        for sub_content in playable.by_ref() {
+            if sub_content.url == child_path {
+                return Self {
+                    previous,
+                    next: playable.next(),
+                };
+            };
+            previous = Some(sub_content);
+        }
+        Self::default()
+    }
+}
+
+/// return the playable [`Siblings`] of ``child_path`` in the page of its parent
+pub fn get_siblings_from_parent(
+    kodi: &Kodi,
+    admission: &dyn CallAdmission,
+    parent_access: &PathAccessData,
+    child_path: &str,
+) -> Siblings {
+    match get_parent_sub_content(kodi, admission, parent_access) {
+        Some(sub_content) => Siblings::find(sub_content, child_path),
+        None => Siblings::default(),
+    }
+}
+
+#[test]
+fn test_find_siblings() {
+    let create = |url: &str, playable: bool| {
+        let mut listitem = data::ListItem::default();
+        if playable {
+            listitem
+                .properties
+                .insert("IsPlayable".into(), "true".into());
+        };
+        SubContent {
+            url: url.into(),
+            is_folder: !playable,
+            total_items: 0,
+            listitem,
+        }
+    };
+    let content = || {
+        vec![
+            create("a", true),
+            create("folder", false),
+            create("b", true),
+            create("c", true),
+        ]
+    };
+    let urls = |siblings: Siblings| {
+        (
+            siblings.previous.map(|x| x.url),
+            siblings.next.map(|x| x.url),
+        )
+    };
+    assert_eq!(
+        urls(Siblings::find(content(), "a")),
+        (None, Some("b".into()))
+    );
+    assert_eq!(
+        urls(Siblings::find(content(), "b")),
+        (Some("a".into()), Some("c".into()))
+    );
+    assert_eq!(
+        urls(Siblings::find(content(), "c")),
+        (Some("b".into()), None)
+    );
+    assert_eq!(urls(Siblings::find(content(), "unknown")), (None, None));
+}
+
+/// return, for each element of an ancestry chain (as returned by [`LinkState::chain`]), its [`SubContent`] in the previous element of the chain. The first element (the root) is always ``None``.
+///
+/// Only the parent of the last element may be called, with ``admission``. The other ones are read from the cache, and are ``None`` if they aren't in it, so a forged chain can't make the server run plugins.
+pub fn get_sub_contents_of_chain(
+    kodi: &Kodi,
+    admission: &dyn CallAdmission,
+    chain: &[&PathAccessData],
+) -> Vec<Option<SubContent>> {
+    let mut result = vec![None];
+    for (position, pair) in chain.windows(2).enumerate() {
+        let (parent, child) = (pair[0], pair[1]);
+        let parent_sub_content = if position + 2 == chain.len() {
+            get_parent_sub_content(kodi, admission, parent)
+        } else {
+            kodi.get_from_cache(parent)
+                .and_then(|cached| parse_parent_result(Ok(cached), parent))
+        };
+        result.push(parent_sub_content.and_then(|sub_contents| {
+            sub_contents
+                .into_iter()
+                .find(|sub_content| sub_content.url == child.path)
+        }));
+    }
+    result.truncate(chain.len());
+    result
+}
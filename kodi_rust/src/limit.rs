@@ -0,0 +1,149 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{InvocationReport, KodiError};
+
+/// Decide whether a plugin can be run on behalf of a client, when its result isn't cached. See [`crate::Kodi::invoke_sandbox_for`].
+pub trait CallAdmission {
+    /// called before a plugin is run because its result isn't cached. The call is refused if an error (usually [`KodiError::RateLimited`]) is returned.
+    fn admit(&self) -> Result<(), KodiError>;
+
+    /// called before any call to the plugin at ``path``, even if its result is cached. The call is refused if an error (usually [`KodiError::AddonNotAllowed`]) is returned.
+    fn check_addon(&self, _path: &str) -> Result<(), KodiError> {
+        Ok(())
+    }
+
+    /// called when a call was refused, either by [`CallAdmission::admit`] or because too many plugins are already running
+    fn refused(&self, _err: &KodiError) {}
+
+    /// called to run a plugin whose call was admitted. ``run`` block the thread until a slot of the [`ConcurrencyLimit`] is free and the plugin end, so this can be overriden to let the other tasks of the thread run elsewhere meanwhile.
+    fn run_blocking(&self, run: &mut dyn FnMut() -> InvocationReport) -> InvocationReport {
+        run()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LimitState {
+    running: usize,
+    waiting: usize,
+}
+
+/// A cap on the number of plugins running at the same time. Calls above the cap wait for a free slot, in a bounded queue.
+#[derive(Debug)]
+pub struct ConcurrencyLimit {
+    /// ``None`` if unlimited
+    max_running: Option<usize>,
+    max_waiting: usize,
+    wait_timeout: Duration,
+    state: Mutex<LimitState>,
+    released: Condvar,
+}
+
+impl Default for ConcurrencyLimit {
+    fn default() -> Self {
+        Self::new(None, 0, Duration::default())
+    }
+}
+
+/// A slot of a [`ConcurrencyLimit`], released when dropped
+#[derive(Debug)]
+pub struct ConcurrencyPermit<'a> {
+    limit: &'a ConcurrencyLimit,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        self.limit.lock().running -= 1;
+        self.limit.released.notify_one();
+    }
+}
+
+impl ConcurrencyLimit {
+    /// create a new [`ConcurrencyLimit`], allowing ``max_running`` calls at the same time (or an unlimited number if ``None``), and ``max_waiting`` calls waiting for at most ``wait_timeout`` for a free slot.
+    pub fn new(max_running: Option<usize>, max_waiting: usize, wait_timeout: Duration) -> Self {
+        Self {
+            max_running,
+            max_waiting,
+            wait_timeout,
+            state: Mutex::new(LimitState::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LimitState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// take a slot, waiting for one to be released if they are all taken.
+    ///
+    /// # Errors
+    ///
+    /// return [`KodiError::Busy`] if the queue is full, or if no slot was released in time
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{ConcurrencyLimit, KodiError};
+    /// use std::time::Duration;
+    ///
+    /// let limit = ConcurrencyLimit::new(Some(1), 0, Duration::from_secs(1));
+    /// let permit = limit.acquire().unwrap();
+    /// assert!(matches!(limit.acquire(), Err(KodiError::Busy(_))));
+    /// drop(permit);
+    /// assert!(limit.acquire().is_ok());
+    /// ```
+    pub fn acquire(&self) -> Result<ConcurrencyPermit<'_>, KodiError> {
+        let mut state = self.lock();
+        let max_running = match self.max_running {
+            Some(value) => value,
+            None => {
+                state.running += 1;
+                return Ok(ConcurrencyPermit { limit: self });
+            }
+        };
+        if state.running >= max_running {
+            if state.waiting >= self.max_waiting {
+                return Err(KodiError::Busy(self.wait_timeout));
+            };
+            state.waiting += 1;
+            let deadline = Instant::now() + self.wait_timeout;
+            while state.running >= max_running {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining == Duration::default() {
+                    state.waiting -= 1;
+                    return Err(KodiError::Busy(self.wait_timeout));
+                };
+                state = self
+                    .released
+                    .wait_timeout(state, remaining)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+            state.waiting -= 1;
+        };
+        state.running += 1;
+        Ok(ConcurrencyPermit { limit: self })
+    }
+}
+
+#[test]
+fn test_wait_for_slot() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let limit = Arc::new(ConcurrencyLimit::new(Some(1), 1, Duration::from_secs(10)));
+    let permit = limit.acquire().unwrap();
+    let waiter = {
+        let limit = limit.clone();
+        thread::spawn(move || limit.acquire().map(|_| ()))
+    };
+    // wait for the second call to be in the queue
+    while limit.lock().waiting == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    // the queue is full
+    assert!(matches!(limit.acquire(), Err(KodiError::Busy(_))));
+    drop(permit);
+    assert!(waiter.join().unwrap().is_ok());
+    assert_eq!(limit.lock().running, 0);
+}
@@ -0,0 +1,400 @@
+use crate::{input::decode_input, PathAccessData, Setting, UserConfig};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::error::Error;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// number of bytes of the HMAC kept in the token
+const SIGNATURE_LENGTH: usize = 16;
+/// the maximum number of ancestors kept in a [`LinkState`]. Only the closest ones are kept above it.
+pub const MAX_ANCESTORS: usize = 8;
+
+#[derive(Debug)]
+/// represent error that can happen while decoding a [`LinkState`] token
+pub enum LinkStateError {
+    TokenNotEnabled,
+    TokenRequired,
+    MissingPath,
+    InvalidFormat,
+    InvalidSignature,
+    CantDecodeBase64(base64::DecodeError),
+    CantParseState(serde_json::Error),
+}
+
+impl fmt::Display for LinkStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TokenNotEnabled => write!(f, "state token are not enabled on this server"),
+            Self::TokenRequired => {
+                write!(f, "this server only accept links with a signed state token")
+            }
+            Self::MissingPath => write!(f, "no path was given"),
+            Self::InvalidFormat => write!(f, "the state token is malformed"),
+            Self::InvalidSignature => write!(f, "the signature of the state token is invalid"),
+            Self::CantDecodeBase64(_) => write!(f, "the state token isn't valid base64"),
+            Self::CantParseState(_) => write!(f, "the content of the state token can't be parsed"),
+        }
+    }
+}
+
+impl Error for LinkStateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CantDecodeBase64(err) => Some(err),
+            Self::CantParseState(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkStateData {
+    #[serde(rename = "p")]
+    path: String,
+    #[serde(rename = "i", default, skip_serializing_if = "Vec::is_empty")]
+    input: Vec<String>,
+    #[serde(rename = "P", default, skip_serializing_if = "Option::is_none")]
+    parent_path: Option<String>,
+    #[serde(rename = "I", default, skip_serializing_if = "Vec::is_empty")]
+    parent_input: Vec<String>,
+    #[serde(rename = "c", default, skip_serializing_if = "String::is_empty")]
+    config: String,
+    #[serde(rename = "A", default, skip_serializing_if = "Vec::is_empty")]
+    ancestors: Vec<AncestorData>,
+}
+
+/// the path and input of an ancestor, as stored in a token or in the ``a`` query parameter
+#[derive(Serialize, Deserialize)]
+struct AncestorData(
+    String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] Vec<String>,
+);
+
+/// The query parameters of a link, as accepted by [`LinkState::from_query`]
+#[derive(Debug, Clone, Default)]
+pub struct LinkQuery {
+    /// the signed state token (``s``)
+    pub token: Option<String>,
+    pub path: Option<String>,
+    pub input: Option<String>,
+    pub parent_path: Option<String>,
+    pub parent_input: Option<String>,
+    /// the ancestors above the parent (``a``), encoded with [`LinkState::encode_ancestors`]
+    pub ancestors: Option<String>,
+    /// the encoded user config (``c``)
+    pub config: Option<String>,
+}
+
+/// The state carried by a link of the website: the accessed path, its parent, the ancestors above the parent, and the user config present in the url.
+///
+/// It can be encoded into a compact token, signed with a server secret, so it can't be forged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkState {
+    pub access: PathAccessData,
+    pub parent: Option<PathAccessData>,
+    /// the ancestors above the parent, from the root of the plugin to the grand parent
+    pub ancestors: Vec<PathAccessData>,
+}
+
+impl LinkState {
+    pub fn new(access: PathAccessData, parent: Option<PathAccessData>) -> Self {
+        Self {
+            access,
+            parent,
+            ancestors: Vec::new(),
+        }
+    }
+
+    /// set the ancestors above the parent. Only the last [`MAX_ANCESTORS`] are kept.
+    pub fn with_ancestors(mut self, mut ancestors: Vec<PathAccessData>) -> Self {
+        Self::truncate_ancestors(&mut ancestors);
+        self.ancestors = ancestors;
+        self
+    }
+
+    /// drop the ancestors closest to the root, so only [`MAX_ANCESTORS`] are left
+    fn truncate_ancestors<T>(ancestors: &mut Vec<T>) {
+        let excess = ancestors.len().saturating_sub(MAX_ANCESTORS);
+        ancestors.drain(..excess);
+    }
+
+    /// return this [`LinkState`], with the config of the access, the parent and the ancestors replaced by ``config``
+    pub fn with_config(mut self, config: UserConfig) -> Self {
+        if let Some(parent) = &mut self.parent {
+            parent.config = config.clone();
+        };
+        for ancestor in &mut self.ancestors {
+            ancestor.config = config.clone();
+        }
+        self.access.config = config;
+        self
+    }
+
+    /// return the [`LinkState`] of ``access``, a child of the accessed path. The ancestry chain is extended with this state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{LinkState, PathAccessData, UserConfig};
+    ///
+    /// let access = |path: &str| PathAccessData::new(path.into(), None, UserConfig::new_empty());
+    /// let state = LinkState::new(access("plugin://a/b"), Some(access("plugin://a/")));
+    /// let child = state.child(access("plugin://a/b/c"));
+    ///
+    /// assert_eq!(child.parent, Some(access("plugin://a/b")));
+    /// assert_eq!(child.ancestors, vec![access("plugin://a/")]);
+    /// assert_eq!(
+    ///     child.chain().into_iter().map(|x| x.path.as_str()).collect::<Vec<_>>(),
+    ///     vec!["plugin://a/", "plugin://a/b", "plugin://a/b/c"]
+    /// );
+    /// ```
+    pub fn child(&self, access: PathAccessData) -> Self {
+        let mut ancestors = self.ancestors.clone();
+        if let Some(parent) = &self.parent {
+            ancestors.push(parent.clone());
+        };
+        Self::truncate_ancestors(&mut ancestors);
+        Self {
+            access,
+            parent: Some(self.access.clone()),
+            ancestors,
+        }
+    }
+
+    /// return the [`LinkState`] of the parent, if any. Its own parent is the last of the ancestors.
+    pub fn parent_state(&self) -> Option<Self> {
+        let parent = self.parent.clone()?;
+        let mut ancestors = self.ancestors.clone();
+        let grand_parent = ancestors.pop();
+        Some(Self {
+            access: parent,
+            parent: grand_parent,
+            ancestors,
+        })
+    }
+
+    /// return the whole ancestry chain, from the root of the plugin to the accessed path
+    pub fn chain(&self) -> Vec<&PathAccessData> {
+        self.ancestors
+            .iter()
+            .chain(self.parent.iter())
+            .chain(std::iter::once(&self.access))
+            .collect()
+    }
+
+    /// encode the path and input of ``ancestors`` in a compact url safe string (base64 of JSON), as used in the ``a`` query parameter
+    pub fn encode_ancestors(ancestors: &[PathAccessData]) -> String {
+        let data: Vec<AncestorData> = ancestors
+            .iter()
+            .map(|ancestor| AncestorData(ancestor.path.clone(), ancestor.input.clone()))
+            .collect();
+        URL_SAFE_NO_PAD
+            .encode(serde_json::to_vec(&data).expect("serializing the ancestors should never fail"))
+    }
+
+    /// decode ancestors encoded with [`LinkState::encode_ancestors`], giving them ``config``
+    ///
+    /// # Errors
+    ///
+    /// return a [`LinkStateError`] if ``encoded`` isn't valid
+    pub fn decode_ancestors(
+        encoded: &str,
+        config: &UserConfig,
+    ) -> Result<Vec<PathAccessData>, LinkStateError> {
+        let data: Vec<AncestorData> = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(encoded)
+                .map_err(LinkStateError::CantDecodeBase64)?,
+        )
+        .map_err(LinkStateError::CantParseState)?;
+        Ok(Self::ancestors_from_data(data, config))
+    }
+
+    fn ancestors_from_data(
+        mut data: Vec<AncestorData>,
+        config: &UserConfig,
+    ) -> Vec<PathAccessData> {
+        Self::truncate_ancestors(&mut data);
+        data.into_iter()
+            .map(|AncestorData(path, input)| PathAccessData {
+                path,
+                input,
+                config: config.clone(),
+            })
+            .collect()
+    }
+
+    /// create a [`LinkState`] from the query parameters of a request. The config of the access is the one present in the url.
+    ///
+    /// If ``token`` is set, the state is decoded from it with the secret of ``setting``, and the plain parameters are ignored.
+    ///
+    /// # Errors
+    ///
+    /// return a [`LinkStateError`] if the token is invalid, if no path is given, or if the setting require a token and none is given.
+    pub fn from_query(setting: &Setting, query: LinkQuery) -> Result<Self, LinkStateError> {
+        if let Some(token) = &query.token {
+            return match &setting.state_token_secret {
+                Some(secret) => Self::decode_token(token, secret),
+                None => Err(LinkStateError::TokenNotEnabled),
+            };
+        };
+        if setting.is_state_token_required() {
+            return Err(LinkStateError::TokenRequired);
+        };
+        let config = UserConfig::new_from_optional_uri(query.config);
+        let parent = PathAccessData::try_create_from_url(
+            query.parent_path,
+            query.parent_input.as_deref(),
+            config.clone(),
+        );
+        // ancestors are meaningless without a parent
+        let ancestors = match (&parent, query.ancestors) {
+            (Some(_), Some(ancestors)) => Self::decode_ancestors(&ancestors, &config)?,
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            access: PathAccessData {
+                path: query.path.ok_or(LinkStateError::MissingPath)?,
+                input: query.input.as_deref().map(decode_input).unwrap_or_default(),
+                config,
+            },
+            parent,
+            ancestors,
+        })
+    }
+
+    fn sign(payload: &str, secret: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    /// encode this state into a token signed with ``secret``, of the form ``<base64 payload>.<base64 signature>``.
+    ///
+    /// The parent access and the ancestors are assumed to use the same config as the main access (it is stored only once).
+    ///
+    /// The token only contain url safe character, and can be decoded with [`LinkState::decode_token`]
+    pub fn encode_token(&self, secret: &str) -> String {
+        let data = LinkStateData {
+            path: self.access.path.clone(),
+            input: self.access.input.clone(),
+            parent_path: self.parent.as_ref().map(|x| x.path.clone()),
+            parent_input: self
+                .parent
+                .as_ref()
+                .map(|x| x.input.clone())
+                .unwrap_or_default(),
+            config: self.access.config.encode_to_uri(),
+            ancestors: self
+                .ancestors
+                .iter()
+                .map(|ancestor| AncestorData(ancestor.path.clone(), ancestor.input.clone()))
+                .collect(),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(
+            serde_json::to_vec(&data).expect("serializing the link state should never fail"),
+        );
+        let signature = Self::sign(&payload, secret).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            payload,
+            URL_SAFE_NO_PAD.encode(&signature[..SIGNATURE_LENGTH])
+        )
+    }
+
+    /// decode a token created by [`LinkState::encode_token`], checking it was signed with the same ``secret``.
+    ///
+    /// # Errors
+    ///
+    /// return a [`LinkStateError`] if the token is malformed or if the signature doesn't match (the token may have been tampered with)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{LinkState, PathAccessData, UserConfig};
+    ///
+    /// let mut config = UserConfig::new_empty();
+    /// *config.language_order = vec!["fr".into()];
+    /// let state = LinkState::new(
+    ///     PathAccessData::new("plugin://plugin.video.test/?a=b".into(), Some("some:input"), config.clone()),
+    ///     Some(PathAccessData::new("plugin://plugin.video.test/".into(), None, config.clone())),
+    /// ).with_ancestors(vec![PathAccessData::new("plugin://plugin.video.test/root".into(), None, config)]);
+    ///
+    /// let token = state.encode_token("secret");
+    /// assert_eq!(LinkState::decode_token(&token, "secret").unwrap(), state);
+    /// assert!(LinkState::decode_token(&token, "other secret").is_err());
+    ///
+    /// let mut tampered = token.clone();
+    /// tampered.insert(2, 'a');
+    /// assert!(LinkState::decode_token(&tampered, "secret").is_err());
+    /// ```
+    pub fn decode_token(token: &str, secret: &str) -> Result<Self, LinkStateError> {
+        let mut splited = token.split('.');
+        let (payload, signature) = match (splited.next(), splited.next(), splited.next()) {
+            (Some(payload), Some(signature), None) => (payload, signature),
+            _ => return Err(LinkStateError::InvalidFormat),
+        };
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(LinkStateError::CantDecodeBase64)?;
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(LinkStateError::InvalidSignature);
+        };
+        Self::sign(payload, secret)
+            .verify_truncated_left(&signature)
+            .map_err(|_| LinkStateError::InvalidSignature)?;
+
+        let data: LinkStateData = serde_json::from_slice(
+            &URL_SAFE_NO_PAD
+                .decode(payload)
+                .map_err(LinkStateError::CantDecodeBase64)?,
+        )
+        .map_err(LinkStateError::CantParseState)?;
+
+        let config = UserConfig::new_from_optional_uri(Some(data.config));
+        let parent_input = data.parent_input;
+        let parent = data.parent_path.map(|parent_path| PathAccessData {
+            path: parent_path,
+            input: parent_input,
+            config: config.clone(),
+        });
+        Ok(Self {
+            ancestors: Self::ancestors_from_data(data.ancestors, &config),
+            access: PathAccessData {
+                path: data.path,
+                input: data.input,
+                config,
+            },
+            parent,
+        })
+    }
+}
+
+#[test]
+fn test_max_ancestors() {
+    let access = |index: usize| {
+        PathAccessData::new(
+            format!("plugin://a/{}", index),
+            None,
+            UserConfig::new_empty(),
+        )
+    };
+    let mut state = LinkState::new(access(0), None);
+    for index in 1..20 {
+        state = state.child(access(index));
+    }
+    assert_eq!(state.parent, Some(access(18)));
+    assert_eq!(state.ancestors.len(), MAX_ANCESTORS);
+    assert_eq!(state.ancestors.last(), Some(&access(17)));
+
+    let long: Vec<_> = (0..20).map(access).collect();
+    let encoded = LinkState::encode_ancestors(&long);
+    let decoded = LinkState::decode_ancestors(&encoded, &UserConfig::new_empty()).unwrap();
+    assert_eq!(decoded, long[20 - MAX_ANCESTORS..]);
+}
@@ -0,0 +1,235 @@
+use crate::data::{ListItem, SubContent};
+
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// A way to sort the content of a folder, other than the order given by the plugin
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SortMethod {
+    Label,
+    Year,
+    Duration,
+    Episode,
+    /// the most recently added first
+    DateAdded,
+}
+
+impl SortMethod {
+    pub const ALL: [Self; 5] = [
+        Self::Label,
+        Self::Year,
+        Self::Duration,
+        Self::Episode,
+        Self::DateAdded,
+    ];
+
+    /// return the [`SortMethod`] that correspond to the value, as encoded with [`SortMethod::as_str`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::SortMethod;
+    ///
+    /// assert_eq!(SortMethod::from_str("episode"), Some(SortMethod::Episode));
+    /// assert_eq!(SortMethod::from_str(SortMethod::DateAdded.as_str()), Some(SortMethod::DateAdded));
+    /// assert_eq!(SortMethod::from_str("invalid"), None);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|method| method.as_str() == value)
+    }
+
+    /// return the name of this method, as used in the uri and in the cookies
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Label => "label",
+            Self::Year => "year",
+            Self::Duration => "duration",
+            Self::Episode => "episode",
+            Self::DateAdded => "date_added",
+        }
+    }
+
+    /// return the ``SORT_METHOD_*`` constants of ``xbmcplugin`` that allow this method
+    fn kodi_sort_methods(self) -> &'static [u32] {
+        match self {
+            // LABEL, LABEL_IGNORE_THE, TITLE, TITLE_IGNORE_THE
+            Self::Label => &[1, 2, 9, 10],
+            // DATE, VIDEO_YEAR
+            Self::Year => &[3, 18],
+            Self::Duration => &[8],
+            Self::Episode => &[24],
+            Self::DateAdded => &[21],
+        }
+    }
+
+    /// return the methods that can be used for a folder, according to the sort methods the plugin declared with ``xbmcplugin.addSortMethod``. Every method is available if the plugin declared none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::SortMethod;
+    ///
+    /// assert_eq!(SortMethod::available(&[]), SortMethod::ALL.to_vec());
+    /// assert_eq!(SortMethod::available(&[0, 1, 24]), vec![SortMethod::Label, SortMethod::Episode]);
+    /// ```
+    pub fn available(declared: &[u32]) -> Vec<Self> {
+        if declared.is_empty() {
+            return Self::ALL.to_vec();
+        };
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(|method| {
+                method
+                    .kodi_sort_methods()
+                    .iter()
+                    .any(|kodi_method| declared.contains(kodi_method))
+            })
+            .collect()
+    }
+
+    fn compare(self, first: &ListItem, second: &ListItem) -> Ordering {
+        // the elements missing the sorted value are put at the end
+        fn compare_option<T: Ord>(first: Option<T>, second: Option<T>) -> Ordering {
+            match (first, second) {
+                (Some(first), Some(second)) => first.cmp(&second),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+        fn label(listitem: &ListItem) -> Option<String> {
+            listitem
+                .label
+                .as_ref()
+                .or(listitem.info.title.as_ref())
+                .map(|label| label.to_lowercase())
+        }
+
+        match self {
+            Self::Label => compare_option(label(first), label(second)),
+            Self::Year => compare_option(first.info.year, second.info.year),
+            Self::Duration => compare_option(first.info.duration, second.info.duration),
+            Self::Episode => compare_option(
+                first
+                    .info
+                    .episode
+                    .map(|episode| (first.info.season, episode)),
+                second
+                    .info
+                    .episode
+                    .map(|episode| (second.info.season, episode)),
+            ),
+            Self::DateAdded => compare_option(
+                first.info.dateadded.as_ref().map(std::cmp::Reverse),
+                second.info.dateadded.as_ref().map(std::cmp::Reverse),
+            ),
+        }
+    }
+
+    /// sort ``sub_content`` with this method. The order of the plugin is kept between equal elements.
+    pub fn sort(self, sub_content: &mut [SubContent]) {
+        sub_content.sort_by(|first, second| self.compare(&first.listitem, &second.listitem))
+    }
+}
+
+/// keep only the elements of ``sub_content`` whose label or plot contain ``query``, ignoring the case
+///
+/// # Example
+///
+/// ```
+/// use kodi_rust::{data::{ListItem, SubContent}, filter_sub_content};
+///
+/// let create = |label: &str, plot: &str| {
+///     let mut listitem = ListItem::default();
+///     listitem.label = Some(label.into());
+///     listitem.info.plot = Some(plot.into());
+///     SubContent { url: label.into(), is_folder: false, total_items: 0, listitem }
+/// };
+/// let mut content = vec![create("The Cat", "a cat"), create("A dog", "the dog chase a CAT"), create("bird", "")];
+/// filter_sub_content(&mut content, "cat");
+/// assert_eq!(content.iter().map(|x| x.url.as_str()).collect::<Vec<_>>(), vec!["The Cat", "A dog"]);
+/// ```
+pub fn filter_sub_content(sub_content: &mut Vec<SubContent>, query: &str) {
+    let query = query.to_lowercase();
+    sub_content.retain(|sub_content| {
+        let listitem = &sub_content.listitem;
+        listitem
+            .label
+            .iter()
+            .chain(listitem.info.title.iter())
+            .chain(listitem.info.plot.iter())
+            .any(|text| text.to_lowercase().contains(&query))
+    })
+}
+
+/// return ``true`` if every element of ``sub_content`` is an episode with a season and an episode number, so it can be grouped with [`group_by_season`]
+pub fn is_episode_list(sub_content: &[SubContent]) -> bool {
+    !sub_content.is_empty()
+        && sub_content.iter().all(|sub_content| {
+            sub_content.listitem.info.season.is_some()
+                && sub_content.listitem.info.episode.is_some()
+        })
+}
+
+/// split ``sub_content`` in groups of consecutive elements of the same season, returning the season and the range of each group.
+///
+/// ``sub_content`` should be sorted with [`SortMethod::Episode`], so each season form a single group.
+///
+/// # Example
+///
+/// ```
+/// use kodi_rust::{data::{ListItem, SubContent}, group_by_season, is_episode_list, SortMethod};
+///
+/// let create = |season: u64, episode: u64| {
+///     let mut listitem = ListItem::default();
+///     listitem.info.season = Some(season);
+///     listitem.info.episode = Some(episode);
+///     SubContent { url: format!("S{}E{}", season, episode), is_folder: false, total_items: 0, listitem }
+/// };
+/// let mut content = vec![create(2, 1), create(1, 2), create(1, 1)];
+/// assert!(is_episode_list(&content));
+/// SortMethod::Episode.sort(&mut content);
+/// assert_eq!(group_by_season(&content), vec![(1, 0..2), (2, 2..3)]);
+/// ```
+pub fn group_by_season(sub_content: &[SubContent]) -> Vec<(u64, Range<usize>)> {
+    let mut result: Vec<(u64, Range<usize>)> = Vec::new();
+    for (index, sub_content) in sub_content.iter().enumerate() {
+        let season = sub_content.listitem.info.season.unwrap_or_default();
+        match result.last_mut() {
+            Some((last_season, range)) if *last_season == season => range.end = index + 1,
+            _ => result.push((season, index..index + 1)),
+        }
+    }
+    result
+}
+
+#[test]
+fn test_sort() {
+    let create = |url: &str, season: Option<u64>, episode: Option<u64>| {
+        let mut listitem = ListItem::default();
+        listitem.info.season = season;
+        listitem.info.episode = episode;
+        SubContent {
+            url: url.into(),
+            is_folder: false,
+            total_items: 0,
+            listitem,
+        }
+    };
+    let mut content = vec![
+        create("bonus", None, None),
+        create("s2e1", Some(2), Some(1)),
+        create("s1e2", Some(1), Some(2)),
+        create("s1e1", Some(1), Some(1)),
+    ];
+    SortMethod::Episode.sort(&mut content);
+    assert_eq!(
+        content.iter().map(|x| x.url.as_str()).collect::<Vec<_>>(),
+        vec!["s1e1", "s1e2", "s2e1", "bonus"]
+    );
+}
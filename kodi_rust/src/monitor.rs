@@ -0,0 +1,246 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use subprocess::Popen;
+
+use crate::{data::KodiResult, get_addon_id, PathAccessData};
+
+/// The default number of invocations kept by [`Monitor`]
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+
+/// A finished call to a plugin
+#[derive(Clone, Debug)]
+pub struct InvocationRecord {
+    pub id: u64,
+    pub access: PathAccessData,
+    pub started: SystemTime,
+    pub duration: Duration,
+    /// ``None`` if the process couldn't be started or waited for
+    pub exit_status: Option<String>,
+    /// ``true`` if a result was returned
+    pub success: bool,
+    /// the merged stdout and stderr of python, if it was captured
+    pub log: Option<String>,
+}
+
+/// A result stored in the cache of [`crate::Kodi`]
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    pub access: PathAccessData,
+    /// the time since the result was stored
+    pub age: Duration,
+    pub result: KodiResult,
+}
+
+/// A call to a plugin that is still running
+#[derive(Clone, Debug)]
+pub struct RunningInvocation {
+    pub id: u64,
+    pub access: PathAccessData,
+    pub started: SystemTime,
+    pub pid: Option<u32>,
+}
+
+/// The number of calls to a plugin that succeeded and failed, since the start of the server
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PluginStats {
+    pub success: u64,
+    pub failure: u64,
+}
+
+impl PluginStats {
+    /// return the part of the calls that succeeded, between 0 and 1
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::PluginStats;
+    ///
+    /// assert_eq!(PluginStats { success: 3, failure: 1 }.success_rate(), 0.75);
+    /// assert_eq!(PluginStats::default().success_rate(), 1.0);
+    /// ```
+    pub fn success_rate(self) -> f64 {
+        let total = self.success + self.failure;
+        if total == 0 {
+            1.0
+        } else {
+            self.success as f64 / total as f64
+        }
+    }
+}
+
+/// Keep track of the calls made by [`crate::Kodi`]: the running ones, the last finished ones (in a bounded ring buffer) and the statistics of each plugin.
+pub struct Monitor {
+    next_id: u64,
+    history: VecDeque<InvocationRecord>,
+    history_size: usize,
+    running: HashMap<u64, (RunningInvocation, Arc<Mutex<Popen>>)>,
+    plugin_stats: HashMap<String, PluginStats>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_SIZE)
+    }
+}
+
+impl std::fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Monitor")
+            .field("history_size", &self.history_size)
+            .field("running", &self.running.len())
+            .finish()
+    }
+}
+
+impl Monitor {
+    /// create a new [`Monitor`], keeping the last ``history_size`` finished invocations
+    pub fn new(history_size: usize) -> Self {
+        Self {
+            next_id: 0,
+            history: VecDeque::with_capacity(history_size),
+            history_size,
+            running: HashMap::new(),
+            plugin_stats: HashMap::new(),
+        }
+    }
+
+    /// change the number of finished invocations kept, forgetting the oldest ones if needed
+    pub fn set_history_size(&mut self, history_size: usize) {
+        self.history_size = history_size;
+        while self.history.len() > history_size {
+            self.history.pop_front();
+        }
+    }
+
+    /// record the start of ``process``, returning the id of the invocation
+    pub fn start(&mut self, access: &PathAccessData, process: Arc<Mutex<Popen>>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let pid = process.lock().ok().and_then(|process| process.pid());
+        self.running.insert(
+            id,
+            (
+                RunningInvocation {
+                    id,
+                    access: access.clone(),
+                    started: SystemTime::now(),
+                    pid,
+                },
+                process,
+            ),
+        );
+        id
+    }
+
+    /// record the end of the invocation ``id``. It is only counted in the statistics of its plugin if ``record_stats`` is true.
+    pub fn finish(
+        &mut self,
+        id: u64,
+        exit_status: Option<String>,
+        success: bool,
+        log: Option<String>,
+        record_stats: bool,
+    ) {
+        let (running, _) = match self.running.remove(&id) {
+            Some(value) => value,
+            None => return,
+        };
+        if let Some(addon_id) = get_addon_id(&running.access.path).filter(|_| record_stats) {
+            let stats = self.plugin_stats.entry(addon_id.to_string()).or_default();
+            if success {
+                stats.success += 1;
+            } else {
+                stats.failure += 1;
+            }
+        };
+        if self.history_size == 0 {
+            return;
+        };
+        if self.history.len() >= self.history_size {
+            self.history.pop_front();
+        };
+        self.history.push_back(InvocationRecord {
+            id,
+            duration: running.started.elapsed().unwrap_or_default(),
+            access: running.access,
+            started: running.started,
+            exit_status,
+            success,
+            log,
+        });
+    }
+
+    /// return the finished invocations still kept, the most recent first
+    pub fn history(&self) -> Vec<InvocationRecord> {
+        self.history.iter().rev().cloned().collect()
+    }
+
+    /// return the running invocations, the oldest first
+    pub fn running(&self) -> Vec<RunningInvocation> {
+        let mut result: Vec<RunningInvocation> = self
+            .running
+            .values()
+            .map(|(running, _)| running.clone())
+            .collect();
+        result.sort_by_key(|running| running.id);
+        result
+    }
+
+    /// return the statistics of every plugin called since the start, sorted by plugin id
+    pub fn plugin_stats(&self) -> Vec<(String, PluginStats)> {
+        let mut result: Vec<(String, PluginStats)> = self
+            .plugin_stats
+            .iter()
+            .map(|(addon_id, stats)| (addon_id.clone(), *stats))
+            .collect();
+        result.sort_by(|first, second| first.0.cmp(&second.0));
+        result
+    }
+
+    /// return the process of the running invocation ``id``
+    pub fn get_process(&self, id: u64) -> Option<Arc<Mutex<Popen>>> {
+        self.running.get(&id).map(|(_, process)| process.clone())
+    }
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let mut monitor = Monitor::new(2);
+    let access = PathAccessData::new(
+        "plugin://plugin.video.test/".into(),
+        None,
+        crate::UserConfig::default(),
+    );
+    for success in &[true, false, true] {
+        let process = subprocess::Exec::cmd("true").popen().unwrap();
+        let id = monitor.start(&access, Arc::new(Mutex::new(process)));
+        assert_eq!(monitor.running().len(), 1);
+        monitor.finish(id, None, *success, None, true);
+    }
+    // an invocation can be kept out of the statistics
+    let unknown_access = PathAccessData::new(
+        "plugin://plugin.video.unknown/".into(),
+        None,
+        crate::UserConfig::default(),
+    );
+    let process = subprocess::Exec::cmd("true").popen().unwrap();
+    let id = monitor.start(&unknown_access, Arc::new(Mutex::new(process)));
+    monitor.finish(id, None, false, None, false);
+    assert!(monitor.running().is_empty());
+    assert_eq!(
+        monitor.history().iter().map(|x| x.id).collect::<Vec<_>>(),
+        vec![3, 2]
+    );
+    assert_eq!(
+        monitor.plugin_stats(),
+        vec![(
+            "plugin.video.test".to_string(),
+            PluginStats {
+                success: 2,
+                failure: 1
+            }
+        )]
+    );
+}
@@ -1,5 +1,69 @@
-use crate::UserConfig;
+use crate::{get_addon_id, UserConfig};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+
+/// How a plugin is isolated from the rest of the system when it is run
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxProfile {
+    /// run in a bubblewrap sandbox, with access to the network
+    Default,
+    /// run in a bubblewrap sandbox, without access to the network
+    Offline,
+    /// run directly, without sandbox
+    Disabled,
+}
+
+/// How the arts of the listings of a plugin are served
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtMode {
+    /// downloaded by the server, resized and cached (only if [`Setting::thumbnail_cache_directory`] is set)
+    Proxy,
+    /// loaded by the browser from their original site
+    Redirect,
+}
+
+/// How the visitors are authenticated
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMode {
+    /// everyone can use the site, without account
+    #[default]
+    None,
+    /// the visitors log in with a user name and a password, checked against [`Setting::users_file`] or the users of the database
+    Password,
+    /// a reverse proxy authenticate the visitors, and give the user name in the [`Setting::auth_header`] header
+    Proxy,
+}
+
+/// Override of the global settings for the plugins of an addon, in [`Setting::plugins`]. Every unset value is taken from the global setting.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PluginSetting {
+    /// the time (in seconds) the results of the plugin are cached
+    #[serde(default)]
+    pub cache_time: Option<u64>,
+    /// the command used to call python
+    #[serde(default)]
+    pub python_command: Option<String>,
+    /// the time (in seconds) after which the plugin is killed
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
+    /// the paths accessible in the sandbox, replacing the global ``allowed_path``
+    #[serde(default)]
+    pub allowed_path: Option<Vec<String>>,
+    /// replace the global ``default_user_config``
+    #[serde(default)]
+    pub default_user_config: Option<UserConfig>,
+    #[serde(default)]
+    pub art_mode: Option<ArtMode>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Setting {
@@ -8,6 +72,153 @@ pub struct Setting {
     pub python_command: String,
     pub default_user_config: UserConfig,
     pub allowed_path: Vec<String>,
+    /// additional directories containing fluent translations of the interface, overriding the embedded one
+    #[serde(default)]
+    pub locale_directories: Vec<String>,
+    /// map a browser language tag (like ``fr-FR``, or only ``fr``) to the language code used by the addons
+    #[serde(default)]
+    pub language_aliases: HashMap<String, String>,
+    /// if set, the state of links (path, input, parent and config) is stored in a compact token signed with this secret, rather than in plain query parameters
+    #[serde(default)]
+    pub state_token_secret: Option<String>,
+    /// if ``true`` (and ``state_token_secret`` is set), only accept signed state token, so the path and input can't be forged. The answer to a keyboard prompt is still given by the visitor, after the signed input
+    #[serde(default)]
+    pub require_state_token: bool,
+    /// the url this site is reachable at (like ``https://example.com``), used to create absolute links (in feeds for example). If not set, it is guessed from the ``Host`` header
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// the paths that ask for a keyboard input to search in a plugin. They are all used by the search page
+    #[serde(default)]
+    pub search_entry_points: Vec<(String, String)>, //label, path
+    /// the path to the SQLite database storing the favourites and the history. If not set, they are kept in memory and lost when the server stop
+    #[serde(default)]
+    pub database_path: Option<String>,
+    /// the maximum number of elements shown on one page of a folder. Bigger folders are split in multiple pages. ``0`` disable the pagination
+    #[serde(default = "default_folder_page_size")]
+    pub folder_page_size: usize,
+    /// if set, the arts of the listings are downloaded by the server, resized and cached in this directory, rather than loaded by the browser from their original site
+    #[serde(default)]
+    pub thumbnail_cache_directory: Option<String>,
+    /// the width (in pixels) of the thumbnails of the listings, when ``thumbnail_cache_directory`` is set. Bigger variants are offered to high density screens
+    #[serde(default = "default_thumbnail_width")]
+    pub thumbnail_width: u32,
+    /// the maximum size (in megabytes) of ``thumbnail_cache_directory``. The oldest thumbnails are removed above it
+    #[serde(default = "default_thumbnail_cache_size")]
+    pub thumbnail_cache_size: u64,
+    /// the password of the administration pages (at ``/admin``), asked with the HTTP basic authentication. They are disabled if not set
+    #[serde(default)]
+    pub admin_password: Option<String>,
+    /// if true, the output of python is kept with each invocation, to be shown in the administration pages, rather than printed in the terminal. The debug view always keep it
+    #[serde(default)]
+    pub keep_plugin_output: bool,
+    /// the number of finished plugin invocations shown in the administration pages
+    #[serde(default = "default_invocation_history_size")]
+    pub invocation_history_size: usize,
+    /// if true, the debug view of the plugins (at ``/debug/plugin``) is available to everyone, otherwise only to the administrators
+    #[serde(default)]
+    pub public_debug_view: bool,
+    /// the number of calls to plugins whose result isn't cached a single IP address can make per ``rate_limit_window`` seconds. Unlimited if not set
+    #[serde(default)]
+    pub rate_limit_calls: Option<usize>,
+    /// the duration (in seconds) over which the calls of ``rate_limit_calls`` are counted
+    #[serde(default = "default_rate_limit_window")]
+    pub rate_limit_window: u64,
+    /// the addresses of the reverse proxies in front of the server. The address of the client is read from the ``X-Real-IP`` header (or the ``ip_header`` of Rocket's configuration) only for the requests they send, and is the address of the connection otherwise
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// the maximum number of plugins running at the same time. Unlimited if not set
+    #[serde(default)]
+    pub max_running_plugins: Option<usize>,
+    /// the number of calls waiting for a plugin to end when ``max_running_plugins`` are running. Further calls are refused
+    #[serde(default = "default_max_waiting_plugins")]
+    pub max_waiting_plugins: usize,
+    /// the time (in seconds) a call wait for a plugin to end before being refused
+    #[serde(default = "default_plugin_wait_timeout")]
+    pub plugin_wait_timeout: u64,
+    /// if set, only the addons with these ids can be called
+    #[serde(default)]
+    pub allowed_addons: Option<Vec<String>>,
+    /// the ids of the addons that can't be called, even if they are allowed otherwise
+    #[serde(default)]
+    pub denied_addons: Vec<String>,
+    /// if true, only the addons of ``plugins_to_show`` and ``search_entry_points`` (and those of ``allowed_addons``) can be called
+    #[serde(default)]
+    pub only_shown_plugins: bool,
+    /// the time (in seconds) the results of the plugins are cached
+    #[serde(default = "default_cache_time")]
+    pub cache_time: u64,
+    /// the time (in seconds) after which a plugin is killed. Unlimited if not set
+    #[serde(default)]
+    pub plugin_timeout: Option<u64>,
+    /// override of the settings above for the plugins of some addons, by addon id
+    #[serde(default)]
+    pub plugins: HashMap<String, PluginSetting>,
+    /// the address the server listen on. If not set, the one of Rocket's configuration is used
+    #[serde(default)]
+    pub listen_address: Option<IpAddr>,
+    /// the port the server listen on. If not set, the one of Rocket's configuration is used
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub auth_mode: AuthMode,
+    /// a file with one ``name:hash`` line per user, where the hash is an argon2 hash of the password. If not set, the users are stored in the database
+    #[serde(default)]
+    pub users_file: Option<String>,
+    /// the header containing the user name, with ``auth_mode`` set to ``proxy``
+    #[serde(default = "default_auth_header")]
+    pub auth_header: String,
+    /// the addresses of the reverse proxies allowed to set ``auth_header``. The header sent by any other address is ignored
+    #[serde(default)]
+    pub auth_proxies: Vec<IpAddr>,
+    /// the time (in seconds) a user stay logged in
+    #[serde(default = "default_session_lifetime")]
+    pub session_lifetime: u64,
+    /// the only addons some users can call (in addition to the restrictions on every user), by user name
+    #[serde(default)]
+    pub user_allowed_addons: HashMap<String, Vec<String>>,
+    /// the password hash of each user of ``users_file``, by user name. It is read by [`Setting::load_users`], when the setting is loaded
+    #[serde(skip)]
+    pub users: HashMap<String, String>,
+}
+
+fn default_folder_page_size() -> usize {
+    200
+}
+
+fn default_thumbnail_width() -> u32 {
+    300
+}
+
+fn default_thumbnail_cache_size() -> u64 {
+    1024
+}
+
+fn default_invocation_history_size() -> usize {
+    crate::DEFAULT_HISTORY_SIZE
+}
+
+fn default_rate_limit_window() -> u64 {
+    60
+}
+
+fn default_max_waiting_plugins() -> usize {
+    20
+}
+
+fn default_plugin_wait_timeout() -> u64 {
+    10
+}
+
+fn default_cache_time() -> u64 {
+    3600
+}
+
+fn default_auth_header() -> String {
+    "Remote-User".into()
+}
+
+fn default_session_lifetime() -> u64 {
+    30 * 24 * 3600
 }
 
 impl Default for Setting {
@@ -18,11 +229,66 @@ impl Default for Setting {
             python_command: "python2".into(), //NOTE: change to python3 once kodi 19 is publicly released
             default_user_config: UserConfig::default(),
             allowed_path: Vec::new(),
+            locale_directories: Vec::new(),
+            language_aliases: HashMap::new(),
+            state_token_secret: None,
+            require_state_token: false,
+            public_url: None,
+            search_entry_points: Vec::new(),
+            database_path: None,
+            folder_page_size: default_folder_page_size(),
+            thumbnail_cache_directory: None,
+            thumbnail_width: default_thumbnail_width(),
+            thumbnail_cache_size: default_thumbnail_cache_size(),
+            admin_password: None,
+            keep_plugin_output: false,
+            invocation_history_size: default_invocation_history_size(),
+            public_debug_view: false,
+            rate_limit_calls: None,
+            rate_limit_window: default_rate_limit_window(),
+            trusted_proxies: Vec::new(),
+            max_running_plugins: None,
+            max_waiting_plugins: default_max_waiting_plugins(),
+            plugin_wait_timeout: default_plugin_wait_timeout(),
+            allowed_addons: None,
+            denied_addons: Vec::new(),
+            only_shown_plugins: false,
+            cache_time: default_cache_time(),
+            plugin_timeout: None,
+            plugins: HashMap::new(),
+            listen_address: None,
+            port: None,
+            auth_mode: AuthMode::None,
+            users_file: None,
+            auth_header: default_auth_header(),
+            auth_proxies: Vec::new(),
+            session_lifetime: default_session_lifetime(),
+            users: HashMap::new(),
+            user_allowed_addons: HashMap::new(),
         }
     }
 }
 
 impl Setting {
+    /// read the users of ``users_file`` (if set) into ``users``. Empty lines and lines starting with ``#`` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// return an error if ``users_file`` can't be read
+    pub fn load_users(&mut self) -> io::Result<()> {
+        self.users = match &self.users_file {
+            Some(users_file) => fs::read_to_string(users_file)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| line.split_once(':'))
+                .map(|(user, hash)| (user.to_string(), hash.to_string()))
+                .collect(),
+            None => HashMap::new(),
+        };
+        Ok(())
+    }
+
     pub fn get_label_for_path(&self, path: &str) -> Option<String> {
         for (label, analyzed_path) in self.plugins_to_show.iter() {
             if path == analyzed_path {
@@ -31,4 +297,77 @@ impl Setting {
         }
         None
     }
+
+    /// return the [`PluginSetting`] of the addon of the plugin ``path``, if it has one
+    pub fn get_plugin_setting(&self, path: &str) -> Option<&PluginSetting> {
+        self.plugins.get(get_addon_id(path)?)
+    }
+
+    /// return the default [`UserConfig`] of the plugin ``path``
+    pub fn get_default_user_config(&self, path: &str) -> &UserConfig {
+        self.get_plugin_setting(path)
+            .and_then(|plugin_setting| plugin_setting.default_user_config.as_ref())
+            .unwrap_or(&self.default_user_config)
+    }
+
+    /// return the directory the arts of the plugin ``path`` are cached in, or ``None`` if they don't go through the thumbnail proxy
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::{ArtMode, PluginSetting, Setting};
+    ///
+    /// let mut setting = Setting::default();
+    /// setting.thumbnail_cache_directory = Some("/var/cache/kodionline".into());
+    /// setting.plugins.insert("plugin.image.a".into(), PluginSetting {
+    ///     art_mode: Some(ArtMode::Redirect),
+    ///     ..PluginSetting::default()
+    /// });
+    ///
+    /// assert_eq!(setting.get_thumbnail_cache_directory("plugin://plugin.image.a/"), None);
+    /// assert_eq!(setting.get_thumbnail_cache_directory("plugin://plugin.image.b/"), Some("/var/cache/kodionline"));
+    /// ```
+    pub fn get_thumbnail_cache_directory(&self, path: &str) -> Option<&str> {
+        let art_mode = self
+            .get_plugin_setting(path)
+            .and_then(|plugin_setting| plugin_setting.art_mode);
+        match art_mode {
+            Some(ArtMode::Redirect) => None,
+            _ => self.thumbnail_cache_directory.as_deref(),
+        }
+    }
+
+    /// return ``true`` if only the link with a signed state token are accepted
+    pub fn is_state_token_required(&self) -> bool {
+        self.require_state_token && self.state_token_secret.is_some()
+    }
+
+    /// return the language code used by addons for a browser language tag (as found in the ``Accept-Language`` header).
+    ///
+    /// It first look for the whole tag in ``language_aliases``, then for the primary language subtag. If none is found, the primary language subtag is returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::Setting;
+    ///
+    /// let mut setting = Setting::default();
+    /// setting.language_aliases.insert("fr-CA".into(), "qc".into());
+    /// setting.language_aliases.insert("de".into(), "ger".into());
+    ///
+    /// assert_eq!(setting.get_addon_language("fr-CA"), "qc");
+    /// assert_eq!(setting.get_addon_language("fr-FR"), "fr");
+    /// assert_eq!(setting.get_addon_language("de-AT"), "ger");
+    /// assert_eq!(setting.get_addon_language("EN"), "en");
+    /// ```
+    pub fn get_addon_language(&self, tag: &str) -> String {
+        if let Some(alias) = self.language_aliases.get(tag) {
+            return alias.clone();
+        };
+        let primary = tag.split('-').next().unwrap_or(tag);
+        match self.language_aliases.get(primary) {
+            Some(alias) => alias.clone(),
+            None => primary.to_lowercase(),
+        }
+    }
 }
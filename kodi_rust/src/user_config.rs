@@ -4,21 +4,126 @@ use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::ops::{Deref, DerefMut};
 
+/// define how an [`OverridableVec`] is merged with the (lower priority) [`OverridableVec`] of its parent, in [`UserConfig::add_config_prioritary`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    /// put the values before the parent ones (the default)
+    #[default]
+    Prepend,
+    /// put the values after the parent ones
+    Append,
+    /// ignore the parent values, and use only these values
+    Replace,
+    /// use the parent values, except the ones listed in these values
+    Remove,
+}
+
+impl MergeMode {
+    /// return the [`MergeMode`] that correspond to the value, as encoded with [`MergeMode::as_str`]
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodi_rust::MergeMode;
+    ///
+    /// assert_eq!(MergeMode::from_str("remove"), Some(MergeMode::Remove));
+    /// assert_eq!(MergeMode::from_str(MergeMode::Append.as_str()), Some(MergeMode::Append));
+    /// assert_eq!(MergeMode::from_str("invalid"), None);
+    /// ```
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "prepend" => Some(Self::Prepend),
+            "append" => Some(Self::Append),
+            "replace" => Some(Self::Replace),
+            "remove" => Some(Self::Remove),
+            _ => None,
+        }
+    }
+
+    /// return the name of this mode, as used in the uri and in the setting file
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Prepend => "prepend",
+            Self::Append => "append",
+            Self::Replace => "replace",
+            Self::Remove => "remove",
+        }
+    }
+}
+
+/// A list of value that can be merged with the one of a parent configuration, according to its [`MergeMode`]
+///
+/// In the setting file, it can either be written as a list (using [`MergeMode::Prepend`]), or as an object like ``{"mode": "remove", "value": ["1080p"]}``
+///
+/// # Example
+///
+/// ```
+/// use kodi_rust::{MergeMode, UserConfig};
+///
+/// let config: UserConfig = serde_json::from_str(r#"{
+///     "language_order": ["fr", "en"],
+///     "resolution_order": {"mode": "remove", "value": ["1080p"]}
+/// }"#).unwrap();
+///
+/// assert_eq!(config.language_order.mode, MergeMode::Prepend);
+/// assert_eq!(*config.resolution_order, vec!["1080p".to_string()]);
+/// assert_eq!(config.resolution_order.mode, MergeMode::Remove);
+/// assert_eq!(
+///     serde_json::to_value(&config.resolution_order).unwrap(),
+///     serde_json::json!({"mode": "remove", "value": ["1080p"]})
+/// );
+/// ```
 #[derive(Default, Clone, Debug, Deserialize, Serialize)]
-#[serde(from = "Vec<T>")]
-#[serde(into = "Vec<T>")]
+#[serde(from = "OverridableVecRepr<T>")]
+#[serde(into = "OverridableVecRepr<T>")]
 pub struct OverridableVec<T: Clone + Eq + Hash> {
     pub value: Vec<T>,
-    pub no_child: bool,
+    pub mode: MergeMode,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum OverridableVecRepr<T> {
+    List(Vec<T>),
+    WithMode {
+        #[serde(default)]
+        mode: MergeMode,
+        value: Vec<T>,
+    },
 }
 
 impl<T: Eq + Clone + Hash> OverridableVec<T> {
-    fn add_child_and_reset_no_child(&mut self, child: OverridableVec<T>) {
-        if self.no_child {
-            self.no_child = false;
-        } else {
-            self.value.extend(child.value);
-        }
+    /// create a new [`OverridableVec`] with the given value and merge mode
+    pub fn new_with_mode(value: Vec<T>, mode: MergeMode) -> Self {
+        Self { value, mode }
+    }
+
+    fn add_child_and_reset_mode(&mut self, child: OverridableVec<T>) {
+        match self.mode {
+            MergeMode::Prepend => self.value.extend(child.value),
+            MergeMode::Append => {
+                let appended: HashSet<T> = self.value.iter().cloned().collect();
+                let mut new_value: Vec<T> = child
+                    .value
+                    .into_iter()
+                    .filter(|v| !appended.contains(v))
+                    .collect();
+                new_value.append(&mut self.value);
+                self.value = new_value;
+            }
+            MergeMode::Replace => (),
+            MergeMode::Remove => {
+                let to_remove: HashSet<T> = self.value.drain(..).collect();
+                self.value = child
+                    .value
+                    .into_iter()
+                    .filter(|v| !to_remove.contains(v))
+                    .collect();
+            }
+        };
+        self.mode = MergeMode::Prepend;
     }
 
     fn is_empty(&self) -> bool {
@@ -30,7 +135,7 @@ impl<T: Eq + Clone + Hash> From<Vec<T>> for OverridableVec<T> {
     fn from(value: Vec<T>) -> Self {
         OverridableVec {
             value,
-            no_child: false,
+            mode: MergeMode::Prepend,
         }
     }
 }
@@ -41,6 +146,28 @@ impl<T: Eq + Clone + Hash> From<OverridableVec<T>> for Vec<T> {
     }
 }
 
+impl<T: Eq + Clone + Hash> From<OverridableVecRepr<T>> for OverridableVec<T> {
+    fn from(repr: OverridableVecRepr<T>) -> Self {
+        match repr {
+            OverridableVecRepr::List(value) => value.into(),
+            OverridableVecRepr::WithMode { mode, value } => Self::new_with_mode(value, mode),
+        }
+    }
+}
+
+impl<T: Eq + Clone + Hash> From<OverridableVec<T>> for OverridableVecRepr<T> {
+    fn from(overridable: OverridableVec<T>) -> Self {
+        if overridable.mode == MergeMode::Prepend {
+            Self::List(overridable.value)
+        } else {
+            Self::WithMode {
+                mode: overridable.mode,
+                value: overridable.value,
+            }
+        }
+    }
+}
+
 impl<T: Eq + Clone + Hash> Deref for OverridableVec<T> {
     type Target = Vec<T>;
 
@@ -57,7 +184,7 @@ impl<T: Eq + Clone + Hash> DerefMut for OverridableVec<T> {
 
 impl<T: Eq + Clone + Hash> PartialEq for OverridableVec<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.value == other.value
+        self.value == other.value && self.mode == other.mode
     }
 }
 
@@ -65,7 +192,8 @@ impl<T: Eq + Clone + Hash> Eq for OverridableVec<T> {}
 
 impl<T: Eq + Clone + Hash> Hash for OverridableVec<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.value.hash(state)
+        self.value.hash(state);
+        self.mode.hash(state)
     }
 }
 
@@ -113,13 +241,15 @@ impl UserConfig {
     /// - ``res_ord`` : the order of resolution. They are separated with a ``:``  character.
     /// - ``form_ord`` : the order of format. Also use a ``:`` for separation.
     ///
-    /// if ``nc-<key>`` is equal to ``t`` (for ``true``), all the child that will be merged with lower priority with [`UserConfig::add_config_prioritary`] are ignored.
-    /// all other keys are silently ignored
+    /// ``m-<key>`` set the [`MergeMode`] used when merging with lower priority config with [`UserConfig::add_config_prioritary`]. Its value is one of ``prepend``, ``append``, ``replace`` or ``remove`` (see [`MergeMode::as_str`]).
+    ///
+    /// if ``nc-<key>`` is equal to ``t`` (for ``true``), this is the same as setting ``m-<key>`` to ``replace`` (all the child that will be merged with lower priority are ignored).
+    /// all other keys (and invalid mode) are silently ignored
     ///
     /// # Example
     ///
     /// ```
-    /// use kodi_rust::UserConfig;
+    /// use kodi_rust::{MergeMode, UserConfig};
     /// use std::collections::HashMap;
     ///
     /// let mut config = HashMap::new();
@@ -127,14 +257,18 @@ impl UserConfig {
     /// config.insert("res_ord".into(), "1080p".into());
     /// config.insert("nc-res_ord".into(), "t".into());
     /// config.insert("nc-inv".into(), "t".into());
+    /// config.insert("form_ord".into(), "webm".into());
+    /// config.insert("m-form_ord".into(), "remove".into());
     /// config.insert("useless".into(), "none".into());
     ///
     /// let user_config = UserConfig::new_from_dict(config);
     ///
     /// assert_eq!(*user_config.language_order, vec!["fr".to_string(), "en".to_string()]);
+    /// assert_eq!(user_config.language_order.mode, MergeMode::Prepend);
     /// assert_eq!(*user_config.resolution_order, vec!["1080p".to_string()]);
-    /// assert_eq!(user_config.resolution_order.no_child, true);
-    /// assert_eq!(*user_config.format_order, Vec::<String>::new());
+    /// assert_eq!(user_config.resolution_order.mode, MergeMode::Replace);
+    /// assert_eq!(*user_config.format_order, vec!["webm".to_string()]);
+    /// assert_eq!(user_config.format_order.mode, MergeMode::Remove);
     /// ```
     pub fn new_from_dict(mut dict: HashMap<String, String>) -> Self {
         let dict_ref_mut = &mut dict;
@@ -145,7 +279,14 @@ impl UserConfig {
                     **result = split_double_dot(list);
                 }
                 if let Some(first) = dict_ref_mut.remove(&format!("nc-{}", keyword)) {
-                    result.no_child = &first == "t";
+                    if first == "t" {
+                        result.mode = MergeMode::Replace;
+                    }
+                }
+                if let Some(mode) = dict_ref_mut.remove(&format!("m-{}", keyword)) {
+                    if let Some(mode) = MergeMode::from_str(&mode) {
+                        result.mode = mode;
+                    }
                 }
             };
 
@@ -177,9 +318,16 @@ impl UserConfig {
         }
 
         let mut result = HashMap::new();
-        result.insert("lang_ord".into(), add_double_dot(&*self.language_order));
-        result.insert("res_ord".into(), add_double_dot(&*self.resolution_order));
-        result.insert("form_ord".into(), add_double_dot(&*self.format_order));
+        for (key, list) in &[
+            ("lang_ord", &self.language_order),
+            ("res_ord", &self.resolution_order),
+            ("form_ord", &self.format_order),
+        ] {
+            result.insert(key.to_string(), add_double_dot(&list.value));
+            if list.mode != MergeMode::Prepend {
+                result.insert(format!("m-{}", key), list.mode.as_str().to_string());
+            }
+        }
         result
     }
 
@@ -244,11 +392,12 @@ impl UserConfig {
     /// # Example
     ///
     /// ```
-    /// use kodi_rust::UserConfig;
+    /// use kodi_rust::{MergeMode, OverridableVec, UserConfig};
     ///
     /// let mut source = UserConfig::new_empty();
     /// *source.language_order = vec!["fr".into(), "!nv/li-=d".into()];
     /// *source.resolution_order = vec!["la%li!".into()];
+    /// source.format_order = OverridableVec::new_with_mode(vec!["webm".into()], MergeMode::Remove);
     ///
     /// assert_eq!(
     ///     UserConfig::new_from_optional_uri(Some(source.encode_to_uri())),
@@ -280,12 +429,18 @@ impl UserConfig {
     ///
     /// after merging, the element are deduplicated (implemented by a call to [`UserConfig::clean`])
     ///
-    /// if the no_child value is set, then the value of ``Self`` are ignored for this list, and no_child is set to false in the returned value.
+    /// each list of ``prio`` is merged according to its [`MergeMode`]:
+    /// - [`MergeMode::Prepend`]: the values of ``prio`` are put before the values of ``Self``
+    /// - [`MergeMode::Append`]: the values of ``prio`` are put after the values of ``Self`` (moving them at the end if they are already present)
+    /// - [`MergeMode::Replace`]: the values of ``Self`` are ignored
+    /// - [`MergeMode::Remove`]: the values of ``Self`` are used, except the one present in ``prio``
+    ///
+    /// the mode of the returned lists is reset to [`MergeMode::Prepend`].
     ///
     /// # Example
     ///
     /// ```
-    /// use kodi_rust::UserConfig;
+    /// use kodi_rust::{MergeMode, UserConfig};
     ///
     /// let mut static_config = UserConfig::new_empty();
     /// *static_config.language_order = vec!["fr".into()];
@@ -296,26 +451,44 @@ impl UserConfig {
     /// *dynamic_config.language_order = vec!["en".into()];
     /// *dynamic_config.resolution_order = vec!["720p".into()];
     /// *dynamic_config.format_order = vec!["ogv".into()];
-    /// dynamic_config.format_order.no_child = true;
+    /// dynamic_config.format_order.mode = MergeMode::Replace;
     ///
     /// let result_config = static_config.add_config_prioritary(dynamic_config);
     /// assert_eq!(*result_config.language_order, vec!["en".to_string(), "fr".to_string()]);
     /// assert_eq!(&*result_config.resolution_order[0], "720p");
     /// assert_eq!(&*result_config.resolution_order[1], "1080p");
     /// assert_eq!(*result_config.format_order, vec!["ogv".to_string()]);
-    /// assert_eq!(result_config.format_order.no_child, false);
+    /// assert_eq!(result_config.format_order.mode, MergeMode::Prepend);
+    /// ```
+    ///
+    /// Everything from the static config, except ``1080p``, with ``webm`` moved to the end:
+    ///
+    /// ```
+    /// use kodi_rust::{MergeMode, OverridableVec, UserConfig};
+    ///
+    /// let mut static_config = UserConfig::new_empty();
+    /// *static_config.resolution_order = vec!["1080p".into(), "720p".into(), "480p".into()];
+    /// *static_config.format_order = vec!["webm".into(), "mp4".into()];
+    ///
+    /// let mut dynamic_config = UserConfig::new_empty();
+    /// dynamic_config.resolution_order = OverridableVec::new_with_mode(vec!["1080p".into()], MergeMode::Remove);
+    /// dynamic_config.format_order = OverridableVec::new_with_mode(vec!["webm".into()], MergeMode::Append);
+    ///
+    /// let result_config = static_config.add_config_prioritary(dynamic_config);
+    /// assert_eq!(*result_config.resolution_order, vec!["720p".to_string(), "480p".to_string()]);
+    /// assert_eq!(*result_config.format_order, vec!["mp4".to_string(), "webm".to_string()]);
     /// ```
     pub fn add_config_prioritary(self, prio: Self) -> Self {
         let mut result = prio;
         result
             .language_order
-            .add_child_and_reset_no_child(self.language_order);
+            .add_child_and_reset_mode(self.language_order);
         result
             .resolution_order
-            .add_child_and_reset_no_child(self.resolution_order);
+            .add_child_and_reset_mode(self.resolution_order);
         result
             .format_order
-            .add_child_and_reset_no_child(self.format_order);
+            .add_child_and_reset_mode(self.format_order);
         result.clean();
         result
     }
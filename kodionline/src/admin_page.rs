@@ -0,0 +1,401 @@
+use crate::{
+    debug_page::get_debug_plugin_path, feed_page::format_duration, reload::CurrentSetting,
+    Presentation, UiLocale, LOCALES,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use fluent_templates::Loader;
+use kodi_rust::{
+    data::KodiResult, get_addon_id, input::encode_input, Kodi, LinkState, PathAccessData,
+};
+use log::warn;
+use maud::{html, Markup, PreEscaped};
+use rocket::{
+    form::Form,
+    http::{Header, Method, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Redirect, Responder},
+    Request, Response, State,
+};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use unic_langid::LanguageIdentifier;
+
+/// Request guard checking the password of the administration pages, given with the HTTP basic authentication (with any user name). The administration pages don't exist if [`kodi_rust::Setting::admin_password`] isn't set.
+///
+/// A POST request must come from a page of the server itself, as the browser send the password with the forms of other sites too.
+pub struct Admin;
+
+/// compare ``given`` and ``expected`` in a time that doesn't depend on where they differ, so the password can't be guessed from the response time
+fn is_same_password(given: &str, expected: &str) -> bool {
+    let given = Sha256::digest(given.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    given
+        .iter()
+        .zip(expected.iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+/// return true if the ``Origin`` header (or the ``Referer`` header if there is none) of ``request`` is the server itself, according to its ``Host`` header
+fn is_same_origin(request: &Request<'_>) -> bool {
+    let headers = request.headers();
+    let host = match headers.get_one("Host") {
+        Some(value) => value,
+        None => return false,
+    };
+    match headers
+        .get_one("Origin")
+        .or_else(|| headers.get_one("Referer"))
+    {
+        Some(source) => source
+            .split_once("://")
+            .is_some_and(|(_, rest)| rest.split('/').next() == Some(host)),
+        None => false,
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let password = match request.guard::<&CurrentSetting>().await {
+            Outcome::Success(setting) => setting.admin_password.clone(),
+            _ => None,
+        };
+        let password = match password {
+            Some(value) => value,
+            None => return Outcome::Error((Status::NotFound, ())),
+        };
+        // the browser send the password with the forms of other sites too, so they are refused
+        if request.method() == Method::Post
+            && (!is_same_origin(request)
+                || matches!(
+                    request.headers().get_one("Sec-Fetch-Site"),
+                    Some(site) if site != "same-origin" && site != "none"
+                ))
+        {
+            return Outcome::Error((Status::Forbidden, ()));
+        };
+        let given_password = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|value| STANDARD.decode(value.trim()).ok())
+            .and_then(|value| String::from_utf8(value).ok())
+            .and_then(|value| value.split_once(':').map(|(_, given)| given.to_string()));
+        if given_password.is_some_and(|given| is_same_password(&given, &password)) {
+            Outcome::Success(Admin)
+        } else {
+            Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+/// Ask the browser for the password of the administration pages
+pub struct AuthenticationRequired;
+
+impl<'r> Responder<'r, 'static> for AuthenticationRequired {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::Unauthorized)
+            .header(Header::new(
+                "WWW-Authenticate",
+                "Basic realm=\"kodionline administration\", charset=\"UTF-8\"",
+            ))
+            .ok()
+    }
+}
+
+#[catch(401)]
+pub fn catch_unauthorized() -> AuthenticationRequired {
+    AuthenticationRequired
+}
+
+#[derive(FromForm)]
+pub struct KillForm {
+    id: u64,
+}
+
+#[derive(FromForm)]
+pub struct EvictForm {
+    /// the key of the cache entry, as returned by [`get_entry_key`]
+    entry: u64,
+}
+
+#[derive(FromForm)]
+pub struct EvictPluginForm {
+    addon: String,
+}
+
+/// return a key identifying the cache entry of ``access`` in the forms, valid until the server restart
+fn get_entry_key(access: &PathAccessData) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    access.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// render the path and the input of ``access``
+pub fn render_access(access: &PathAccessData) -> Markup {
+    html!(
+        code { (access.path) }
+        @if !access.input.is_empty() {
+            " " code class="admin_input" { (encode_input(&access.input)) }
+        }
+    )
+}
+
+fn render_age(age: Duration, locale: &LanguageIdentifier) -> String {
+    let mut args = HashMap::new();
+    args.insert("duration".into(), format_duration(age.as_secs()).into());
+    LOCALES.lookup_with_args(locale, "admin-ago", &args)
+}
+
+fn render_result_summary(result: &KodiResult, locale: &LanguageIdentifier) -> String {
+    match result {
+        KodiResult::Content(page) if page.resolved_listitem.is_some() => {
+            LOCALES.lookup(locale, "admin-result-media")
+        }
+        KodiResult::Content(page) => {
+            let mut args = HashMap::new();
+            args.insert("count".into(), page.sub_content.len().into());
+            LOCALES.lookup_with_args(locale, "admin-result-folder", &args)
+        }
+        KodiResult::Keyboard(_) => LOCALES.lookup(locale, "admin-result-keyboard"),
+    }
+}
+
+/// render a button sending a POST request to ``action``, with the hidden ``fields``
+pub fn render_action_button(action: &str, fields: &[(&str, String)], label: String) -> Markup {
+    html!(
+        form method="post" action=(action) class="inline_form" {
+            @for (name, value) in fields {
+                input type="hidden" name=(name) value=(value) {}
+            }
+            input type="submit" value=(label) {}
+        }
+    )
+}
+
+/// show the state of [`Kodi`]: the running plugins, the health of each plugin, the last invocations and the content of the cache
+#[get("/admin")]
+pub fn render_admin(
+    _admin: Admin,
+    kodi: &State<Kodi>,
+    setting: &CurrentSetting,
+    ui_locale: UiLocale,
+) -> PreEscaped<String> {
+    let locale = ui_locale.0;
+    let token_secret = setting.state_token_secret.as_deref();
+    let running = kodi.running_invocations();
+    let plugin_stats = kodi.plugin_stats();
+    let history = kodi.invocation_history();
+    // the cache entries grouped by addon
+    let mut cache: BTreeMap<String, Vec<_>> = BTreeMap::new();
+    for entry in kodi.cache_entries() {
+        cache
+            .entry(
+                get_addon_id(&entry.access.path)
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+            .or_default()
+            .push(entry);
+    }
+
+    Presentation::new(
+        html!((LOCALES.lookup(&locale, "admin"))),
+        html!(
+            h2 { (LOCALES.lookup(&locale, "admin-running")) }
+            @if running.is_empty() {
+                p { (LOCALES.lookup(&locale, "admin-none")) }
+            } @else {
+                table class="list_table" {
+                    tr {
+                        th { (LOCALES.lookup(&locale, "admin-path")) }
+                        th { (LOCALES.lookup(&locale, "admin-started")) }
+                        th { "PID" }
+                        th {}
+                    }
+                    @for invocation in &running {
+                        tr {
+                            td { (render_access(&invocation.access)) }
+                            td { (render_age(invocation.started.elapsed().unwrap_or_default(), &locale)) }
+                            td { @if let Some(pid) = invocation.pid { (pid) } }
+                            td { (render_action_button("/admin/kill", &[("id", invocation.id.to_string())], LOCALES.lookup(&locale, "admin-kill"))) }
+                        }
+                    }
+                }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "admin-plugins")) }
+            @if plugin_stats.is_empty() {
+                p { (LOCALES.lookup(&locale, "admin-none")) }
+            } @else {
+                table class="list_table" {
+                    tr {
+                        th { (LOCALES.lookup(&locale, "admin-plugin")) }
+                        th { (LOCALES.lookup(&locale, "admin-successes")) }
+                        th { (LOCALES.lookup(&locale, "admin-failures")) }
+                        th { (LOCALES.lookup(&locale, "admin-success-rate")) }
+                    }
+                    @for (addon_id, stats) in &plugin_stats {
+                        tr class=[if stats.success_rate() < 0.5 { Some("unhealthy") } else { None }] {
+                            td { code { (addon_id) } }
+                            td { (stats.success) }
+                            td { (stats.failure) }
+                            td { (format!("{:.0} %", stats.success_rate() * 100.0)) }
+                        }
+                    }
+                }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "admin-history")) }
+            @if history.is_empty() {
+                p { (LOCALES.lookup(&locale, "admin-none")) }
+            } @else {
+                table class="list_table" {
+                    tr {
+                        th { (LOCALES.lookup(&locale, "admin-path")) }
+                        th { (LOCALES.lookup(&locale, "admin-started")) }
+                        th { (LOCALES.lookup(&locale, "word-duration")) }
+                        th { (LOCALES.lookup(&locale, "admin-exit-status")) }
+                        th { (LOCALES.lookup(&locale, "admin-log")) }
+                        th {}
+                    }
+                    @for invocation in &history {
+                        tr class=[if invocation.success { None } else { Some("unhealthy") }] {
+                            td { (render_access(&invocation.access)) }
+                            td { (render_age(invocation.started.elapsed().unwrap_or_default(), &locale)) }
+                            td { (format!("{:.2} s", invocation.duration.as_secs_f64())) }
+                            td { (invocation.exit_status.as_deref().unwrap_or("-")) }
+                            td {
+                                @match &invocation.log {
+                                    Some(log) if !log.is_empty() => {
+                                        details {
+                                            summary { (LOCALES.lookup(&locale, "admin-show-log")) }
+                                            pre { (log) }
+                                        }
+                                    }
+                                    _ => { "-" }
+                                }
+                            }
+                            td { a href=(get_debug_plugin_path(&LinkState::new(invocation.access.clone(), None), token_secret)) { (LOCALES.lookup(&locale, "admin-debug")) } }
+                        }
+                    }
+                }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "admin-cache")) }
+            @if cache.is_empty() {
+                p { (LOCALES.lookup(&locale, "admin-none")) }
+            }
+            @for (addon_id, entries) in &cache {
+                h3 {
+                    code { (addon_id) } " "
+                    (render_action_button("/admin/evict_plugin", &[("addon", addon_id.clone())], LOCALES.lookup(&locale, "admin-evict-plugin")))
+                }
+                table class="list_table" {
+                    tr {
+                        th { (LOCALES.lookup(&locale, "admin-path")) }
+                        th { (LOCALES.lookup(&locale, "admin-stored")) }
+                        th { (LOCALES.lookup(&locale, "admin-result")) }
+                        th {}
+                    }
+                    @for entry in entries {
+                        tr {
+                            td { (render_access(&entry.access)) }
+                            td { (render_age(entry.age, &locale)) }
+                            td { (render_result_summary(&entry.result, &locale)) }
+                            td { (render_action_button("/admin/evict", &[("entry", get_entry_key(&entry.access).to_string())], LOCALES.lookup(&locale, "admin-evict"))) }
+                        }
+                    }
+                }
+            }
+        ),
+    )
+    .build(&locale)
+}
+
+/// kill the python process of a running invocation
+#[post("/admin/kill", data = "<form>")]
+pub fn kill_invocation(_admin: Admin, kodi: &State<Kodi>, form: Form<KillForm>) -> Redirect {
+    if !kodi.kill_invocation(form.id) {
+        warn!(
+            "can't kill the invocation {}, it probably already ended",
+            form.id
+        );
+    };
+    Redirect::to("/admin")
+}
+
+/// remove an entry from the cache
+#[post("/admin/evict", data = "<form>")]
+pub fn evict_cache_entry(_admin: Admin, kodi: &State<Kodi>, form: Form<EvictForm>) -> Redirect {
+    for entry in kodi.cache_entries() {
+        if get_entry_key(&entry.access) == form.entry {
+            kodi.evict_cache_entry(&entry.access);
+        };
+    }
+    Redirect::to("/admin")
+}
+
+/// remove every entry of an addon from the cache
+#[post("/admin/evict_plugin", data = "<form>")]
+pub fn evict_plugin_cache(
+    _admin: Admin,
+    kodi: &State<Kodi>,
+    form: Form<EvictPluginForm>,
+) -> Redirect {
+    kodi.evict_plugin_cache(&form.addon);
+    Redirect::to("/admin")
+}
+
+#[test]
+fn test_admin_guard() {
+    use crate::reload::SettingHandle;
+    use kodi_rust::Setting;
+    use rocket::http::ContentType;
+
+    let setting = Setting {
+        admin_password: Some("secret".into()),
+        ..Setting::default()
+    };
+    let rocket = rocket::build()
+        .manage(SettingHandle::new(setting))
+        .manage(Kodi::new("/nonexistent", 0, 1))
+        .mount("/", routes![kill_invocation]);
+    let client = rocket::local::blocking::Client::tracked(rocket).unwrap();
+    let kill = |password: &str, origin: Option<&str>| {
+        let mut request = client
+            .post("/admin/kill")
+            .header(ContentType::Form)
+            .header(Header::new("Host", "example.com"))
+            .header(Header::new(
+                "Authorization",
+                format!("Basic {}", STANDARD.encode(format!("admin:{}", password))),
+            ))
+            .body("id=1");
+        if let Some(origin) = origin {
+            request = request.header(Header::new("Origin", origin.to_string()));
+        };
+        request.dispatch().status()
+    };
+    assert_eq!(
+        kill("secret", Some("https://example.com")),
+        Status::SeeOther
+    );
+    assert_eq!(
+        kill("wrong", Some("https://example.com")),
+        Status::Unauthorized
+    );
+    assert_eq!(kill("secret", None), Status::Forbidden);
+    assert_eq!(kill("secret", Some("https://other.com")), Status::Forbidden);
+    assert_eq!(
+        kill("secret", Some("https://example.com.other.com")),
+        Status::Forbidden
+    );
+}
@@ -0,0 +1,340 @@
+use crate::{
+    get_absolute_plugin_path, get_child_link, get_final_user_config, rate_limit::Client,
+    reload::CurrentSetting, AcceptLanguage, LinkParams, PluginRequest,
+};
+use kodi_rust::{
+    data::{Keyboard, KodiResult, ListItem, SubContent},
+    get_art_link_subcontent, get_media_link_resolved_url, get_media_link_subcontent,
+    get_sub_content_from_parent, Kodi, KodiError, LinkState, PathAccessData, UserConfig,
+};
+
+use log::error;
+use rocket::http::{ContentType, CookieJar, MediaType, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::status::Custom;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use std::collections::HashMap;
+
+static OPENAPI_DOCUMENT: &str = include_str!("../static/openapi.json");
+
+/// The links related to an element, already computed
+#[derive(Serialize)]
+pub struct ApiLinks {
+    /// the link to the JSON representation of this element
+    pub api: String,
+    /// the link to the HTML page of this element
+    pub page: String,
+    /// the link to the media, if this element is playable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,
+    /// the link to the art of this element, by category
+    pub arts: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiSubContent {
+    #[serde(flatten)]
+    pub sub_content: SubContent,
+    pub links: ApiLinks,
+}
+
+#[derive(Serialize)]
+pub struct ApiResolvedListItem {
+    #[serde(flatten)]
+    pub listitem: ListItem,
+    /// the link to the media
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,
+}
+
+/// The JSON representation of a plugin page. It mirror [`KodiResult`], with links already computed.
+#[derive(Serialize)]
+#[serde(tag = "type")]
+pub enum ApiPluginResult {
+    Content {
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        sub_content: Vec<ApiSubContent>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resolved_listitem: Option<Box<ApiResolvedListItem>>,
+    },
+    Keyboard {
+        path: String,
+        #[serde(flatten)]
+        keyboard: Keyboard,
+        /// the link to call with the ``additional_input`` parameter added to answer the keyboard prompt
+        submit: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct ApiAddon {
+    pub label: String,
+    pub path: String,
+    pub links: ApiLinks,
+}
+
+fn api_error(status: Status, message: String) -> Custom<Json<ApiPluginResult>> {
+    Custom(status, Json(ApiPluginResult::Error { message }))
+}
+
+fn get_api_plugin_path(link: &LinkState, token_secret: Option<&str>) -> String {
+    format!("/api/v1{}", get_absolute_plugin_path(link, token_secret).0)
+}
+
+fn get_sub_content_links(
+    sub_content: &SubContent,
+    current: &LinkState,
+    token_secret: Option<&str>,
+) -> ApiLinks {
+    let child = get_child_link(current, sub_content.url.clone());
+    ApiLinks {
+        api: get_api_plugin_path(&child, token_secret),
+        page: get_absolute_plugin_path(&child, token_secret).0,
+        media: if sub_content.listitem.is_playable() {
+            Some(get_media_link_subcontent(
+                sub_content,
+                current,
+                token_secret,
+            ))
+        } else {
+            None
+        },
+        arts: sub_content
+            .listitem
+            .arts
+            .iter()
+            .filter(|(_, url)| url.is_some())
+            .map(|(category, _)| {
+                (
+                    category.clone(),
+                    get_art_link_subcontent(sub_content, category, current, token_secret),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// return the JSON representation of a plugin page, as [`ApiPluginResult`]. It take the same parameter as [`crate::plugin_page::render_plugin`].
+#[get("/api/v1/plugin?<additional_input>&<link..>")]
+pub fn api_plugin(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    link: LinkParams,
+) -> Custom<Json<ApiPluginResult>> {
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => return api_error(Status::BadRequest, err.to_string()),
+    };
+
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+
+    let PluginRequest { link, final_link } =
+        PluginRequest::new(link_state, additional_input, final_config);
+    let current_access = final_link.access;
+
+    let subcontent_from_parent = final_link.parent.as_ref().and_then(|parent_access| {
+        get_sub_content_from_parent(
+            kodi,
+            &client.secondary(),
+            parent_access,
+            &current_access.path,
+        )
+    });
+
+    match kodi.invoke_sandbox_for(&current_access, client) {
+        Ok(KodiResult::Content(page)) => {
+            let resolved_listitem = page.resolved_listitem.map(|mut resolved_listitem| {
+                if let Some(subcontent_from_parent) = &subcontent_from_parent {
+                    resolved_listitem.extend(subcontent_from_parent.listitem.clone());
+                }
+                let media = resolved_listitem
+                    .path
+                    .as_ref()
+                    .map(|media_url| get_media_link_resolved_url(media_url, &link, token_secret));
+                Box::new(ApiResolvedListItem {
+                    listitem: resolved_listitem,
+                    media,
+                })
+            });
+
+            let label = match subcontent_from_parent {
+                Some(subcontent) => subcontent.listitem.label,
+                None => setting.get_label_for_path(&current_access.path),
+            };
+
+            let sub_content = page
+                .sub_content
+                .into_iter()
+                .map(|sub_content| ApiSubContent {
+                    links: get_sub_content_links(&sub_content, &link, token_secret),
+                    sub_content,
+                })
+                .collect();
+
+            Custom(
+                Status::Ok,
+                Json(ApiPluginResult::Content {
+                    path: current_access.path,
+                    label,
+                    sub_content,
+                    resolved_listitem,
+                }),
+            )
+        }
+        Ok(KodiResult::Keyboard(keyboard)) => Custom(
+            Status::Ok,
+            Json(ApiPluginResult::Keyboard {
+                submit: get_api_plugin_path(&link, token_secret),
+                path: current_access.path,
+                keyboard,
+            }),
+        ),
+        Err(err) => {
+            error!(
+                "error while getting the page at {} for the api: {:?}",
+                current_access.path, err
+            );
+            let status = match err {
+                KodiError::NotAPlugin(_) | KodiError::AddonNotAllowed(_) => Status::Forbidden,
+                _ => Status::InternalServerError,
+            };
+            api_error(status, err.to_string())
+        }
+    }
+}
+
+/// Request guard that only succeed when JSON is the preferred media type in the ``Accept`` header.
+///
+/// A wildcard like ``*/*`` isn't enough, so simple HTTP clients still get the HTML page.
+pub struct PrefersJson;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PrefersJson {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.accept() {
+            Some(accept) if accept.preferred().media_type() == &MediaType::JSON => {
+                Outcome::Success(PrefersJson)
+            }
+            _ => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+/// the same as [`api_plugin`], used when a client ask for JSON with the ``Accept`` header on ``/plugin``
+#[allow(clippy::too_many_arguments)]
+#[get("/plugin?<additional_input>&<link..>", rank = 1)]
+pub fn api_plugin_negotiated(
+    _prefers_json: PrefersJson,
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    link: LinkParams,
+) -> Custom<Json<ApiPluginResult>> {
+    api_plugin(
+        kodi,
+        client,
+        setting,
+        cookies,
+        accept_language,
+        additional_input,
+        link,
+    )
+}
+
+/// return the resolved listitem of a media, with the link to the media. It take the same parameter as [`crate::redirect_page::redirect_media`].
+#[get("/api/v1/media?<link..>")]
+pub fn api_media(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    link: LinkParams,
+) -> Custom<Json<ApiPluginResult>> {
+    match api_plugin(kodi, client, setting, cookies, accept_language, None, link) {
+        Custom(
+            status,
+            Json(ApiPluginResult::Content {
+                path,
+                label,
+                resolved_listitem: Some(resolved_listitem),
+                ..
+            }),
+        ) => Custom(
+            status,
+            Json(ApiPluginResult::Content {
+                path,
+                label,
+                sub_content: Vec::new(),
+                resolved_listitem: Some(resolved_listitem),
+            }),
+        ),
+        Custom(_, Json(ApiPluginResult::Content { path, .. })) => {
+            api_error(Status::NotFound, format!("no media were found at {}", path))
+        }
+        Custom(_, Json(ApiPluginResult::Keyboard { path, .. })) => api_error(
+            Status::NotFound,
+            format!("an input is required to get the media at {}", path),
+        ),
+        other => other,
+    }
+}
+
+/// return the list of addons shown on the main page, without those the client can't call
+#[get("/api/v1/addons")]
+pub fn api_addons(setting: &CurrentSetting, client: &Client) -> Json<Vec<ApiAddon>> {
+    let token_secret = setting.state_token_secret.as_deref();
+    Json(
+        setting
+            .plugins_to_show
+            .iter()
+            .filter(|(_, path)| client.is_addon_allowed(path))
+            .map(|(label, path)| {
+                let link = LinkState::new(
+                    PathAccessData::new(path.clone(), None, UserConfig::new_empty()),
+                    None,
+                );
+                ApiAddon {
+                    label: label.clone(),
+                    path: path.clone(),
+                    links: ApiLinks {
+                        api: get_api_plugin_path(&link, token_secret),
+                        page: get_absolute_plugin_path(&link, token_secret).0,
+                        media: None,
+                        arts: HashMap::new(),
+                    },
+                }
+            })
+            .collect(),
+    )
+}
+
+/// return the OpenAPI document describing this API
+#[get("/api/v1/openapi.json")]
+pub fn api_openapi() -> (ContentType, &'static str) {
+    (ContentType::JSON, OPENAPI_DOCUMENT)
+}
@@ -0,0 +1,702 @@
+use crate::{
+    is_local_path,
+    listing::{SORT_COOKIE, VIEW_COOKIE},
+    locale::UI_LOCALE_KEY,
+    preferences_page::USER_CONFIG_COOKIE,
+    rate_limit::{run_blocking, Client, RateLimiter},
+    reload::{CurrentSetting, SettingHandle},
+    user_data::{UserData, UserDataError, PROFILE_COOKIE},
+    Presentation, UiLocale, LOCALES,
+};
+use argon2::{
+    password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use fluent_templates::Loader;
+use kodi_rust::{urlencode, AuthMode, Setting};
+use log::{error, info, warn};
+use maud::{html, Markup};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    form::Form,
+    http::{uri::Origin, Cookie, CookieJar, Method, SameSite, Status},
+    request::{FromRequest, Outcome},
+    response::Redirect,
+    time, Data, Request, Response, State,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+use unic_langid::LanguageIdentifier;
+
+/// name of the cookie that store the token of the session of a logged in user
+pub const SESSION_COOKIE: &str = "session";
+
+/// the cookies holding the preferences of the visitor. When [`Setting::auth_mode`] is set, they are saved for each user, and restored in every browser they log in with.
+pub const USER_COOKIES: &[&str] = &[USER_CONFIG_COOKIE, SORT_COOKIE, VIEW_COOKIE, UI_LOCALE_KEY];
+
+/// number of failed logins allowed to an IP address in [`FAILED_LOGIN_WINDOW`]
+const MAX_FAILED_LOGINS: usize = 5;
+
+const FAILED_LOGIN_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug)]
+/// represent error that can happen while looking for the password of a user
+pub enum AuthError {
+    DatabaseError(UserDataError),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DatabaseError(_) => write!(f, "can't get the user from the database"),
+        }
+    }
+}
+
+impl Error for AuthError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::DatabaseError(err) => Some(err),
+        }
+    }
+}
+
+/// hash ``password`` with argon2 and a random salt, to store it in [`Setting::users_file`] or in the database
+///
+/// # Example
+///
+/// ```
+/// use kodionline::auth::{hash_password, verify_password};
+///
+/// let hash = hash_password("secret").unwrap();
+/// assert!(hash.starts_with("$argon2"));
+/// assert!(verify_password("secret", &hash));
+/// assert!(!verify_password("other", &hash));
+/// ```
+pub fn hash_password(password: &str) -> Result<String, password_hash::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// return true if ``password`` match ``hash``, as created by [`hash_password`]. An invalid hash never match.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(err) => {
+            error!("invalid password hash: {}", err);
+            false
+        }
+    }
+}
+
+/// return a hash of a random password, with the same parameters as [`hash_password`]. It is checked in place of the hash of a missing user, so the time taken by a login doesn't reveal which users exist.
+fn get_dummy_hash() -> &'static str {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        let password = SaltString::generate(&mut rand::thread_rng());
+        hash_password(password.as_str()).unwrap_or_default()
+    })
+}
+
+/// return the password hash of the user ``name``, from the users of [`Setting::users_file`] if it is set, or from the database otherwise
+///
+/// # Errors
+///
+/// return an error if the database can't be read
+pub fn get_password_hash(
+    setting: &Setting,
+    user_data: &UserData,
+    name: &str,
+) -> Result<Option<String>, AuthError> {
+    if setting.users_file.is_some() {
+        return Ok(setting.users.get(name).cloned());
+    };
+    user_data
+        .get_password_hash(name)
+        .map_err(AuthError::DatabaseError)
+}
+
+/// return the identifier of the browser profile of ``user``, that hold its favourites and history
+///
+/// # Example
+///
+/// ```
+/// use kodionline::auth::get_user_profile;
+///
+/// assert_eq!(get_user_profile("jean dupont"), "user-jean%20dupont");
+/// ```
+pub fn get_user_profile(user: &str) -> String {
+    format!("user-{}", urlencode(user))
+}
+
+/// return ``next`` if it is a path of this site, or the path of the index otherwise
+fn get_local_path(next: Option<String>) -> String {
+    match next {
+        Some(next) if is_local_path(&next) => next,
+        _ => "/".to_string(),
+    }
+}
+
+/// The user that made a request, as a request guard. It is ``None`` if the visitor isn't authenticated, or if [`Setting::auth_mode`] is ``none``.
+pub struct Identity(pub Option<String>);
+
+impl Identity {
+    fn get<'r>(request: &'r Request<'_>) -> &'r Self {
+        request.local_cache(|| Identity(None))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Identity {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Identity::get(request))
+    }
+}
+
+/// return the user of the session cookie of ``request``, if it is valid. A session is closed if its user was removed, or if its password changed since it was opened.
+fn get_session_user(request: &Request<'_>, setting: &Setting) -> Option<String> {
+    let token = request.cookies().get(SESSION_COOKIE)?.value();
+    let user_data = request.rocket().state::<UserData>()?;
+    let (user, session_hash) = match user_data.get_session(token) {
+        Ok(value) => value?,
+        Err(err) => {
+            error!("can't get the session: {}", err);
+            return None;
+        }
+    };
+    match get_password_hash(setting, user_data, &user) {
+        Ok(Some(hash)) if hash == session_hash => Some(user),
+        Ok(_) => {
+            info!(
+                "closing a session of {:?}, whose account was removed or changed",
+                user
+            );
+            if let Err(err) = user_data.remove_session(token) {
+                error!("can't remove the session: {}", err);
+            };
+            None
+        }
+        Err(err) => {
+            match err.source() {
+                Some(source) => error!("{}: {}", err, source),
+                None => error!("{}", err),
+            };
+            None
+        }
+    }
+}
+
+/// return the user given by the reverse proxy in [`Setting::auth_header`], if ``request`` come from one of [`Setting::auth_proxies`]
+fn get_proxy_user(request: &Request<'_>, setting: &Setting) -> Option<String> {
+    let trusted = request
+        .remote()
+        .is_some_and(|remote| setting.auth_proxies.contains(&remote.ip()));
+    if !trusted {
+        warn!(
+            "ignoring the {} header sent by {:?}, which isn't in auth_proxies",
+            setting.auth_header,
+            request.remote()
+        );
+        return None;
+    };
+    request
+        .headers()
+        .get_one(&setting.auth_header)
+        .map(str::trim)
+        .filter(|user| !user.is_empty())
+        .map(str::to_string)
+}
+
+/// Fairing authenticating the visitors according to [`Setting::auth_mode`], with a session cookie or a header set by a reverse proxy.
+///
+/// The requests of unauthenticated visitors are answered by the login page. When a user log in, the preferences of the browser are replaced by the one of the user (see [`USER_COOKIES`]), and its favourites and history are those of its own profile. The preferences it change are then saved for it. The addons it can call are restricted by [`Setting::user_allowed_addons`].
+///
+/// It should be attached after [`crate::rate_limit::RateLimit`], which create the [`Client`] it restrict.
+pub struct Authentication;
+
+#[rocket::async_trait]
+impl Fairing for Authentication {
+    fn info(&self) -> Info {
+        Info {
+            name: "authentication",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let setting = match request.rocket().state::<SettingHandle>() {
+            Some(handle) => handle.get(),
+            None => return,
+        };
+        let user = match setting.auth_mode {
+            AuthMode::None => return,
+            AuthMode::Password => get_session_user(request, &setting),
+            AuthMode::Proxy => get_proxy_user(request, &setting),
+        };
+        if let Some(addons) = user
+            .as_ref()
+            .and_then(|user| setting.user_allowed_addons.get(user))
+        {
+            Client::get(request).restrict_addons(addons.iter().cloned().collect());
+        };
+
+        let target = match &user {
+            None => Some("/login"),
+            Some(user) => {
+                let profile = request.cookies().get(PROFILE_COOKIE).map(Cookie::value);
+                if profile != Some(get_user_profile(user).as_str()) {
+                    Some("/login/sync")
+                } else {
+                    None
+                }
+            }
+        };
+        request.local_cache(|| Identity(user));
+
+        let path = request.uri().path();
+        if path == "/login" || path.starts_with("/login/") || path.starts_with("/static/") {
+            return;
+        };
+        let target = match target {
+            Some(value) => value,
+            None => return,
+        };
+        // the page is shown again after the login, if it can be
+        let next = if matches!(request.method(), Method::Get | Method::Head) {
+            request.uri().to_string()
+        } else {
+            "/".to_string()
+        };
+        match Origin::parse_owned(format!("{}?next={}", target, urlencode(&next))) {
+            Ok(uri) => {
+                request.set_method(Method::Get);
+                request.set_uri(uri);
+            }
+            Err(err) => error!("can't redirect {} to {}: {}", next, target, err),
+        };
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let user = match &Identity::get(request).0 {
+            Some(value) => value,
+            None => return,
+        };
+        // the cookies set by the handler are already in the response, ``None`` if removed
+        let mut changed: HashMap<String, Option<String>> = HashMap::new();
+        for header in response.headers().get("Set-Cookie") {
+            if let Ok(cookie) = Cookie::parse_encoded(header) {
+                let value = if cookie.max_age() == Some(time::Duration::ZERO) {
+                    None
+                } else {
+                    Some(cookie.value().to_string())
+                };
+                changed.insert(cookie.name().to_string(), value);
+            };
+        }
+        // after a logout, the cookies aren't the one of the user anymore
+        let profile = match changed.get(PROFILE_COOKIE) {
+            Some(value) => value.clone(),
+            None => request
+                .cookies()
+                .get(PROFILE_COOKIE)
+                .map(|cookie| cookie.value().to_string()),
+        };
+        if profile != Some(get_user_profile(user)) {
+            return;
+        };
+        let user_data = match request.rocket().state::<UserData>() {
+            Some(value) => value,
+            None => return,
+        };
+        for name in USER_COOKIES {
+            if let Some(value) = changed.get(*name) {
+                if let Err(err) = user_data.set_user_cookie(user, name, value.as_deref()) {
+                    error!("can't save the cookie {} of {}: {}", name, user, err);
+                };
+            };
+        }
+    }
+}
+
+/// The failed logins of each IP address, so passwords can't be guessed by trying many of them
+pub struct FailedLogins(RateLimiter);
+
+impl Default for FailedLogins {
+    fn default() -> Self {
+        Self(RateLimiter::new(MAX_FAILED_LOGINS, FAILED_LOGIN_WINDOW))
+    }
+}
+
+#[derive(FromForm)]
+pub struct LoginForm {
+    user: String,
+    password: String,
+    next: Option<String>,
+}
+
+/// render the login page, with the message ``error`` if set
+fn render_login_page(
+    setting: &Setting,
+    next: Option<&str>,
+    error: Option<&str>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    Presentation::new(
+        html!((LOCALES.lookup(locale, "login-title"))),
+        html!(
+            @match setting.auth_mode {
+                AuthMode::Password => {
+                    @if let Some(error) = error {
+                        p class="errormessage" { (LOCALES.lookup(locale, error)) }
+                    }
+                    form method="post" action="/login" {
+                        label for="login_user" { (LOCALES.lookup(locale, "login-user")) }
+                        input type="text" id="login_user" name="user" autocomplete="username" required {}
+                        br {}
+                        label for="login_password" { (LOCALES.lookup(locale, "login-password")) }
+                        input type="password" id="login_password" name="password" autocomplete="current-password" required {}
+                        br {}
+                        @if let Some(next) = next {
+                            input type="hidden" name="next" value=(next) {}
+                        }
+                        input type="submit" value=(LOCALES.lookup(locale, "login-submit")) {}
+                    }
+                }
+                AuthMode::Proxy => { p { (LOCALES.lookup(locale, "login-proxy")) } }
+                AuthMode::None => { p { (LOCALES.lookup(locale, "login-disabled")) } }
+            }
+        ),
+    )
+    .build(locale)
+}
+
+/// the login page. Unauthenticated visitors get it in place of the page they asked for, in which case ``next`` is that page.
+#[get("/login?<next>")]
+pub fn render_login(
+    setting: &CurrentSetting,
+    ui_locale: UiLocale,
+    next: Option<String>,
+) -> (Status, Markup) {
+    let status = match setting.auth_mode {
+        AuthMode::None => Status::NotFound,
+        _ if next.is_some() => Status::Unauthorized,
+        _ => Status::Ok,
+    };
+    (
+        status,
+        render_login_page(setting, next.as_deref(), None, &ui_locale.0),
+    )
+}
+
+/// check the password of the user, and open a session for it if it is correct. The IP addresses that failed too many times are refused for a while, without checking the password.
+#[allow(clippy::too_many_arguments)]
+#[post("/login", data = "<form>")]
+pub fn login(
+    setting: &CurrentSetting,
+    user_data: &State<UserData>,
+    failed_logins: &State<FailedLogins>,
+    client: &Client,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    form: Form<LoginForm>,
+) -> Result<Redirect, (Status, Markup)> {
+    let locale = ui_locale.0;
+    let form = form.into_inner();
+    let failure = |status, error| {
+        (
+            status,
+            render_login_page(setting, form.next.as_deref(), Some(error), &locale),
+        )
+    };
+    if setting.auth_mode != AuthMode::Password {
+        return Err(failure(Status::NotFound, "login-disabled"));
+    };
+    if let Some(ip) = client.ip {
+        if failed_logins.0.check(ip).is_err() {
+            warn!("refused a login from {}, which failed too many times", ip);
+            return Err(failure(Status::TooManyRequests, "login-too-many-attempts"));
+        };
+    };
+    // argon2 is slow on purpose, so it doesn't stall the other requests
+    let checked_hash = run_blocking(|| {
+        get_password_hash(setting, user_data, &form.user).map(|hash| {
+            let is_valid = verify_password(
+                &form.password,
+                hash.as_deref().unwrap_or_else(|| get_dummy_hash()),
+            );
+            hash.filter(|_| is_valid)
+        })
+    });
+    let hash = match checked_hash {
+        Ok(Some(hash)) => hash,
+        Ok(None) => {
+            warn!("failed login of {:?} from {:?}", form.user, client.ip);
+            if let Some(ip) = client.ip {
+                // only the failed logins are counted, the error is returned at the next attempt
+                let _ = failed_logins.0.try_call(ip);
+            };
+            return Err(failure(Status::Unauthorized, "login-failed"));
+        }
+        Err(err) => {
+            match err.source() {
+                Some(source) => error!("{}: {}", err, source),
+                None => error!("{}", err),
+            };
+            return Err(failure(Status::InternalServerError, "login-error"));
+        }
+    };
+    let token = match user_data.create_session(&form.user, &hash, setting.session_lifetime) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("can't create a session for {:?}: {}", form.user, err);
+            return Err(failure(Status::InternalServerError, "login-error"));
+        }
+    };
+    cookies.add(
+        Cookie::build((SESSION_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            // the session can't leak over plain http when the site is served with https
+            .secure(
+                setting
+                    .public_url
+                    .as_deref()
+                    .is_some_and(|url| url.starts_with("https://")),
+            )
+            .max_age(time::Duration::seconds(
+                setting.session_lifetime.min(i64::MAX as u64) as i64,
+            )),
+    );
+    // the preferences of the user are restored when the next page is requested
+    Ok(Redirect::to(get_local_path(form.next)))
+}
+
+/// replace the preferences and the profile of the browser with the one of the logged in user, then redirect to ``next``
+#[get("/login/sync?<next>")]
+pub fn sync_user_cookies(
+    identity: &Identity,
+    user_data: &State<UserData>,
+    cookies: &CookieJar<'_>,
+    next: Option<String>,
+) -> Redirect {
+    let user = match &identity.0 {
+        Some(value) => value,
+        None => return Redirect::to("/login"),
+    };
+    let mut saved: HashMap<String, String> = match user_data.get_user_cookies(user) {
+        Ok(value) => value.into_iter().collect(),
+        Err(err) => {
+            error!("can't get the cookies of {}: {}", user, err);
+            HashMap::new()
+        }
+    };
+    for name in USER_COOKIES {
+        match saved.remove(*name) {
+            Some(value) => cookies.add(Cookie::build((*name, value)).path("/").permanent()),
+            None => cookies.remove(Cookie::from(*name)),
+        };
+    }
+    cookies.add(
+        Cookie::build((PROFILE_COOKIE, get_user_profile(user)))
+            .path("/")
+            .permanent(),
+    );
+    Redirect::to(get_local_path(next))
+}
+
+/// close the session of the user, and remove its preferences from the browser
+#[post("/logout")]
+pub fn logout(user_data: &State<UserData>, cookies: &CookieJar<'_>) -> Redirect {
+    if let Some(session) = cookies.get(SESSION_COOKIE) {
+        if let Err(err) = user_data.remove_session(session.value()) {
+            error!("can't remove the session: {}", err);
+        };
+    };
+    for name in [SESSION_COOKIE, PROFILE_COOKIE].iter().chain(USER_COOKIES) {
+        cookies.remove(Cookie::from(*name));
+    }
+    Redirect::to("/login")
+}
+
+#[cfg(test)]
+fn get_test_client(setting: Setting) -> rocket::local::blocking::Client {
+    let rocket = rocket::build()
+        .attach(Authentication)
+        .manage(SettingHandle::new(setting))
+        .manage(UserData::open(None).unwrap())
+        .manage(FailedLogins::default())
+        .mount(
+            "/",
+            routes![
+                render_login,
+                login,
+                sync_user_cookies,
+                logout,
+                crate::preferences_page::render_preferences
+            ],
+        );
+    rocket::local::blocking::Client::tracked(rocket).unwrap()
+}
+
+/// a hash of ``password`` that is quick to check, so the tests stay fast
+#[cfg(test)]
+fn get_test_hash(password: &str) -> String {
+    use argon2::{Algorithm, Params, Version};
+
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8, 1, 1, None).unwrap(),
+    )
+    .hash_password(password.as_bytes(), &salt)
+    .unwrap()
+    .to_string()
+}
+
+/// return a setting with the password authentication, whose users are read from ``users_file``
+#[cfg(test)]
+fn get_password_setting(users_file: &tempfile::NamedTempFile) -> Setting {
+    let mut setting = Setting {
+        auth_mode: AuthMode::Password,
+        users_file: Some(users_file.path().to_string_lossy().to_string()),
+        public_url: Some("https://example.com".to_string()),
+        ..Setting::default()
+    };
+    setting.load_users().unwrap();
+    setting
+}
+
+#[test]
+fn test_password_login() {
+    use rocket::http::ContentType;
+    use std::fs;
+
+    let users_file = tempfile::NamedTempFile::new().unwrap();
+    fs::write(
+        users_file.path(),
+        format!("# comment\nalice:{}\n", get_test_hash("secret")),
+    )
+    .unwrap();
+    let client = get_test_client(get_password_setting(&users_file));
+    let post_login = |body: &str| {
+        client
+            .post("/login")
+            .header(ContentType::Form)
+            .body(body)
+            .dispatch()
+    };
+
+    // unauthenticated visitors get the login page in place of the page
+    let response = client.get("/preferences").dispatch();
+    assert_eq!(response.status(), Status::Unauthorized);
+    assert!(response
+        .into_string()
+        .unwrap()
+        .contains("name=\"next\" value=\"/preferences\""));
+
+    let response = post_login("user=alice&password=wrong&next=/preferences");
+    assert_eq!(response.status(), Status::Unauthorized);
+    let response = post_login("user=bob&password=secret&next=/preferences");
+    assert_eq!(response.status(), Status::Unauthorized);
+
+    // the redirection after the login stay on the site
+    let response = post_login("user=alice&password=secret&next=/%5Cevil.com");
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/"));
+    // the site is served with https, so the session cookie is only sent over it
+    assert!(response
+        .cookies()
+        .get(SESSION_COOKIE)
+        .is_some_and(|cookie| cookie.secure() == Some(true)));
+
+    let response = post_login("user=alice&password=secret&next=/preferences");
+    assert_eq!(response.headers().get_one("Location"), Some("/preferences"));
+    // the preferences of the user are loaded in the browser first
+    let response = client.get("/preferences").dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(response.headers().get_one("Location"), Some("/preferences"));
+    let response = client.get("/preferences").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(response.into_string().unwrap().contains("alice"));
+
+    // the session is closed when the password change, once the setting is reloaded
+    fs::write(
+        users_file.path(),
+        format!("alice:{}\n", get_test_hash("other")),
+    )
+    .unwrap();
+    client
+        .rocket()
+        .state::<SettingHandle>()
+        .unwrap()
+        .replace(get_password_setting(&users_file));
+    assert_eq!(
+        client.get("/preferences").dispatch().status(),
+        Status::Unauthorized
+    );
+}
+
+#[test]
+fn test_failed_logins_limit() {
+    use rocket::http::ContentType;
+
+    let users_file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        users_file.path(),
+        format!("alice:{}\n", get_test_hash("secret")),
+    )
+    .unwrap();
+    let client = get_test_client(get_password_setting(&users_file));
+    let post_login = |body: &str| {
+        client
+            .post("/login")
+            .remote("192.0.2.1:1000".parse().unwrap())
+            .header(ContentType::Form)
+            .body(body)
+            .dispatch()
+            .status()
+    };
+    for _ in 0..MAX_FAILED_LOGINS {
+        assert_eq!(
+            post_login("user=alice&password=guess"),
+            Status::Unauthorized
+        );
+    }
+    // even the right password is refused
+    assert_eq!(
+        post_login("user=alice&password=secret"),
+        Status::TooManyRequests
+    );
+}
+
+#[test]
+fn test_proxy_authentication() {
+    let client = get_test_client(Setting {
+        auth_mode: AuthMode::Proxy,
+        auth_proxies: vec!["192.0.2.1".parse().unwrap()],
+        ..Setting::default()
+    });
+    let get_preferences = |remote: &str| {
+        client
+            .get("/preferences")
+            .remote(remote.parse().unwrap())
+            .header(rocket::http::Header::new("Remote-User", "alice"))
+            .dispatch()
+            .status()
+    };
+    // the header is ignored if it doesn't come from the proxy
+    assert_eq!(get_preferences("198.51.100.1:1000"), Status::Unauthorized);
+    assert_eq!(get_preferences("192.0.2.1:1000"), Status::SeeOther);
+    assert_eq!(get_preferences("192.0.2.1:1000"), Status::Ok);
+}
@@ -0,0 +1,41 @@
+use crate::reload::CurrentSetting;
+use rocket::request::{FromRequest, Outcome, Request};
+
+/// Request guard containing the url this site is reachable at, without the trailing ``/``.
+///
+/// It is [`kodi_rust::Setting::public_url`] if set, otherwise it is guessed from the ``Host`` header of the request.
+pub struct BaseUrl(pub String);
+
+impl BaseUrl {
+    /// return an absolute version of ``link``. Links that are already absolute are returned unchanged.
+    pub fn absolute(&self, link: &str) -> String {
+        if link.starts_with('/') {
+            format!("{}{}", self.0, link)
+        } else {
+            link.to_string()
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BaseUrl {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let public_url = match request.guard::<&CurrentSetting>().await {
+            Outcome::Success(setting) => setting.public_url.clone(),
+            _ => None,
+        };
+        let base_url = match public_url {
+            Some(url) => url,
+            None => format!(
+                "http://{}",
+                request
+                    .host()
+                    .map(|host| host.to_string())
+                    .unwrap_or_else(|| "localhost".to_string())
+            ),
+        };
+        Outcome::Success(BaseUrl(base_url.trim_end_matches('/').to_string()))
+    }
+}
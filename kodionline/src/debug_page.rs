@@ -0,0 +1,193 @@
+use crate::{
+    admin_page::{render_access, render_action_button, Admin},
+    error_page::generate_error_page,
+    get_absolute_plugin_path, get_final_user_config,
+    rate_limit::{run_blocking, Client},
+    reload::CurrentSetting,
+    AcceptLanguage, LinkParams, PluginRequest, Presentation, PresentationResponse, UiLocale,
+    LOCALES,
+};
+use fluent_templates::Loader;
+use kodi_rust::{CallAdmission, Kodi, LinkState};
+use maud::html;
+use rocket::{
+    http::{CookieJar, Status},
+    State,
+};
+use std::error::Error;
+
+/// return the link to the debug view of ``link``
+pub fn get_debug_plugin_path(link: &LinkState, token_secret: Option<&str>) -> String {
+    format!("/debug{}", get_absolute_plugin_path(link, token_secret).0)
+}
+
+/// show how the plugin page of the same link is obtained: the command line of python, its log, the raw result of the plugin, the time taken and whether it came from the cache.
+///
+/// It is only available to the administrators, unless [`kodi_rust::Setting::public_debug_view`] is set.
+#[allow(clippy::too_many_arguments)]
+#[get("/debug/plugin?<additional_input>&<link..>")]
+pub fn render_debug_plugin(
+    admin: Result<Admin, ()>,
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    link: LinkParams,
+) -> Result<PresentationResponse, Status> {
+    if admin.is_err() && !setting.public_debug_view {
+        return Err(if setting.admin_password.is_some() {
+            Status::Unauthorized
+        } else {
+            Status::NotFound
+        });
+    };
+    Ok(render_debug(
+        admin.is_ok(),
+        kodi,
+        client,
+        setting,
+        cookies,
+        ui_locale,
+        accept_language,
+        false,
+        additional_input,
+        link,
+    ))
+}
+
+/// call the plugin again for the debug view, even if its result is in the cache. The new result replace the cached one, so only the administrators can do it.
+#[allow(clippy::too_many_arguments)]
+#[post("/debug/plugin?<additional_input>&<link..>")]
+pub fn bypass_cache_debug_plugin(
+    _admin: Admin,
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    link: LinkParams,
+) -> PresentationResponse {
+    render_debug(
+        true,
+        kodi,
+        client,
+        setting,
+        cookies,
+        ui_locale,
+        accept_language,
+        true,
+        additional_input,
+        link,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_debug(
+    is_admin: bool,
+    kodi: &Kodi,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    accept_language: AcceptLanguage,
+    bypass_cache: bool,
+    additional_input: Option<String>,
+    link: LinkParams,
+) -> PresentationResponse {
+    let locale = ui_locale.0;
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => {
+            return generate_error_page(
+                html!((LOCALES.lookup(&locale, "error-invalid-link")) " (" (err) ")"),
+                &locale,
+            )
+            .build_response(&locale)
+        }
+    };
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+    let PluginRequest { link, final_link } =
+        PluginRequest::new(link_state, additional_input, final_config);
+
+    // the administrators aren't rate limited
+    let admission: Option<&dyn CallAdmission> = if is_admin { None } else { Some(client) };
+    let report = run_blocking(|| kodi.invoke_debug(&final_link.access, bypass_cache, admission));
+    let result = report
+        .result
+        .as_ref()
+        .map(|result| serde_json::to_string_pretty(result).unwrap_or_default());
+
+    Presentation::new(
+        html!((LOCALES.lookup(&locale, "debug-title"))),
+        html!(
+            div {
+                a href=(get_absolute_plugin_path(&link, token_secret)) { (LOCALES.lookup(&locale, "debug-open-page")) }
+                " "
+                @if is_admin {
+                    (render_action_button(
+                        &get_debug_plugin_path(&link, token_secret),
+                        &[],
+                        LOCALES.lookup(&locale, "debug-bypass-cache"),
+                    ))
+                }
+            }
+            table class="list_table" {
+                tr {
+                    th { (LOCALES.lookup(&locale, "admin-path")) }
+                    td { (render_access(&final_link.access)) }
+                }
+                tr {
+                    th { (LOCALES.lookup(&locale, "debug-from-cache")) }
+                    td { (LOCALES.lookup(&locale, if report.from_cache { "debug-yes" } else { "debug-no" })) }
+                }
+                tr {
+                    th { (LOCALES.lookup(&locale, "word-duration")) }
+                    td { (format!("{:.3} s", report.duration.as_secs_f64())) }
+                }
+                tr {
+                    th { (LOCALES.lookup(&locale, "admin-exit-status")) }
+                    td { @match report.exit_status { Some(exit_status) => { (format!("{:?}", exit_status)) } None => { "-" } } }
+                }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "debug-command-line")) }
+            @match &report.command_line {
+                Some(command_line) => { pre { (command_line) } }
+                None => { p { (LOCALES.lookup(&locale, "debug-not-invoked")) } }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "admin-log")) }
+            @match &report.log {
+                Some(log) if !log.is_empty() => { pre { (log) } }
+                _ => { p { (LOCALES.lookup(&locale, "admin-none")) } }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "admin-result")) }
+            @match &result {
+                Ok(json) => { pre { (json) } }
+                Err(err) => {
+                    p class="unhealthy" {
+                        (err)
+                        @if let Some(source) = err.source() {
+                            " (" (source) ")"
+                        }
+                    }
+                }
+            }
+        ),
+    )
+    .build_response(&locale)
+}
@@ -1,7 +1,10 @@
 use crate::Presentation;
 use fluent_templates::Loader;
+use kodi_rust::KodiError;
 use maud::{html, Markup};
+use rocket::http::Status;
 use serde::Serialize;
+use std::collections::HashMap;
 use unic_langid::LanguageIdentifier;
 
 use crate::LOCALES;
@@ -22,3 +25,27 @@ pub fn generate_error_page(error_message: Markup, locale: &LanguageIdentifier) -
         ),
     )
 }
+
+/// render the message of an error of [`kodi_rust::Kodi`]. The addons refused by the [`kodi_rust::AddonPolicy`] get a translated message.
+pub fn render_kodi_error(err: &KodiError, locale: &LanguageIdentifier) -> Markup {
+    let mut args = HashMap::new();
+    match err {
+        KodiError::NotAPlugin(path) => {
+            args.insert("path".into(), path.clone().into());
+            html!((LOCALES.lookup_with_args(locale, "error-not-a-plugin", &args)))
+        }
+        KodiError::AddonNotAllowed(addon_id) => {
+            args.insert("addon".into(), addon_id.clone().into());
+            html!((LOCALES.lookup_with_args(locale, "error-addon-not-allowed", &args)))
+        }
+        _ => html!((err)),
+    }
+}
+
+/// return the HTTP status of a page showing ``err``. A refused addon is forbidden, while the other errors of the plugins are shown in a normal page.
+pub fn get_kodi_error_status(err: &KodiError) -> Status {
+    match err {
+        KodiError::AddonNotAllowed(_) => Status::Forbidden,
+        _ => Status::Ok,
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    get_absolute_plugin_path, get_final_user_config,
+    preferences_page::RefererPath,
+    reload::CurrentSetting,
+    render_link_inputs,
+    user_data::{
+        export_kodi_favourites, get_or_create_profile, get_profile, import_kodi_favourites,
+        HistoryEntry, SavedEntry, UserData, UserDataError,
+    },
+    AcceptLanguage, BaseUrl, LinkParams, PluginRequest, Presentation,
+};
+use fluent_templates::Loader;
+use log::error;
+use maud::{html, Markup};
+use rocket::form::Form;
+use rocket::http::{ContentType, CookieJar, Status};
+use rocket::response::Redirect;
+use rocket::State;
+use unic_langid::LanguageIdentifier;
+
+use crate::{UiLocale, LOCALES};
+
+/// number of entries shown in the history and in the "continue watching" sections
+const HISTORY_LENGTH: usize = 50;
+
+#[derive(FromForm)]
+pub struct SavedEntryForm {
+    /// the link, as created by [`render_link_inputs`] with the ``link.`` prefix
+    link: LinkParams,
+    label: String,
+    thumbnail: Option<String>,
+    is_folder: bool,
+}
+
+#[derive(FromForm)]
+pub struct RemoveFavouriteForm {
+    link: LinkParams,
+}
+
+#[derive(FromForm)]
+pub struct PositionForm {
+    position: f64,
+    duration: Option<f64>,
+}
+
+#[derive(FromForm)]
+pub struct ImportForm {
+    xml: String,
+}
+
+/// render a button adding ``entry`` to the favourites, or removing it if it already is one
+pub fn render_favourite_button(
+    user_data: &UserData,
+    cookies: &CookieJar<'_>,
+    entry: &SavedEntry,
+    token_secret: Option<&str>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    let is_favourite = match get_profile(cookies) {
+        Some(profile) => user_data
+            .is_favourite(&profile, &entry.link.access)
+            .unwrap_or_else(|err| {
+                error!(
+                    "can't check if {:?} is a favourite: {:?}",
+                    entry.link.access, err
+                );
+                false
+            }),
+        None => false,
+    };
+    html!(
+        @if is_favourite {
+            form method="post" action="/favourites/remove" class="favourite_button" {
+                (render_link_inputs(&entry.link, token_secret, "link."))
+                input type="submit" value=(LOCALES.lookup(locale, "favourite-remove")) {}
+            }
+        } @else {
+            form method="post" action="/favourites/add" class="favourite_button" {
+                (render_link_inputs(&entry.link, token_secret, "link."))
+                input type="hidden" name="label" value=(entry.label) {}
+                @if let Some(thumbnail) = &entry.thumbnail {
+                    input type="hidden" name="thumbnail" value=(thumbnail) {}
+                }
+                input type="hidden" name="is_folder" value=(entry.is_folder) {}
+                input type="submit" value=(LOCALES.lookup(locale, "favourite-add")) {}
+            }
+        }
+    )
+}
+
+fn render_saved_entry(entry: &SavedEntry, token_secret: Option<&str>) -> Markup {
+    html!(
+        a href=(get_absolute_plugin_path(&entry.link, token_secret)) {
+            div class="subelem_title" { (entry.label) }
+            @if let Some(thumbnail) = &entry.thumbnail {
+                img class="illustration" src=(thumbnail) {}
+            }
+        }
+    )
+}
+
+fn render_history_entries(
+    entries: &[HistoryEntry],
+    token_secret: Option<&str>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    html!(
+        ul class="list_media" {
+            @for history_entry in entries {
+                li class="media_in_list" {
+                    (render_saved_entry(&history_entry.entry, token_secret))
+                    @if history_entry.is_in_progress() {
+                        p class="resume_position" {
+                            (LOCALES.lookup(locale, "history-position")) " : " (format_position(history_entry.position))
+                            @if let Some(duration) = history_entry.duration {
+                                " / " (format_position(duration))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
+
+/// format a position in seconds as ``H:MM:SS``
+fn format_position(position: f64) -> String {
+    let seconds = position.max(0.0) as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+/// render the favourites and "continue watching" sections of the current profile. It is empty if the profile has none.
+pub fn render_user_sections(
+    user_data: &UserData,
+    cookies: &CookieJar<'_>,
+    token_secret: Option<&str>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    let profile = match get_profile(cookies) {
+        Some(profile) => profile,
+        None => return html!(),
+    };
+    let favourites = user_data.get_favourites(&profile).unwrap_or_else(|err| {
+        error!("can't get the favourites: {:?}", err);
+        Vec::new()
+    });
+    let continue_watching = user_data
+        .get_continue_watching(&profile, HISTORY_LENGTH)
+        .unwrap_or_else(|err| {
+            error!("can't get the history: {:?}", err);
+            Vec::new()
+        });
+    html!(
+        @if !continue_watching.is_empty() {
+            h2 { (LOCALES.lookup(locale, "continue-watching")) }
+            (render_history_entries(&continue_watching, token_secret, locale))
+        }
+        @if !favourites.is_empty() {
+            h2 { (LOCALES.lookup(locale, "favourites")) }
+            ul class="list_media" {
+                @for favourite in &favourites {
+                    li class="media_in_list" { (render_saved_entry(favourite, token_secret)) }
+                }
+            }
+        }
+    )
+}
+
+/// record a media page in the history of the profile (creating one if needed), and return the position to resume it at
+pub fn record_media_in_history(
+    user_data: &UserData,
+    cookies: &CookieJar<'_>,
+    entry: &SavedEntry,
+) -> Option<f64> {
+    let profile = get_or_create_profile(cookies);
+    if let Err(err) = user_data.record_history(&profile, entry) {
+        error!(
+            "can't record {:?} in the history: {:?}",
+            entry.link.access, err
+        );
+    };
+    user_data
+        .get_resume_position(&profile, &entry.link.access)
+        .unwrap_or_else(|err| {
+            error!(
+                "can't get the position of {:?}: {:?}",
+                entry.link.access, err
+            );
+            None
+        })
+}
+
+/// display the favourites and the history of the profile, with the import and export of Kodi favourites
+#[get("/favourites")]
+pub fn render_favourites(
+    user_data: &State<UserData>,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+) -> Markup {
+    let locale = ui_locale.0;
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let history = match get_profile(cookies) {
+        Some(profile) => user_data
+            .get_history(&profile, HISTORY_LENGTH)
+            .unwrap_or_else(|err| {
+                error!("can't get the history: {:?}", err);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+
+    Presentation::new(
+        html!((LOCALES.lookup(&locale, "favourites-title"))),
+        html!(
+            (render_user_sections(user_data, cookies, token_secret, &locale))
+
+            h2 { (LOCALES.lookup(&locale, "history")) }
+            @if history.is_empty() {
+                p { (LOCALES.lookup(&locale, "history-empty")) }
+            } @else {
+                (render_history_entries(&history, token_secret, &locale))
+                form method="post" action="/history/clear" {
+                    input type="submit" value=(LOCALES.lookup(&locale, "history-clear")) {}
+                }
+            }
+
+            h2 { (LOCALES.lookup(&locale, "kodi-favourites")) }
+            p { a href="/favourites.xml" { (LOCALES.lookup(&locale, "kodi-favourites-export")) } }
+            form method="post" action="/favourites/import" {
+                label for="favourites_xml" { (LOCALES.lookup(&locale, "kodi-favourites-import")) }
+                br {}
+                textarea id="favourites_xml" name="xml" rows="8" cols="80" {}
+                br {}
+                input type="submit" value=(LOCALES.lookup(&locale, "kodi-favourites-import-submit")) {}
+            }
+        ),
+    )
+    .build(&locale)
+}
+
+/// the status to return when a favourite can't be added. Having too many favourites is an error of the visitor.
+fn get_add_favourite_status(err: &UserDataError) -> Status {
+    match err {
+        UserDataError::TooManyFavourites => Status::BadRequest,
+        _ => Status::InternalServerError,
+    }
+}
+
+#[post("/favourites/add", data = "<form>")]
+pub fn add_favourite(
+    user_data: &State<UserData>,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    referer: RefererPath,
+    form: Form<SavedEntryForm>,
+) -> Result<Redirect, Status> {
+    let form = form.into_inner();
+    let link = form.link.into_link_state(setting).map_err(|err| {
+        error!("invalid link to add to the favourites: {}", err);
+        Status::BadRequest
+    })?;
+    let entry = SavedEntry {
+        link,
+        label: form.label,
+        thumbnail: form.thumbnail.filter(|thumbnail| !thumbnail.is_empty()),
+        is_folder: form.is_folder,
+    };
+    let profile = get_or_create_profile(cookies);
+    user_data.add_favourite(&profile, &entry).map_err(|err| {
+        error!(
+            "can't add {:?} to the favourites: {:?}",
+            entry.link.access, err
+        );
+        get_add_favourite_status(&err)
+    })?;
+    Ok(referer.redirect())
+}
+
+#[post("/favourites/remove", data = "<form>")]
+pub fn remove_favourite(
+    user_data: &State<UserData>,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    referer: RefererPath,
+    form: Form<RemoveFavouriteForm>,
+) -> Result<Redirect, Status> {
+    let link = form
+        .into_inner()
+        .link
+        .into_link_state(setting)
+        .map_err(|err| {
+            error!("invalid link to remove from the favourites: {}", err);
+            Status::BadRequest
+        })?;
+    if let Some(profile) = get_profile(cookies) {
+        user_data
+            .remove_favourite(&profile, &link.access)
+            .map_err(|err| {
+                error!(
+                    "can't remove {:?} from the favourites: {:?}",
+                    link.access, err
+                );
+                Status::InternalServerError
+            })?;
+    };
+    Ok(referer.redirect())
+}
+
+#[post("/history/clear")]
+pub fn clear_history(
+    user_data: &State<UserData>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Status> {
+    if let Some(profile) = get_profile(cookies) {
+        user_data.clear_history(&profile).map_err(|err| {
+            error!("can't clear the history: {:?}", err);
+            Status::InternalServerError
+        })?;
+    };
+    Ok(Redirect::to(uri!(render_favourites)))
+}
+
+/// store the position reached by the player of a media page. The query is the same as the one of the media page.
+#[post("/history/position?<additional_input>&<link..>", data = "<form>")]
+pub fn update_position(
+    user_data: &State<UserData>,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    link: LinkParams,
+    form: Form<PositionForm>,
+) -> Status {
+    if !form.position.is_finite() || form.position < 0.0 {
+        return Status::BadRequest;
+    };
+    if let Some(duration) = form.duration {
+        if !duration.is_finite() || duration <= 0.0 {
+            return Status::BadRequest;
+        };
+    };
+    let profile = match get_profile(cookies) {
+        Some(profile) => profile,
+        None => return Status::NoContent,
+    };
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("invalid link to store a position: {}", err);
+            return Status::BadRequest;
+        }
+    };
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+    let request = PluginRequest::new(link_state, additional_input, final_config);
+    match user_data.update_position(&profile, &request.link.access, form.position, form.duration) {
+        Ok(()) => Status::NoContent,
+        Err(err) => {
+            error!(
+                "can't store the position of {:?}: {:?}",
+                request.link.access, err
+            );
+            Status::InternalServerError
+        }
+    }
+}
+
+/// export the favourites of the profile in the ``favourites.xml`` format of Kodi
+#[get("/favourites.xml")]
+pub fn export_favourites(
+    user_data: &State<UserData>,
+    cookies: &CookieJar<'_>,
+    base_url: BaseUrl,
+) -> Result<(ContentType, String), Status> {
+    let favourites = match get_profile(cookies) {
+        Some(profile) => user_data.get_favourites(&profile).map_err(|err| {
+            error!("can't get the favourites: {:?}", err);
+            Status::InternalServerError
+        })?,
+        None => Vec::new(),
+    };
+    let favourites: Vec<SavedEntry> = favourites
+        .into_iter()
+        .map(|mut favourite| {
+            favourite.thumbnail = favourite
+                .thumbnail
+                .map(|thumbnail| base_url.absolute(&thumbnail));
+            favourite
+        })
+        .collect();
+    Ok((ContentType::XML, export_kodi_favourites(&favourites)))
+}
+
+/// import favourites in the ``favourites.xml`` format of Kodi. Only the favourites pointing to a plugin are imported.
+#[post("/favourites/import", data = "<form>")]
+pub fn import_favourites(
+    user_data: &State<UserData>,
+    cookies: &CookieJar<'_>,
+    form: Form<ImportForm>,
+) -> Result<Redirect, Status> {
+    let favourites = import_kodi_favourites(&form.xml).map_err(|err| {
+        error!("can't import the favourites: {:?}", err);
+        Status::BadRequest
+    })?;
+    let profile = get_or_create_profile(cookies);
+    for favourite in &favourites {
+        user_data
+            .add_favourite(&profile, favourite)
+            .map_err(|err| {
+                error!("can't import the favourite {:?}: {:?}", favourite, err);
+                get_add_favourite_status(&err)
+            })?;
+    }
+    Ok(Redirect::to(uri!(render_favourites)))
+}
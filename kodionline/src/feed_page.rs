@@ -0,0 +1,203 @@
+use crate::{
+    get_absolute_plugin_path, get_child_link, get_link_to_route, get_plugin_type,
+    rate_limit::Client, reload::CurrentSetting, AcceptLanguage, BaseUrl, ExportedPage, LinkParams,
+};
+use kodi_rust::{
+    data::SubContent, get_art_link_subcontent, get_media_link_subcontent, Kodi, LinkState,
+};
+
+use rocket::http::{ContentType, CookieJar};
+use rocket::State;
+use rss::extension::itunes::{ITunesChannelExtension, ITunesItemExtension};
+use rss::{Channel, Enclosure, Guid, Item};
+
+/// return the link to the RSS feed of a folder, taking the same parameters as [`get_absolute_plugin_path`]
+pub fn get_feed_path(link: &LinkState, token_secret: Option<&str>) -> String {
+    get_link_to_route("/feed.rss", link, token_secret)
+}
+
+/// guess the mime type of a media from the extension of its url, falling back to a generic type for the plugin type
+fn guess_mime_type(media_url: &str, plugin_type: &str) -> String {
+    let without_query = media_url.split(['?', '#']).next().unwrap_or(media_url);
+    let extension = without_query
+        .rsplit('/')
+        .next()
+        .and_then(|file_name| file_name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_lowercase());
+    match extension.as_deref() {
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") | Some("oga") => "audio/ogg",
+        Some("opus") => "audio/opus",
+        Some("m4a") => "audio/mp4",
+        Some("flac") => "audio/flac",
+        Some("wav") => "audio/wav",
+        Some("mp4") | Some("m4v") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mkv") => "video/x-matroska",
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        _ => {
+            if plugin_type == "audio" {
+                "audio/mpeg"
+            } else {
+                "video/mp4"
+            }
+        }
+    }
+    .to_string()
+}
+
+/// format a year as a RFC 822 date, as expected in the ``pubDate`` of RSS
+fn year_to_rfc822(year: i64) -> String {
+    format!("01 Jan {:04} 00:00:00 +0000", year)
+}
+
+/// format a duration in seconds as ``HH:MM:SS``, as expected by ``itunes:duration``
+pub fn format_duration(duration: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        duration / 3600,
+        (duration / 60) % 60,
+        duration % 60
+    )
+}
+
+fn get_thumb_link(
+    sub_content: &SubContent,
+    parent: &LinkState,
+    token_secret: Option<&str>,
+    base_url: &BaseUrl,
+) -> Option<String> {
+    sub_content.listitem.get_thumb_category().map(|category| {
+        base_url.absolute(&get_art_link_subcontent(
+            sub_content,
+            category,
+            parent,
+            token_secret,
+        ))
+    })
+}
+
+fn create_item(
+    sub_content: &SubContent,
+    current: &LinkState,
+    plugin_type: &str,
+    token_secret: Option<&str>,
+    base_url: &BaseUrl,
+) -> Item {
+    let listitem = &sub_content.listitem;
+    let media_url = base_url.absolute(&get_media_link_subcontent(
+        sub_content,
+        current,
+        token_secret,
+    ));
+    let mime_type = guess_mime_type(
+        listitem.path.as_deref().unwrap_or(&media_url),
+        listitem.category.as_deref().unwrap_or(plugin_type),
+    );
+    let page_url = base_url.absolute(
+        &get_absolute_plugin_path(
+            &get_child_link(current, sub_content.url.clone()),
+            token_secret,
+        )
+        .0,
+    );
+
+    Item {
+        title: listitem
+            .info
+            .title
+            .clone()
+            .or_else(|| listitem.label.clone()),
+        link: Some(page_url),
+        description: listitem.info.plot.clone(),
+        author: listitem.info.artist.clone(),
+        enclosure: Some(Enclosure {
+            url: media_url,
+            // the size of the media is unknown without fetching it
+            length: "0".to_string(),
+            mime_type,
+        }),
+        guid: Some(Guid {
+            value: sub_content.url.clone(),
+            permalink: false,
+        }),
+        pub_date: listitem.info.year.map(year_to_rfc822),
+        itunes_ext: Some(ITunesItemExtension {
+            author: listitem.info.artist.clone(),
+            image: get_thumb_link(sub_content, current, token_secret, base_url),
+            duration: listitem.info.duration.map(format_duration),
+            summary: listitem.info.plot.clone(),
+            season: listitem.info.season.map(|x| x.to_string()),
+            episode: listitem.info.episode.map(|x| x.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// return a RSS 2.0 podcast feed (with the iTunes extension) of a folder. Every playable element of the folder is an item of the feed, with the media as enclosure.
+///
+/// It take the same parameters as [`crate::plugin_page::render_plugin`].
+#[get("/feed.rss?<link..>")]
+pub fn render_feed(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    base_url: BaseUrl,
+    link: LinkParams,
+) -> Option<(ContentType, String)> {
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let ExportedPage {
+        link,
+        page,
+        subcontent_from_parent,
+        title,
+    } = ExportedPage::load(
+        kodi,
+        client,
+        setting,
+        cookies,
+        &accept_language,
+        link,
+        "feed",
+    )?;
+
+    let plugin_type = get_plugin_type(&link.access.path).unwrap_or_default();
+
+    let image = match (&subcontent_from_parent, link.parent_state()) {
+        (Some(subcontent), Some(parent)) => {
+            get_thumb_link(subcontent, &parent, token_secret, &base_url)
+        }
+        _ => None,
+    };
+
+    let items = page
+        .sub_content
+        .iter()
+        .filter(|sub_content| sub_content.listitem.is_playable())
+        .map(|sub_content| create_item(sub_content, &link, &plugin_type, token_secret, &base_url))
+        .collect();
+
+    let channel = Channel {
+        link: base_url.absolute(&get_absolute_plugin_path(&link, token_secret).0),
+        description: subcontent_from_parent
+            .as_ref()
+            .and_then(|subcontent| subcontent.listitem.info.plot.clone())
+            .unwrap_or_else(|| title.clone()),
+        title,
+        generator: Some("kodi online".to_string()),
+        items,
+        itunes_ext: Some(ITunesChannelExtension {
+            image,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    Some((
+        ContentType::new("application", "rss+xml"),
+        channel.to_string(),
+    ))
+}
@@ -1,14 +1,29 @@
-use crate::{get_absolute_plugin_path, Presentation};
+use crate::{
+    favourites_page::render_user_sections, get_absolute_plugin_path, rate_limit::Client,
+    reload::CurrentSetting, search_page::render_search_form, user_data::UserData, Presentation,
+};
 use fluent_templates::Loader;
-use kodi_rust::{PathAccessData, Setting, UserConfig};
+use kodi_rust::{LinkState, PathAccessData, UserConfig};
 use maud::{html, PreEscaped};
+use rocket::http::CookieJar;
 use rocket::State;
 
-use crate::{get_ui_locale, LOCALES};
+use crate::{UiLocale, LOCALES};
 
 #[get("/")]
-pub fn render_index(setting: &State<Setting>) -> PreEscaped<String> {
-    let locale = get_ui_locale();
+pub fn render_index(
+    setting: &CurrentSetting,
+    user_data: &State<UserData>,
+    client: &Client,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+) -> PreEscaped<String> {
+    let locale = ui_locale.0;
+    let plugins: Vec<_> = setting
+        .plugins_to_show
+        .iter()
+        .filter(|(_, path)| client.is_addon_allowed(path))
+        .collect();
 
     Presentation::new(
         html!((LOCALES.lookup(&locale, "kodionline"))),
@@ -29,22 +44,30 @@ pub fn render_index(setting: &State<Setting>) -> PreEscaped<String> {
                 }
             }
 
-            @if !setting.plugins_to_show.is_empty() {
-                h2 { "avalaible plugins "}
+            @if !setting.search_entry_points.is_empty() {
+                (render_search_form(&locale, None))
+            }
+
+            (render_user_sections(user_data, cookies, setting.state_token_secret.as_deref(), &locale))
+
+            @if !plugins.is_empty() {
+                h2 { (LOCALES.lookup(&locale, "avalaible-plugins")) }
                 ul {
-                    @for plugin in &setting.plugins_to_show {
+                    @for plugin in &plugins {
                         li {
-                            a href = (get_absolute_plugin_path(&PathAccessData::new(plugin.1.clone(), None, UserConfig::new_empty()), None)) { (plugin.0) }
+                            a href = (get_absolute_plugin_path(&LinkState::new(PathAccessData::new(plugin.1.clone(), None, UserConfig::new_empty()), None), setting.state_token_secret.as_deref())) { (plugin.0) }
                         }
                     }
                 }
             }
 
-            form method="get" action="/plugin" {
-                label for="path_input" { "use a direct kodi plugin page" }
-                input type="text" id="path_input" name="path" {}
-                br {}
-                input type="submit" value="go to the path" {}
+            @if !setting.is_state_token_required() {
+                form method="get" action="/plugin" {
+                    label for="path_input" { (LOCALES.lookup(&locale, "direct-plugin-path")) }
+                    input type="text" id="path_input" name="path" {}
+                    br {}
+                    input type="submit" value=(LOCALES.lookup(&locale, "go-to-path")) {}
+                }
             }
         )
     ).build(&locale)
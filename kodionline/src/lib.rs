@@ -2,8 +2,15 @@
 #[macro_use]
 extern crate rocket;
 
-use kodi_rust::{input::encode_input, urlencode, PathAccessData};
-use maud::PreEscaped;
+use kodi_rust::{
+    data::{KodiResult, Page, SubContent},
+    get_sub_content_from_parent,
+    input::encode_input,
+    urlencode, Kodi, LinkQuery, LinkState, LinkStateError, PathAccessData, Setting, UserConfig,
+};
+use log::error;
+use maud::{html, Markup, PreEscaped};
+use rocket::http::CookieJar;
 
 pub mod plugin_page;
 
@@ -13,19 +20,97 @@ pub mod redirect_page;
 
 pub mod index_page;
 
+pub mod preferences_page;
+
+pub mod api;
+
+pub mod feed_page;
+
+pub mod playlist_page;
+
+pub mod search_page;
+
+pub mod favourites_page;
+
+pub mod listing;
+
+pub mod thumbnail;
+
+pub mod admin_page;
+
+pub mod debug_page;
+
+pub mod rate_limit;
+
+pub mod reload;
+
+pub mod auth;
+
+pub mod user_data;
+use preferences_page::get_preferences_from_cookie;
+
 mod presentation;
-pub use presentation::Presentation;
+pub use presentation::{Presentation, PresentationResponse};
+
+mod base_url;
+pub use base_url::BaseUrl;
 
 mod locale;
 pub use locale::get_ui_locale;
+pub use locale::AcceptLanguage;
+pub use locale::UiLocale;
 pub use locale::LOCALES;
 
+/// The query parameters describing a [`LinkState`], shared by every route that take one (see [`LinkState::from_query`])
+#[derive(FromForm, Debug, Default)]
+pub struct LinkParams {
+    pub path: Option<String>,
+    pub input: Option<String>,
+    pub parent_path: Option<String>,
+    pub parent_input: Option<String>,
+    /// the ancestors above the parent, encoded with [`LinkState::encode_ancestors`]
+    pub a: Option<String>,
+    pub c: Option<String>,
+    pub s: Option<String>,
+}
+
+impl LinkParams {
+    /// decode the [`LinkState`] described by these parameters
+    pub fn into_link_state(self, setting: &Setting) -> Result<LinkState, LinkStateError> {
+        LinkState::from_query(
+            setting,
+            LinkQuery {
+                token: self.s,
+                path: self.path,
+                input: self.input,
+                parent_path: self.parent_path,
+                parent_input: self.parent_input,
+                ancestors: self.a,
+                config: self.c,
+            },
+        )
+    }
+}
+
+/// return the link to the plugin page of ``link``.
+///
+/// If ``token_secret`` is set, the state is passed as a signed token (see [`LinkState::encode_token`]), otherwise it is passed as plain parameters.
 pub fn get_absolute_plugin_path(
-    main: &PathAccessData,
-    parent: Option<&PathAccessData>,
+    link: &LinkState,
+    token_secret: Option<&str>,
 ) -> PreEscaped<String> {
-    PreEscaped(format!(
-        "/plugin?path={}{}{}{}",
+    PreEscaped(get_link_to_route("/plugin", link, token_secret))
+}
+
+/// return the link to ``route`` for ``link``, with the same parameters as [`get_absolute_plugin_path`] (read back by [`LinkParams`])
+pub fn get_link_to_route(route: &str, link: &LinkState, token_secret: Option<&str>) -> String {
+    if let Some(secret) = token_secret {
+        return format!("{}?s={}", route, link.encode_token(secret));
+    };
+    let main = &link.access;
+    format!(
+        "{}?path={}{}{}{}",
+        route,
         urlencode(&main.path),
         if !main.input.is_empty() {
             format!("&input={}", encode_input(&main.input))
@@ -37,18 +122,223 @@ pub fn get_absolute_plugin_path(
         } else {
             String::new()
         },
-        if let Some(parent) = parent {
+        if let Some(parent) = &link.parent {
             format!(
-                "&parent_path={}{}",
+                "&parent_path={}{}{}",
                 urlencode(&parent.path),
                 if !parent.input.is_empty() {
                     format!("&parent_input={}", encode_input(&parent.input))
                 } else {
                     String::new()
                 },
+                if !link.ancestors.is_empty() {
+                    format!("&a={}", LinkState::encode_ancestors(&link.ancestors))
+                } else {
+                    String::new()
+                }
             )
         } else {
             String::new()
         }
+    )
+}
+
+/// render the hidden fields of a form identifying ``link``, with the same names as [`LinkParams`]. Each name is prefixed with ``prefix``, for forms containing [`LinkParams`] as a field.
+pub fn render_link_inputs(link: &LinkState, token_secret: Option<&str>, prefix: &str) -> Markup {
+    html!(
+        @if let Some(secret) = token_secret {
+            input type="hidden" name=(format!("{}s", prefix)) value=(link.encode_token(secret)) {}
+        } @else {
+            input type="hidden" name=(format!("{}path", prefix)) value=(link.access.path) {}
+            input type="hidden" name=(format!("{}input", prefix)) value=(encode_input(&link.access.input)) {}
+            @if !link.access.config.is_empty() {
+                input type="hidden" name=(format!("{}c", prefix)) value=(link.access.config.encode_to_uri()) {}
+            }
+            @if let Some(parent) = &link.parent {
+                input type="hidden" name=(format!("{}parent_path", prefix)) value=(parent.path) {}
+                input type="hidden" name=(format!("{}parent_input", prefix)) value=(encode_input(&parent.input)) {}
+                @if !link.ancestors.is_empty() {
+                    input type="hidden" name=(format!("{}a", prefix)) value=(LinkState::encode_ancestors(&link.ancestors)) {}
+                }
+            }
+        }
+    )
+}
+
+/// return the [`LinkState`] of the page of a child of the page described by ``parent``. As with the other links of the website, the config isn't passed to the child.
+pub fn get_child_link(parent: &LinkState, child_path: String) -> LinkState {
+    parent.child(PathAccessData::new(
+        child_path,
+        None,
+        UserConfig::new_empty(),
     ))
 }
+
+/// return a [`UserConfig`] whose language order is derived from the languages accepted by the browser, translated with [`Setting::get_addon_language`]
+pub fn get_user_config_from_accept_language(
+    setting: &Setting,
+    accept_language: &AcceptLanguage,
+) -> UserConfig {
+    let mut result = UserConfig::new_empty();
+    *result.language_order = accept_language
+        .0
+        .iter()
+        .map(|language| setting.get_addon_language(&language.to_string()))
+        .collect();
+    result.clean();
+    result
+}
+
+/// return the [`UserConfig`] to use for a request to the plugin ``path``, by merging (from the lowest to the highest priority) the server default (of the plugin, if it has one), the languages accepted by the browser, the preferences stored in the cookie and the config present in the url.
+pub fn get_final_user_config(
+    setting: &Setting,
+    cookies: &CookieJar<'_>,
+    accept_language: &AcceptLanguage,
+    path: &str,
+    config_in_url: UserConfig,
+) -> UserConfig {
+    setting
+        .get_default_user_config(path)
+        .clone()
+        .add_config_prioritary(get_user_config_from_accept_language(
+            setting,
+            accept_language,
+        ))
+        .add_config_prioritary(get_preferences_from_cookie(cookies))
+        .add_config_prioritary(config_in_url)
+}
+
+/// return true if ``path`` is a path of this site, that can be redirected to without leaving it. Browsers treat ``//`` and ``/\`` as the start of another host.
+///
+/// # Example
+///
+/// ```
+/// use kodionline::is_local_path;
+///
+/// assert!(is_local_path("/plugin?path=a"));
+/// assert!(is_local_path("/"));
+/// assert!(!is_local_path("//evil.com/a"));
+/// assert!(!is_local_path("/\\evil.com"));
+/// assert!(!is_local_path("http://evil.com"));
+/// ```
+pub fn is_local_path(path: &str) -> bool {
+    let mut chars = path.chars();
+    chars.next() == Some('/') && !matches!(chars.next(), Some('/' | '\\'))
+}
+
+/// return the type of the plugin of a ``plugin://`` path (like ``video`` or ``audio``), or ``None`` if it can't be found
+pub fn get_plugin_type(path: &str) -> Option<String> {
+    let mut splited = path.split('.');
+    splited.next();
+    splited.next().map(|value| value.to_string())
+}
+
+/// The states used to render a plugin page
+pub struct PluginRequest {
+    /// the state of the page, with only the config present in the url (used to create links)
+    pub link: LinkState,
+    /// the state of the page, with the final config (used to invoke kodi)
+    pub final_link: LinkState,
+}
+
+impl PluginRequest {
+    /// create a new [`PluginRequest`] from the [`LinkState`] of the query, adding ``additional_input`` (given by the user after a keyboard prompt) to the input.
+    ///
+    /// ``additional_input`` isn't covered by the signature of the state token, as it is typed by the user: the token only protect the path and the input before it. It can be added to any link, and is always the last input.
+    pub fn new(
+        mut link: LinkState,
+        additional_input: Option<String>,
+        final_config: UserConfig,
+    ) -> Self {
+        if let Some(value) = additional_input {
+            link.access.input.push(value)
+        };
+        Self {
+            final_link: link.clone().with_config(final_config),
+            link,
+        }
+    }
+}
+
+/// A plugin page loaded to be exported in another format, like a feed or a playlist
+pub struct ExportedPage {
+    /// the state of the page, with only the config present in the url (used to create links)
+    pub link: LinkState,
+    pub page: Page,
+    /// the element of the parent page leading to this page, if the parent is known
+    pub subcontent_from_parent: Option<SubContent>,
+    /// the label of the page in its parent, falling back to the label of the plugin and to the path
+    pub title: String,
+}
+
+impl ExportedPage {
+    /// invoke the plugin for ``link`` as [`plugin_page::render_plugin`] would, with the final user config. Failures are logged, mentioning ``export_name`` (like ``"feed"``), and ``None`` is returned.
+    pub fn load(
+        kodi: &Kodi,
+        client: &rate_limit::Client,
+        setting: &Setting,
+        cookies: &CookieJar<'_>,
+        accept_language: &AcceptLanguage,
+        link: LinkParams,
+        export_name: &str,
+    ) -> Option<Self> {
+        let link_state = match link.into_link_state(setting) {
+            Ok(value) => value,
+            Err(err) => {
+                error!("invalid link to a {}: {}", export_name, err);
+                return None;
+            }
+        };
+
+        let final_config = get_final_user_config(
+            setting,
+            cookies,
+            accept_language,
+            &link_state.access.path,
+            link_state.access.config.clone(),
+        );
+
+        let PluginRequest { link, final_link } = PluginRequest::new(link_state, None, final_config);
+        let current_access = &final_link.access;
+
+        let page = match kodi.invoke_sandbox_for(current_access, client) {
+            Ok(KodiResult::Content(page)) => page,
+            Ok(result) => {
+                error!(
+                    "asked for input to create the {} of {:?} (result: {:?})",
+                    export_name, current_access, result
+                );
+                return None;
+            }
+            Err(err) => {
+                error!(
+                    "error {:?} while creating the {} of {:?}",
+                    err, export_name, current_access
+                );
+                return None;
+            }
+        };
+
+        let subcontent_from_parent = final_link.parent.as_ref().and_then(|parent_access| {
+            get_sub_content_from_parent(
+                kodi,
+                &client.secondary(),
+                parent_access,
+                &current_access.path,
+            )
+        });
+
+        let title = subcontent_from_parent
+            .as_ref()
+            .and_then(|subcontent| subcontent.listitem.label.clone())
+            .or_else(|| setting.get_label_for_path(&current_access.path))
+            .unwrap_or_else(|| current_access.path.clone());
+
+        Some(Self {
+            link,
+            page,
+            subcontent_from_parent,
+            title,
+        })
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{render_link_inputs, LOCALES};
+use fluent_templates::Loader;
+use kodi_rust::{LinkState, SortMethod};
+use maud::{html, Markup};
+use rocket::http::{Cookie, CookieJar};
+use unic_langid::LanguageIdentifier;
+
+/// name of the cookie that store the [`SortMethod`] last chosen by the user, encoded with [`SortMethod::as_str`]. It is absent to keep the order of the plugin.
+pub const SORT_COOKIE: &str = "listing_sort";
+
+/// name of the cookie that store the [`ViewMode`] last chosen by the user, encoded with [`ViewMode::as_str`]
+pub const VIEW_COOKIE: &str = "listing_view";
+
+/// The layout of the content of a folder
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ViewMode {
+    /// a list of tiles with the thumbnail and the plot
+    #[default]
+    List,
+    /// a grid of posters, with only the title
+    Grid,
+    /// a table with one line per element
+    Table,
+}
+
+impl ViewMode {
+    pub const ALL: [Self; 3] = [Self::List, Self::Grid, Self::Table];
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.as_str() == value)
+    }
+
+    /// return the name of this mode, as used in the uri and in the cookies
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Grid => "grid",
+            Self::Table => "table",
+        }
+    }
+}
+
+/// The way the user want the content of folders to be displayed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ListingPreferences {
+    /// ``None`` to keep the order of the plugin
+    pub sort: Option<SortMethod>,
+    pub view: ViewMode,
+}
+
+impl ListingPreferences {
+    /// return the preferences stored in the cookies
+    pub fn from_cookies(cookies: &CookieJar<'_>) -> Self {
+        Self {
+            sort: cookies
+                .get(SORT_COOKIE)
+                .and_then(|cookie| SortMethod::from_str(cookie.value())),
+            view: cookies
+                .get(VIEW_COOKIE)
+                .and_then(|cookie| ViewMode::from_str(cookie.value()))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// return the preferences stored in the cookies, replaced by the ``sort`` and ``view`` of the query if present. The choice of the query is stored in the cookies, so it is remembered for the next folders.
+    ///
+    /// An empty ``sort`` select the order of the plugin.
+    pub fn from_query(cookies: &CookieJar<'_>, sort: Option<&str>, view: Option<&str>) -> Self {
+        let mut result = Self::from_cookies(cookies);
+        if let Some(sort) = sort {
+            result.sort = SortMethod::from_str(sort);
+        };
+        if let Some(view) = view.and_then(ViewMode::from_str) {
+            result.view = view;
+        };
+        if sort.is_some() || view.is_some() {
+            result.save(cookies);
+        };
+        result
+    }
+
+    /// store these preferences in the cookies
+    pub fn save(self, cookies: &CookieJar<'_>) {
+        match self.sort {
+            Some(sort) => cookies.add(
+                Cookie::build((SORT_COOKIE, sort.as_str()))
+                    .path("/")
+                    .permanent(),
+            ),
+            None => cookies.remove(Cookie::from(SORT_COOKIE)),
+        };
+        if self.view == ViewMode::default() {
+            cookies.remove(Cookie::from(VIEW_COOKIE));
+        } else {
+            cookies.add(
+                Cookie::build((VIEW_COOKIE, self.view.as_str()))
+                    .path("/")
+                    .permanent(),
+            );
+        };
+    }
+
+    /// remove these preferences from the cookies
+    pub fn reset(cookies: &CookieJar<'_>) {
+        cookies.remove(Cookie::from(SORT_COOKIE));
+        cookies.remove(Cookie::from(VIEW_COOKIE));
+    }
+}
+
+/// render the ``<select>`` of the sort method, offering only ``available``
+pub fn render_sort_select(
+    available: &[SortMethod],
+    current: Option<SortMethod>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    html!(
+        label for="listing_sort" { (LOCALES.lookup(locale, "sort-by")) " " }
+        select id="listing_sort" name="sort" {
+            option value="" selected[current.is_none()] { (LOCALES.lookup(locale, "sort-plugin-order")) }
+            @for method in available {
+                option value=(method.as_str()) selected[Some(*method) == current] {
+                    (LOCALES.lookup(locale, &format!("sort-{}", method.as_str())))
+                }
+            }
+        }
+    )
+}
+
+/// render the ``<select>`` of the view mode
+pub fn render_view_select(current: ViewMode, locale: &LanguageIdentifier) -> Markup {
+    html!(
+        label for="listing_view" { (LOCALES.lookup(locale, "view-mode")) " " }
+        select id="listing_view" name="view" {
+            @for mode in &ViewMode::ALL {
+                option value=(mode.as_str()) selected[*mode == current] {
+                    (LOCALES.lookup(locale, &format!("view-{}", mode.as_str())))
+                }
+            }
+        }
+    )
+}
+
+/// render the form choosing the sort method, the filter and the view mode of the folder at ``link``
+pub fn render_listing_form(
+    link: &LinkState,
+    token_secret: Option<&str>,
+    available: &[SortMethod],
+    preferences: ListingPreferences,
+    filter: Option<&str>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    html!(
+        form method="get" action="/plugin" class="listing_form" {
+            (render_link_inputs(link, token_secret, ""))
+            (render_sort_select(available, preferences.sort, locale))
+            " "
+            label for="listing_filter" { (LOCALES.lookup(locale, "filter")) " " }
+            input type="search" id="listing_filter" name="filter" value=(filter.unwrap_or_default()) {}
+            " "
+            (render_view_select(preferences.view, locale))
+            " "
+            input type="submit" value=(LOCALES.lookup(locale, "listing-apply")) {}
+        }
+    )
+}
+
+/// number of pages linked before and after the current one by [`render_pagination`]
+const PAGE_LINK_RADIUS: usize = 3;
+
+/// The position of a page in the content of a folder split by [`paginate`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pagination {
+    /// the number of the displayed page, starting at 1
+    pub current: usize,
+    pub count: usize,
+}
+
+impl Pagination {
+    pub fn previous(self) -> Option<usize> {
+        if self.current > 1 {
+            Some(self.current - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn next(self) -> Option<usize> {
+        if self.current < self.count {
+            Some(self.current + 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// keep only the elements of the page ``requested`` (starting at 1) of ``elements``, each page containing ``page_size`` elements. An invalid page number display the nearest valid page. A ``page_size`` of 0 keep all the elements.
+pub fn paginate<T>(
+    elements: &mut Vec<T>,
+    page_size: usize,
+    requested: Option<usize>,
+) -> Pagination {
+    if page_size == 0 {
+        return Pagination {
+            current: 1,
+            count: 1,
+        };
+    };
+    let count = elements.len().div_ceil(page_size).max(1);
+    let current = requested.unwrap_or(1).clamp(1, count);
+    let start = (current - 1) * page_size;
+    elements.truncate(start + page_size);
+    elements.drain(..start.min(elements.len()));
+    Pagination { current, count }
+}
+
+/// render the links to the other pages of a folder. ``get_page_url`` return the url of a page from its number.
+pub fn render_pagination(
+    pagination: Pagination,
+    get_page_url: impl Fn(usize) -> String,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    html!(
+        @if pagination.count > 1 {
+            ul class="horizontallist pagination" {
+                @if let Some(previous) = pagination.previous() {
+                    li { a href=(get_page_url(previous)) rel="prev" { (LOCALES.lookup(locale, "page-previous")) } }
+                }
+                @for page in 1..=pagination.count {
+                    // only the first, the last and the pages near the current one are linked
+                    @if page == 1 || page == pagination.count || page.abs_diff(pagination.current) <= PAGE_LINK_RADIUS {
+                        li {
+                            @if page == pagination.current {
+                                strong { (page) }
+                            } @else {
+                                a href=(get_page_url(page)) { (page) }
+                            }
+                        }
+                    } @else if page.abs_diff(pagination.current) == PAGE_LINK_RADIUS + 1 {
+                        li { "…" }
+                    }
+                }
+                @if let Some(next) = pagination.next() {
+                    li { a href=(get_page_url(next)) rel="next" id="next_page" { (LOCALES.lookup(locale, "page-next")) } }
+                }
+            }
+        }
+    )
+}
+
+#[test]
+fn test_paginate() {
+    let get_elements = || (1..=7).collect::<Vec<usize>>();
+
+    let mut elements = get_elements();
+    let pagination = paginate(&mut elements, 3, None);
+    assert_eq!(
+        pagination,
+        Pagination {
+            current: 1,
+            count: 3
+        }
+    );
+    assert_eq!(elements, vec![1, 2, 3]);
+    assert_eq!(pagination.previous(), None);
+    assert_eq!(pagination.next(), Some(2));
+
+    // the last page is incomplete
+    let mut elements = get_elements();
+    let pagination = paginate(&mut elements, 3, Some(3));
+    assert_eq!(
+        pagination,
+        Pagination {
+            current: 3,
+            count: 3
+        }
+    );
+    assert_eq!(elements, vec![7]);
+    assert_eq!(pagination.previous(), Some(2));
+    assert_eq!(pagination.next(), None);
+
+    // invalid pages display the nearest valid one
+    let mut elements = get_elements();
+    assert_eq!(paginate(&mut elements, 3, Some(10)).current, 3);
+    assert_eq!(elements, vec![7]);
+    let mut elements = get_elements();
+    assert_eq!(paginate(&mut elements, 3, Some(0)).current, 1);
+    assert_eq!(elements, vec![1, 2, 3]);
+
+    // a page size of 0 keep everything
+    let mut elements = get_elements();
+    assert_eq!(
+        paginate(&mut elements, 0, Some(2)),
+        Pagination {
+            current: 1,
+            count: 1
+        }
+    );
+    assert_eq!(elements, get_elements());
+
+    // an empty folder still has a page
+    let mut elements: Vec<usize> = Vec::new();
+    assert_eq!(
+        paginate(&mut elements, 3, Some(2)),
+        Pagination {
+            current: 1,
+            count: 1
+        }
+    );
+    assert!(elements.is_empty());
+}
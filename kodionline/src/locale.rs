@@ -1,14 +1,201 @@
-use fluent_templates;
+use fluent_langneg::{accepted_languages, negotiate_languages, NegotiationStrategy};
+use fluent_templates::fluent_bundle::FluentValue;
+use fluent_templates::{ArcLoader, Loader, StaticLoader};
+use log::error;
+use rocket::request::{FromRequest, Outcome, Request};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{LazyLock, OnceLock};
 use unic_langid::{langid, LanguageIdentifier};
 
 fluent_templates::static_loader! {
-    pub static LOCALES = {
+    static EMBEDDED_LOCALES = {
         locales: "../locales",
         fallback_language: "en-US",
         customise: |bundle| bundle.set_use_isolating(false),
     };
 }
 
-pub fn get_ui_locale() -> LanguageIdentifier {
-    langid!("fr-FR")
+/// name of the cookie and of the query parameter that can be used to choose the language of the interface
+pub const UI_LOCALE_KEY: &str = "ui_lang";
+
+static FALLBACK_LOCALE: LanguageIdentifier = langid!("en-US");
+
+/// The translations of the interface. It contain the translations embedded in the binary, and the one from the directories loaded with [`Locales::load_extra_directories`].
+///
+/// The translations of the extra directories take priority over the embedded one.
+pub struct Locales {
+    embedded: &'static LazyLock<StaticLoader>,
+    extra: OnceLock<Vec<ArcLoader>>,
+}
+
+pub static LOCALES: Locales = Locales {
+    embedded: &EMBEDDED_LOCALES,
+    extra: OnceLock::new(),
+};
+
+impl Locales {
+    /// load the translations present in the given directories. They should have the same layout as the ``locales`` folder of this repository.
+    ///
+    /// It can only be called once (subsequent call will be ignored with a log).
+    ///
+    /// # Errors
+    ///
+    /// return an error if one of the directories can't be loaded
+    pub fn load_extra_directories(&self, directories: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut loaders = Vec::new();
+        for directory in directories {
+            loaders.push(
+                ArcLoader::builder(directory, FALLBACK_LOCALE.clone())
+                    .customize(|bundle| bundle.set_use_isolating(false))
+                    .build()?,
+            );
+        }
+        if self.extra.set(loaders).is_err() {
+            error!(
+                "extra locale directories were already loaded. Ignoring {:?}",
+                directories
+            );
+        };
+        Ok(())
+    }
+
+    fn extra(&self) -> &[ArcLoader] {
+        self.extra.get().map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    /// return the list of the locale that have a translation, without duplicate
+    pub fn available_locales(&self) -> Vec<LanguageIdentifier> {
+        let mut result: Vec<LanguageIdentifier> = Vec::new();
+        for locale in self.locales() {
+            if !result.contains(locale) {
+                result.push(locale.clone());
+            }
+        }
+        result.sort_by_key(|x| x.to_string());
+        result
+    }
+}
+
+impl Loader for Locales {
+    fn lookup_complete(
+        &self,
+        lang: &LanguageIdentifier,
+        text_id: &str,
+        args: Option<&HashMap<Cow<'static, str>, FluentValue>>,
+    ) -> String {
+        self.try_lookup_complete(lang, text_id, args)
+            .unwrap_or_else(|| format!("Unknown localization {}", text_id))
+    }
+
+    fn try_lookup_complete(
+        &self,
+        lang: &LanguageIdentifier,
+        text_id: &str,
+        args: Option<&HashMap<Cow<'static, str>, FluentValue>>,
+    ) -> Option<String> {
+        // first try the asked language (and its fallback), then the fallback language
+        for loader in self.extra() {
+            if let Some(text) = loader.lookup_no_default_fallback(lang, text_id, args) {
+                return Some(text);
+            }
+        }
+        if let Some(text) = self
+            .embedded
+            .lookup_no_default_fallback(lang, text_id, args)
+        {
+            return Some(text);
+        }
+        for loader in self.extra() {
+            if let Some(text) = loader.try_lookup_complete(lang, text_id, args) {
+                return Some(text);
+            }
+        }
+        self.embedded.try_lookup_complete(lang, text_id, args)
+    }
+
+    fn locales(&self) -> Box<dyn Iterator<Item = &LanguageIdentifier> + '_> {
+        Box::new(
+            self.extra()
+                .iter()
+                .flat_map(|loader| loader.locales())
+                .chain(self.embedded.locales()),
+        )
+    }
+}
+
+fn parse_available_locale(
+    value: &str,
+    available: &[LanguageIdentifier],
+) -> Option<LanguageIdentifier> {
+    let locale: LanguageIdentifier = value.parse().ok()?;
+    if available.contains(&locale) {
+        Some(locale)
+    } else {
+        None
+    }
+}
+
+/// return the languages listed in the ``Accept-Language`` header of the request, by order of preference
+pub fn get_accepted_languages(request: &Request<'_>) -> Vec<LanguageIdentifier> {
+    request
+        .headers()
+        .get_one("Accept-Language")
+        .map(accepted_languages::parse)
+        .unwrap_or_default()
+}
+
+/// return the locale to use for the interface of this request.
+///
+/// In order, it use the ``ui_lang`` query parameter, the ``ui_lang`` cookie, then negotiate it with the ``Accept-Language`` header. It default to ``en-US``.
+pub fn get_ui_locale(request: &Request<'_>) -> LanguageIdentifier {
+    let available = LOCALES.available_locales();
+
+    if let Some(Ok(query_value)) = request.query_value::<&str>(UI_LOCALE_KEY) {
+        if let Some(locale) = parse_available_locale(query_value, &available) {
+            return locale;
+        }
+    };
+
+    if let Some(cookie) = request.cookies().get(UI_LOCALE_KEY) {
+        if let Some(locale) = parse_available_locale(cookie.value(), &available) {
+            return locale;
+        }
+    };
+
+    let requested = get_accepted_languages(request);
+    if !requested.is_empty() {
+        if let Some(locale) =
+            negotiate_languages(&requested, &available, None, NegotiationStrategy::Lookup).first()
+        {
+            return (*locale).clone();
+        }
+    };
+
+    FALLBACK_LOCALE.clone()
+}
+
+/// Request guard containing the locale of the interface, as returned by [`get_ui_locale`]
+pub struct UiLocale(pub LanguageIdentifier);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UiLocale {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(UiLocale(get_ui_locale(request)))
+    }
+}
+
+/// Request guard containing the languages accepted by the browser, as returned by [`get_accepted_languages`]
+pub struct AcceptLanguage(pub Vec<LanguageIdentifier>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptLanguage {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(AcceptLanguage(get_accepted_languages(request)))
+    }
 }
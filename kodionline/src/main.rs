@@ -2,18 +2,49 @@
 #[macro_use]
 extern crate rocket;
 
-use clap::{App, Arg};
-use kodi_rust::{Kodi, Setting};
+use clap::{App, Arg, ArgMatches};
+use kodi_rust::{
+    check_setting, ConcurrencyLimit, Kodi, KodiParameters, SettingLoader, SettingOverrides,
+};
+use kodionline::admin_page::{
+    catch_unauthorized, evict_cache_entry, evict_plugin_cache, kill_invocation, render_admin,
+};
+use kodionline::api::{api_addons, api_media, api_openapi, api_plugin, api_plugin_negotiated};
+use kodionline::auth::{
+    hash_password, login, logout, render_login, sync_user_cookies, Authentication, FailedLogins,
+};
+use kodionline::debug_page::{bypass_cache_debug_plugin, render_debug_plugin};
+use kodionline::favourites_page::{
+    add_favourite, clear_history, export_favourites, import_favourites, remove_favourite,
+    render_favourites, update_position,
+};
+use kodionline::feed_page::render_feed;
 use kodionline::index_page::render_index;
+use kodionline::playlist_page::{render_playlist_m3u8, render_playlist_xspf};
 use kodionline::plugin_page::render_plugin;
+use kodionline::preferences_page::{
+    render_preferences, reset_preferences, save_preferences, set_ui_locale,
+};
+use kodionline::rate_limit::{RateLimit, RateLimiter};
+use kodionline::redirect_page::redirect_addon_art;
 use kodionline::redirect_page::redirect_art;
 use kodionline::redirect_page::redirect_media;
+use kodionline::reload::{SettingHandle, SettingReload};
+use kodionline::search_page::render_search;
+use kodionline::user_data::UserData;
+use kodionline::LOCALES;
 use rocket::http::ContentType;
 use rust_embed::Embed;
 use std::borrow::Cow;
+use std::error::Error;
 use std::ffi::OsStr;
-use std::fs::File;
+use std::fmt::Display;
+use std::io::{self, BufRead};
 use std::path::PathBuf;
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Embed)]
 #[folder = "static/"]
@@ -33,6 +64,47 @@ fn static_files(file: PathBuf) -> Option<(ContentType, Cow<'static, [u8]>)> {
   Some((content_type, asset.data))
 }
 
+/// parse the value of the argument ``name``, exiting with an error message if it is invalid
+fn parse_arg<T: FromStr>(app_m: &ArgMatches, name: &str) -> Option<T>
+where
+    T::Err: Display,
+{
+    app_m.value_of(name).map(|value| match value.parse() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("invalid value {:?} for --{}: {}", value, name, err);
+            exit(2);
+        }
+    })
+}
+
+/// read a password on the first line of the standard input, exiting with an error message if there is none
+fn read_password() -> String {
+    eprintln!("password:");
+    let mut line = String::new();
+    if let Err(err) = io::stdin().lock().read_line(&mut line) {
+        eprintln!("error: can't read the password: {}", err);
+        exit(2);
+    };
+    let password = line.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("error: the password is empty");
+        exit(2);
+    };
+    password.to_string()
+}
+
+/// hash the password read on the standard input, exiting with an error message if it fail
+fn read_password_hash() -> String {
+    match hash_password(&read_password()) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("error: can't hash the password: {}", err);
+            exit(1);
+        }
+    }
+}
+
 #[launch]
 fn rocket() -> _{
     let app_m = App::new("kodi online")
@@ -40,35 +112,209 @@ fn rocket() -> _{
             Arg::with_name("config")
                 .short("c")
                 .long("config")
-                .help("path to the setting file")
+                .help("path to the setting file, in JSON or TOML (if its extension is .toml)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("address")
+                .long("address")
+                .help("the address to listen on")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .help("the port to listen on")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("cache-time")
+                .long("cache-time")
+                .help("the time (in seconds) the results of every plugin are cached")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dev")
+                .long("dev")
+                .help("development mode: the results of the plugins are only cached for a few seconds"),
+        )
+        .arg(
+            Arg::with_name("check-config")
+                .long("check-config")
+                .help("check the configuration and exit"),
+        )
+        .arg(
+            Arg::with_name("hash-password")
+                .long("hash-password")
+                .help("print the hash of the password read on the standard input, for the users file, and exit"),
+        )
+        .arg(
+            Arg::with_name("add-user")
+                .long("add-user")
+                .help("create the user in the database (or change its password), with the password read on the standard input, and exit")
+                .value_name("NAME")
                 .takes_value(true),
         )
         .get_matches();
 
-    let setting = if let Some(config_path) = app_m.value_of("config") {
-        let file = File::open(config_path).unwrap();
-        serde_json::from_reader(file).unwrap()
-    } else {
-        Setting::default()
+    if app_m.is_present("hash-password") {
+        println!("{}", read_password_hash());
+        exit(0);
+    };
+
+    let loader = SettingLoader {
+        file: app_m.value_of("config").map(PathBuf::from),
+        overrides: SettingOverrides {
+            listen_address: parse_arg(&app_m, "address"),
+            port: parse_arg(&app_m, "port"),
+            cache_time: parse_arg(&app_m, "cache-time"),
+            dev_mode: app_m.is_present("dev"),
+            ..SettingOverrides::default()
+        },
+        ..SettingLoader::default()
+    };
+    let setting = match loader.load() {
+        Ok(setting) => setting,
+        Err(err) => {
+            match err.source() {
+                Some(source) => eprintln!("error: {}: {}", err, source),
+                None => eprintln!("error: {}", err),
+            };
+            exit(1);
+        }
+    };
+    for name in loader.unknown_env_variables(std::env::vars()) {
+        eprintln!(
+            "warning: the environment variable {} isn't a known setting, and is ignored",
+            name
+        );
+    }
+    let problems = check_setting(&setting);
+    for warning in &problems.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    for error in &problems.errors {
+        eprintln!("error: {}", error);
+    }
+    if !problems.errors.is_empty() {
+        exit(1);
+    };
+    if app_m.is_present("check-config") {
+        println!("the configuration is valid");
+        exit(0);
+    };
+
+    if let Err(err) = LOCALES.load_extra_directories(&setting.locale_directories) {
+        panic!(
+            "can't load the locale directories {:?}: {}",
+            setting.locale_directories, err
+        );
     };
 
-    //TODO: restore with a switch in clap once clap is updated.
-    let mut kodi = /*if Environment::active().unwrap().is_dev() {
-        Kodi::new(&setting.kodi_path, 2, 500)
-    } else {*/
-        Kodi::new(&setting.kodi_path, 3600, 500);
-    //};
+    let mut kodi = Kodi::new(&setting.kodi_path, setting.cache_time, 500);
 
-    kodi.set_python_command(setting.python_command.clone());
-    kodi.set_catch_stdout(false);
+    kodi.set_catch_stdout(setting.keep_plugin_output);
+    kodi.set_history_size(setting.invocation_history_size);
     kodi.sandbox_call(true);
-    kodi.allowed_path = setting.allowed_path.clone();
+    kodi.reconfigure(KodiParameters::from_setting(&setting));
+    kodi.set_concurrency_limit(ConcurrencyLimit::new(
+        setting.max_running_plugins,
+        setting.max_waiting_plugins,
+        Duration::from_secs(setting.plugin_wait_timeout),
+    ));
+    let rate_limit = RateLimit {
+        limiter: setting.rate_limit_calls.map(|max_calls| {
+            Arc::new(RateLimiter::new(
+                max_calls,
+                Duration::from_secs(setting.rate_limit_window),
+            ))
+        }),
+    };
+
+    let user_data = match UserData::open(setting.database_path.as_deref()) {
+        Ok(value) => value,
+        Err(err) => panic!(
+            "can't open the database {:?}: {}",
+            setting.database_path, err
+        ),
+    };
+
+    if let Some(name) = app_m.value_of("add-user") {
+        if setting.users_file.is_some() {
+            eprintln!("warning: users_file is set, so the users of the database are ignored");
+        };
+        if let Err(err) = user_data.set_user(name, &read_password_hash()) {
+            eprintln!("error: can't save the user {}: {}", name, err);
+            exit(1);
+        };
+        println!("the user {} was saved", name);
+        exit(0);
+    };
+
+    let mut figment = rocket::Config::figment();
+    if let Some(listen_address) = setting.listen_address {
+        figment = figment.merge(("address", listen_address));
+    };
+    if let Some(port) = setting.port {
+        figment = figment.merge(("port", port));
+    };
+
+    let setting = SettingHandle::new(setting);
+    let setting_reload = SettingReload {
+        loader,
+        setting: setting.clone(),
+        kodi: kodi.reloader(),
+    };
 
-    rocket::build()
+    rocket::custom(figment)
+        .attach(rate_limit)
+        .attach(Authentication)
+        .attach(setting_reload)
         .manage(kodi)
+        .manage(user_data)
         .manage(setting)
+        .manage(FailedLogins::default())
         .mount(
             "/",
-            routes![render_index, render_plugin, redirect_media, redirect_art, static_files],
+            routes![
+                render_index,
+                render_plugin,
+                redirect_media,
+                redirect_art,
+                redirect_addon_art,
+                render_preferences,
+                save_preferences,
+                reset_preferences,
+                set_ui_locale,
+                api_plugin,
+                api_plugin_negotiated,
+                api_media,
+                api_addons,
+                api_openapi,
+                render_feed,
+                render_playlist_m3u8,
+                render_playlist_xspf,
+                render_search,
+                render_favourites,
+                add_favourite,
+                remove_favourite,
+                clear_history,
+                update_position,
+                export_favourites,
+                import_favourites,
+                render_admin,
+                kill_invocation,
+                evict_cache_entry,
+                evict_plugin_cache,
+                render_debug_plugin,
+                bypass_cache_debug_plugin,
+                render_login,
+                login,
+                sync_user_cookies,
+                logout,
+                static_files
+            ],
         )
+        .register("/", catchers![catch_unauthorized])
 }
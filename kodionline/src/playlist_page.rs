@@ -0,0 +1,256 @@
+use crate::{
+    get_link_to_route, rate_limit::Client, reload::CurrentSetting, AcceptLanguage, BaseUrl,
+    ExportedPage, LinkParams,
+};
+use kodi_rust::{
+    data::ListItem, get_art_link_subcontent, get_media_link_resolved_url,
+    get_media_link_subcontent, Kodi, LinkState,
+};
+
+use log::error;
+use maud::{html, PreEscaped};
+use rocket::http::{ContentType, CookieJar};
+use rocket::State;
+
+/// The format of a playlist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::M3u8 => "m3u8",
+            Self::Xspf => "xspf",
+        }
+    }
+
+    pub fn content_type(self) -> ContentType {
+        match self {
+            Self::M3u8 => ContentType::new("audio", "x-mpegurl"),
+            Self::Xspf => ContentType::new("application", "xspf+xml"),
+        }
+    }
+}
+
+/// return the link to the playlist of a folder (or of a single media), taking the same parameters as [`crate::get_absolute_plugin_path`]
+pub fn get_playlist_path(
+    format: PlaylistFormat,
+    link: &LinkState,
+    token_secret: Option<&str>,
+) -> String {
+    get_link_to_route(
+        &format!("/playlist.{}", format.extension()),
+        link,
+        token_secret,
+    )
+}
+
+/// An entry of a playlist, with absolute links
+pub struct PlaylistEntry {
+    pub title: String,
+    /// the duration, in seconds
+    pub duration: Option<u64>,
+    pub media_url: String,
+    pub image_url: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub plot: Option<String>,
+}
+
+impl PlaylistEntry {
+    fn new(listitem: &ListItem, media_url: String, image_url: Option<String>) -> Self {
+        Self {
+            title: listitem
+                .info
+                .title
+                .clone()
+                .or_else(|| listitem.label.clone())
+                .unwrap_or_else(|| media_url.clone()),
+            duration: listitem.info.duration,
+            media_url,
+            image_url,
+            artist: listitem.info.artist.clone(),
+            album: listitem.info.album.clone(),
+            plot: listitem.info.plot.clone(),
+        }
+    }
+}
+
+/// remove the line breaks of a value, as they would break a M3U8 playlist
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// render a playlist in the extended M3U format
+pub fn render_m3u8(title: &str, entries: &[PlaylistEntry]) -> String {
+    let mut result = format!("#EXTM3U\n#PLAYLIST:{}\n", single_line(title));
+    for entry in entries {
+        result.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            entry
+                .duration
+                .map(|duration| duration.to_string())
+                .unwrap_or_else(|| "-1".to_string()),
+            single_line(&entry.title),
+            single_line(&entry.media_url)
+        ));
+    }
+    result
+}
+
+/// render a playlist in the XSPF format
+pub fn render_xspf(title: &str, entries: &[PlaylistEntry]) -> String {
+    html!(
+        (PreEscaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"))
+        playlist version="1" xmlns="http://xspf.org/ns/0/" {
+            title { (title) }
+            trackList {
+                @for entry in entries {
+                    track {
+                        location { (entry.media_url) }
+                        title { (entry.title) }
+                        @if let Some(artist) = &entry.artist {
+                            creator { (artist) }
+                        }
+                        @if let Some(album) = &entry.album {
+                            album { (album) }
+                        }
+                        @if let Some(plot) = &entry.plot {
+                            annotation { (plot) }
+                        }
+                        @if let Some(duration) = entry.duration {
+                            duration { (duration * 1000) }
+                        }
+                        @if let Some(image_url) = &entry.image_url {
+                            image { (image_url) }
+                        }
+                    }
+                }
+            }
+        }
+    )
+    .into_string()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_playlist(
+    format: PlaylistFormat,
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    base_url: BaseUrl,
+    link: LinkParams,
+) -> Option<(ContentType, String)> {
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let ExportedPage {
+        link,
+        page,
+        subcontent_from_parent,
+        title,
+    } = ExportedPage::load(
+        kodi,
+        client,
+        setting,
+        cookies,
+        &accept_language,
+        link,
+        "playlist",
+    )?;
+
+    let entries: Vec<PlaylistEntry> = match page.resolved_listitem {
+        // a single media
+        Some(mut resolved_listitem) => {
+            if let Some(subcontent_from_parent) = subcontent_from_parent {
+                resolved_listitem.extend(subcontent_from_parent.listitem);
+            }
+            let media_url = match &resolved_listitem.path {
+                Some(media_url) => {
+                    base_url.absolute(&get_media_link_resolved_url(media_url, &link, token_secret))
+                }
+                None => {
+                    error!("no media found for the playlist of {:?}", link.access);
+                    return None;
+                }
+            };
+            vec![PlaylistEntry::new(&resolved_listitem, media_url, None)]
+        }
+        // a folder
+        None => page
+            .sub_content
+            .iter()
+            .filter(|sub_content| sub_content.listitem.is_playable())
+            .map(|sub_content| {
+                PlaylistEntry::new(
+                    &sub_content.listitem,
+                    base_url.absolute(&get_media_link_subcontent(sub_content, &link, token_secret)),
+                    sub_content.listitem.get_thumb_category().map(|category| {
+                        base_url.absolute(&get_art_link_subcontent(
+                            sub_content,
+                            category,
+                            &link,
+                            token_secret,
+                        ))
+                    }),
+                )
+            })
+            .collect(),
+    };
+
+    let content = match format {
+        PlaylistFormat::M3u8 => render_m3u8(&title, &entries),
+        PlaylistFormat::Xspf => render_xspf(&title, &entries),
+    };
+    Some((format.content_type(), content))
+}
+
+/// return a M3U8 playlist of every playable element of a folder, or of the media of a media page. It take the same parameters as [`crate::plugin_page::render_plugin`].
+#[get("/playlist.m3u8?<link..>")]
+pub fn render_playlist_m3u8(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    base_url: BaseUrl,
+    link: LinkParams,
+) -> Option<(ContentType, String)> {
+    render_playlist(
+        PlaylistFormat::M3u8,
+        kodi,
+        client,
+        setting,
+        cookies,
+        accept_language,
+        base_url,
+        link,
+    )
+}
+
+/// the same as [`render_playlist_m3u8`], in the XSPF format
+#[get("/playlist.xspf?<link..>")]
+pub fn render_playlist_xspf(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    base_url: BaseUrl,
+    link: LinkParams,
+) -> Option<(ContentType, String)> {
+    render_playlist(
+        PlaylistFormat::Xspf,
+        kodi,
+        client,
+        setting,
+        cookies,
+        accept_language,
+        base_url,
+        link,
+    )
+}
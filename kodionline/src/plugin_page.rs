@@ -1,87 +1,255 @@
-use crate::{error_page::generate_error_page, get_absolute_plugin_path, Presentation};
+use crate::{
+    error_page::{generate_error_page, get_kodi_error_status, render_kodi_error},
+    favourites_page::{record_media_in_history, render_favourite_button},
+    feed_page::{format_duration, get_feed_path},
+    get_absolute_plugin_path, get_child_link, get_final_user_config, get_plugin_type,
+    listing::{paginate, render_listing_form, render_pagination, ListingPreferences, ViewMode},
+    playlist_page::{get_playlist_path, PlaylistFormat},
+    rate_limit::Client,
+    reload::CurrentSetting,
+    render_link_inputs,
+    thumbnail::{get_addon_art_url, get_art_url, render_illustration},
+    user_data::{SavedEntry, UserData},
+    LinkParams, PluginRequest, Presentation, PresentationResponse,
+};
 use kodi_rust::{
-    data::KodiResult, format_to_string, get_art_link_subcontent, get_media_link_resolved_url,
-    get_media_link_subcontent, get_sub_content_from_parent, input::decode_input,
-    input::encode_input, Kodi, PathAccessData, Setting, UserConfig,
+    data::{KodiResult, SubContent},
+    filter_sub_content, format_to_string, get_media_link_resolved_url, get_media_link_subcontent,
+    get_siblings_from_parent, get_sub_contents_of_chain, group_by_season, is_episode_list,
+    urlencode, ArtContext, Kodi, LinkState, Setting, Siblings, SortMethod,
 };
 
 use fluent_templates::Loader;
 use log::error;
 use maud::{html, Markup, PreEscaped};
+use rocket::http::CookieJar;
 use rocket::State;
 use std::collections::HashMap;
+use std::ops::Range;
+use unic_langid::LanguageIdentifier;
 
-use crate::{get_ui_locale, LOCALES};
+use crate::{AcceptLanguage, UiLocale, LOCALES};
 
-#[allow(clippy::too_many_arguments)]
-#[get("/plugin?<path>&<parent_path>&<input>&<parent_input>&<additional_input>&<c>")]
-pub fn render_plugin(
-    kodi: &State<Kodi>,
-    setting: &State<Setting>,
-    path: String,
-    parent_path: Option<String>,
-    input: Option<&str>,
-    parent_input: Option<&str>,
-    additional_input: Option<String>,
-    c: Option<String>, //TODO: user_config_encoded in cookie
+/// render the link to a child of a folder, with its title, its thumbnail and its plot. With ``show_episode``, the title is prefixed by the episode code (like ``S01E02``) and the duration is displayed.
+pub fn render_sub_content_link(
+    sub_content: &SubContent,
+    current: &LinkState,
+    setting: &Setting,
+    show_episode: bool,
 ) -> Markup {
-    let locale = get_ui_locale();
+    let token_secret = setting.state_token_secret.as_deref();
+    let info = &sub_content.listitem.info;
+    html!(
+        a href=(get_absolute_plugin_path(&get_child_link(current, sub_content.url.clone()), token_secret)) {
+            div class="subelem_title" {
+                @if show_episode {
+                    @if let Some(episode_code) = info.episode_code() {
+                        span class="episode_code" { (episode_code) } " – "
+                    }
+                }
+                (PreEscaped(sub_content.listitem.get_display_html()))
+            }
+            (render_illustration(setting, sub_content, current))
 
-    let user_config_encoded = c;
+            @if show_episode {
+                @if let Some(duration) = info.duration {
+                    p class="duration" { (format_duration(duration)) }
+                }
+            }
 
-    let user_config_in_url = UserConfig::new_from_optional_uri(user_config_encoded);
-    let user_config = user_config_in_url;
+            @if let Some(plot) = &info.plot {
+                p class="plot" { (plot) }
+            }
+        }
+    )
+}
 
-    let final_config = setting
-        .default_user_config
-        .clone()
-        .add_config_prioritary(user_config.clone());
+/// return the url of the art of the current page that fit ``context`` best. It is searched in the page itself (as listed by its parent), then in the folders above it (the closest first, like the show of an episode), and finally in the addon.
+fn get_page_art_url(
+    kodi: &Kodi,
+    setting: &Setting,
+    link: &LinkState,
+    subcontent_from_parent: Option<&SubContent>,
+    chain_sub_contents: &[Option<SubContent>],
+    context: ArtContext,
+) -> Option<String> {
+    // every page of the chain with its state, the current one first
+    let states = std::iter::successors(Some(link.clone()), |state| state.parent_state());
+    let sub_contents = std::iter::once(subcontent_from_parent)
+        .chain(chain_sub_contents.iter().rev().map(Option::as_ref));
+    for (state, sub_content) in states.zip(sub_contents) {
+        if let (Some(sub_content), Some(parent)) = (sub_content, state.parent_state()) {
+            if let Some(category) = sub_content.listitem.get_art_category(context) {
+                return Some(get_art_url(setting, sub_content, category, None, &parent));
+            }
+        }
+    }
+    let category = context.addon_fallback()?;
+    kodi.get_addon_art(&link.access.path).get(category)?;
+    Some(get_addon_art_url(&link.access.path, category))
+}
+
+/// return the label and the link of every page above ``link``, from the root of the plugin. ``chain_sub_contents`` are the [`SubContent`] of these pages in their own parent, as returned by [`get_sub_contents_of_chain`].
+fn get_breadcrumb(
+    link: &LinkState,
+    chain_sub_contents: &[Option<SubContent>],
+    setting: &Setting,
+    token_secret: Option<&str>,
+) -> Vec<(Markup, String)> {
+    let mut states: Vec<LinkState> =
+        std::iter::successors(link.parent_state(), |state| state.parent_state()).collect();
+    states.reverse();
+    states
+        .iter()
+        .zip(chain_sub_contents.iter())
+        .map(|(state, sub_content)| {
+            let label = match sub_content {
+                Some(sub_content) => html!((PreEscaped(sub_content.listitem.get_display_html()))),
+                None => html!(
+                    (setting
+                        .get_label_for_path(&state.access.path)
+                        .unwrap_or_else(|| state.access.path.clone()))
+                ),
+            };
+            (label, get_absolute_plugin_path(state, token_secret).0)
+        })
+        .collect()
+}
 
-    let mut input = input.map(|x| decode_input(&x)).unwrap_or_else(Vec::new);
+/// The links to the previous and next playable elements of the folder containing a media
+#[derive(Default)]
+struct SiblingLinks {
+    previous: Option<(Markup, String)>,
+    next: Option<(Markup, String)>,
+}
 
-    if let Some(value) = additional_input {
-        input.push(value)
+impl SiblingLinks {
+    fn new(siblings: Siblings, parent: &LinkState, token_secret: Option<&str>) -> Self {
+        let get_link = |sibling: SubContent| {
+            (
+                html!((PreEscaped(sibling.listitem.get_display_html()))),
+                get_absolute_plugin_path(&get_child_link(parent, sibling.url), token_secret).0,
+            )
+        };
+        Self {
+            previous: siblings.previous.map(get_link),
+            next: siblings.next.map(get_link),
+        }
     }
 
-    let current_access = PathAccessData {
-        path: path.clone(),
-        input,
-        config: final_config.clone(),
+    fn render(&self, locale: &LanguageIdentifier) -> Markup {
+        html!(
+            @if self.previous.is_some() || self.next.is_some() {
+                ul class="horizontallist siblings" {
+                    @if let Some((label, link)) = &self.previous {
+                        li class="previous" {
+                            a href=(link) rel="prev" { (LOCALES.lookup(locale, "previous-item")) " : " (label) }
+                        }
+                    }
+                    @if let Some((label, link)) = &self.next {
+                        li class="next" {
+                            a href=(link) rel="next" { (LOCALES.lookup(locale, "next-item")) " : " (label) }
+                        }
+                    }
+                }
+            }
+        )
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get(
+    "/plugin?<additional_input>&<sort>&<filter>&<view>&<page>&<link..>",
+    rank = 2
+)]
+pub fn render_plugin(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    user_data: &State<UserData>,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    accept_language: AcceptLanguage,
+    additional_input: Option<String>,
+    sort: Option<String>,
+    filter: Option<String>,
+    view: Option<String>,
+    page: Option<usize>,
+    link: LinkParams,
+) -> PresentationResponse {
+    let locale = ui_locale.0;
+
+    let token_secret = setting.state_token_secret.as_deref();
+
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => {
+            return generate_error_page(
+                html!((LOCALES.lookup(&locale, "error-invalid-link")) " (" (err) ")"),
+                &locale,
+            )
+            .build_response(&locale)
+        }
     };
 
-    let current_access_without_static = {
-        let mut v = current_access.clone();
-        v.config = user_config;
-        v
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+
+    let PluginRequest { link, final_link } =
+        PluginRequest::new(link_state, additional_input, final_config);
+    let current_access = &final_link.access;
+
+    if let Err(err) = kodi.check_addon(&current_access.path) {
+        return generate_error_page(render_kodi_error(&err, &locale), &locale)
+            .build_response(&locale)
+            .status(get_kodi_error_status(&err));
     };
 
-    let mut splited = path.split('.');
-    splited.next();
-    let plugin_type = match splited.next() {
-        Some(value) => value.to_string(),
+    let plugin_type = match get_plugin_type(&current_access.path) {
+        Some(value) => value,
         None => {
             return generate_error_page(
                 html!((LOCALES.lookup(&locale, "error-cant-get-plugin-type"))),
                 &locale,
             )
-            .kodi_url(Some(current_access_without_static.path))
-            .build(&locale)
+            .kodi_url(Some(link.access.path))
+            .build_response(&locale)
         }
     };
 
-    let parent_access = PathAccessData::try_create_from_url(
-        parent_path,
-        parent_input,
-        final_config,
-    );
+    // the sub content of every page of the ancestry chain in its own parent. The last one is the current page.
+    let mut chain_sub_contents =
+        get_sub_contents_of_chain(kodi, &client.secondary(), &final_link.chain());
+    let subcontent_from_parent = chain_sub_contents.pop().flatten();
+
+    let breadcrumb = get_breadcrumb(&link, &chain_sub_contents, setting, token_secret);
 
-    let subcontent_from_parent = if let Some(ref parent_access_internal) = parent_access {
-        get_sub_content_from_parent(&kodi, &parent_access_internal, &current_access.path)
-    } else {
-        None
+    let get_art = |context: ArtContext| {
+        get_page_art_url(
+            kodi,
+            setting,
+            &link,
+            subcontent_from_parent.as_ref(),
+            &chain_sub_contents,
+            context,
+        )
     };
+    let thumbnail = get_art(ArtContext::Tile);
+    let background_url = get_art(ArtContext::Background);
+    let logo_url = get_art(ArtContext::Logo);
+    let poster_url = get_art(ArtContext::Poster);
+
+    let saved_link = link.clone();
 
-    match kodi.invoke_sandbox(&current_access) {
+    // ``page`` is the content of the plugin page below
+    let page_number = page;
+
+    match kodi.invoke_sandbox_for(current_access, client) {
         Ok(KodiResult::Content(mut page)) => {
             match page.resolved_listitem {
                 // contain a media
@@ -89,10 +257,14 @@ pub fn render_plugin(
                     if let Some(subcontent_from_parent) = subcontent_from_parent {
                         resolved_listitem.extend(subcontent_from_parent.listitem);
                     }
+                    // the folders higher in the hierarchy (like the show of an episode), the closest first
+                    for ancestor in chain_sub_contents.into_iter().rev().flatten() {
+                        resolved_listitem.extend_from_ancestor(ancestor.listitem);
+                    }
 
                     let media_base_url = match &resolved_listitem.path {
                         Some(url) => url.clone(),
-                        None => return generate_error_page(html!((LOCALES.lookup(&locale, "error-no-media-found-on-page"))), &locale).kodi_url(Some(current_access_without_static.path)).build(&locale),
+                        None => return generate_error_page(html!((LOCALES.lookup(&locale, "error-no-media-found-on-page"))), &locale).kodi_url(Some(link.access.path)).build_response(&locale),
                     };
 
                     let title = html!((PreEscaped(resolved_listitem.get_display_html())));
@@ -103,12 +275,20 @@ pub fn render_plugin(
                         .clone()
                         .map(|comment| format_to_string(&comment));
 
-                    let media_url = get_media_link_resolved_url(
-                        &media_base_url,
-                        &current_access.path,
-                        current_access.input.clone(),
-                        &current_access_without_static,
-                    );
+                    let media_url =
+                        get_media_link_resolved_url(&media_base_url, &link, token_secret);
+
+                    let saved_entry = SavedEntry {
+                        link: saved_link,
+                        label: resolved_listitem
+                            .label
+                            .clone()
+                            .or_else(|| resolved_listitem.info.title.clone())
+                            .unwrap_or_else(|| current_access.path.clone()),
+                        thumbnail,
+                        is_folder: false,
+                    };
+                    let resume_position = record_media_in_history(user_data, cookies, &saved_entry);
 
                     let media_type = if let Some(t) = resolved_listitem.category {
                         t
@@ -116,37 +296,58 @@ pub fn render_plugin(
                         plugin_type
                     };
 
+                    let siblings = match &final_link.parent {
+                        Some(parent) => get_siblings_from_parent(kodi, &client.secondary(), parent, &current_access.path),
+                        None => Siblings::default(),
+                    };
+                    let sibling_links = link.parent_state().map(|parent| SiblingLinks::new(siblings, &parent, token_secret)).unwrap_or_default();
+
                     Presentation::new(
                         title,
                         html!(
                             div class = "main_media" {
                                 @if media_type == "video" {
-                                    video controls="" {
+                                    video class="tracked_media" controls="" poster=[poster_url] data-resume=[resume_position] data-next=[sibling_links.next.as_ref().map(|(_, link)| link)] {
                                         source src=(media_url) {}
                                         //TODO: subtitles
                                     }
                                 } @else if media_type == "audio" {
-                                    audio controls="" {
+                                    @if let Some(poster_url) = poster_url {
+                                        img class="media_poster" src=(poster_url) alt="" {}
+                                    }
+                                    audio class="tracked_media" controls="" data-resume=[resume_position] {
                                         source src=(media_url) {}
                                     }
                                 } @else {
-                                    p { "can't detect the media type. Please download the file to use it." }
+                                    p { (LOCALES.lookup(&locale, "error-cant-detect-media-type")) }
+                                }
+                            }
+                            script type="text/javascript" src="/static/history.js" {}
+
+                            (sibling_links.render(&locale))
+                            @if media_type == "video" && (sibling_links.previous.is_some() || sibling_links.next.is_some()) {
+                                p class="autoplay_next" {
+                                    input type="checkbox" id="autoplay_next" {}
+                                    label for="autoplay_next" { (LOCALES.lookup(&locale, "autoplay-next")) }
                                 }
+                                script type="text/javascript" src="/static/autoplay.js" {}
                             }
 
+                            (render_favourite_button(user_data, cookies, &saved_entry, token_secret, &locale))
+
                             div class = "alt_media" {
                                 @if !resolved_listitem.x_avalaible_languages.is_empty() {
-                                    p { strong { "avalaible languages" }}
+                                    p { strong { (LOCALES.lookup(&locale, "avalaible-languages")) }}
                                     ul class="language altlink" {
                                         @for language in resolved_listitem.x_avalaible_languages {
                                             li {
                                                 a href=(get_absolute_plugin_path(
                                                     &{
-                                                        let mut parent = current_access_without_static.clone();
-                                                        parent.config.language_order.value = vec![language.clone()];
-                                                        parent
+                                                        let mut link = link.clone();
+                                                        link.access.config.language_order.value = vec![language.clone()];
+                                                        link
                                                     },
-                                                    parent_access.as_ref()
+                                                    token_secret,
                                                 )) {
                                                     (language)
                                                 }
@@ -197,64 +398,177 @@ pub fn render_plugin(
                                         }
                                     }
                                 }
+                                li {
+                                    a href=(get_playlist_path(PlaylistFormat::M3u8, &link, token_secret)) {
+                                        (LOCALES.lookup(&locale, "open-in-external-player"))
+                                    }
+                                }
                             }
                         )
-                    ).kodi_url(Some(current_access_without_static.path)).build(&locale)
+                    ).kodi_url(Some(link.access.path)).breadcrumb(breadcrumb).arts(background_url, logo_url).build_response(&locale)
                 }
                 // contain a folder
                 None => {
+                    let saved_entry = SavedEntry {
+                        link: saved_link,
+                        label: subcontent_from_parent
+                            .as_ref()
+                            .and_then(|subcontent| subcontent.listitem.label.clone())
+                            .or_else(|| setting.get_label_for_path(&current_access.path))
+                            .unwrap_or_else(|| current_access.path.clone()),
+                        thumbnail,
+                        is_folder: true,
+                    };
+
                     let title_rendered = match subcontent_from_parent {
                         Some(subcontent) => {
                             html!((PreEscaped(subcontent.listitem.get_display_html())))
                         }
                         None => match setting.get_label_for_path(&current_access.path) {
                             Some(value) => html!((value)),
-                            None => html!((current_access_without_static.path)),
+                            None => html!((link.access.path)),
                         },
                     };
 
-                    let mut contain_playable_element = false;
+                    let feed_url = if page.sub_content.iter().any(|x| x.listitem.is_playable()) {
+                        Some(get_feed_path(&link, token_secret))
+                    } else {
+                        None
+                    };
+
+                    let available_sort_methods = SortMethod::available(&page.sort_methods);
+                    let listing = ListingPreferences::from_query(cookies, sort.as_deref(), view.as_deref());
+                    let filter = filter.filter(|filter| !filter.trim().is_empty());
+                    if let Some(filter) = &filter {
+                        filter_sub_content(&mut page.sub_content, filter.trim());
+                    };
+                    let sort = listing.sort.filter(|sort| available_sort_methods.contains(sort));
+                    // the episodes of a series are grouped by season, unless the user chose another order
+                    let group_by_seasons = is_episode_list(&page.sub_content)
+                        && matches!(sort, None | Some(SortMethod::Episode));
+                    if group_by_seasons {
+                        SortMethod::Episode.sort(&mut page.sub_content);
+                    } else if let Some(sort) = sort {
+                        sort.sort(&mut page.sub_content);
+                    };
+                    let pagination = paginate(&mut page.sub_content, setting.folder_page_size, page_number);
+                    let get_page_url = |page_number: usize| {
+                        format!(
+                            "{}&page={}{}",
+                            get_absolute_plugin_path(&link, token_secret).0,
+                            page_number,
+                            match &filter {
+                                Some(filter) => format!("&filter={}", urlencode(filter)),
+                                None => String::new(),
+                            }
+                        )
+                    };
+                    let rendered_pagination = render_pagination(pagination, get_page_url, &locale);
+                    let previous_page_url = pagination.previous().map(get_page_url);
+                    let next_page_url = pagination.next().map(get_page_url);
+                    let groups: Vec<(Option<u64>, Range<usize>)> = if group_by_seasons {
+                        group_by_season(&page.sub_content)
+                            .into_iter()
+                            .map(|(season, range)| (Some(season), range))
+                            .collect()
+                    } else {
+                        vec![(None, 0..page.sub_content.len())]
+                    };
+
+                    let contain_playable_element = plugin_type == "audio" && page.sub_content.iter().any(|x| x.listitem.is_playable());
+                    let render_preview = |loop_nb: usize, sub_content: &SubContent| html!(
+                        @if sub_content.listitem.is_playable() && plugin_type == "audio" {
+                            audio class="audiopreview" audiopreview_nb=(loop_nb.to_string()) preload=(if loop_nb == 0 { "auto" } else { "none" }) controls="true" {
+                                source src = (get_media_link_subcontent(
+                                    sub_content,
+                                    &link,
+                                    token_secret,
+                                )) {}
+                            }
+                        }
+                    );
+
                     Presentation::new(
                         title_rendered,
                         html!(
-                            ul class="list_media" {
-                                @for (loop_nb, sub_content) in page.sub_content.drain(..).enumerate() {
-                                    li class="media_in_list" {
-                                        a href=(get_absolute_plugin_path(&PathAccessData::new(sub_content.url.clone(), None, UserConfig::new_empty()), Some(&current_access))) {
-                                            div class="subelem_title" { (PreEscaped(sub_content.listitem.get_display_html())) }
-                                            @if let Some(thumb_category) = sub_content.listitem.get_thumb_category() {
-                                                img class="illustration" src=(get_art_link_subcontent(
-                                                    &sub_content,
-                                                    thumb_category,
-                                                    &current_access_without_static
-                                                )) {}
-                                            }
+                            (render_favourite_button(user_data, cookies, &saved_entry, token_secret, &locale))
 
-                                            @if let Some(plot) = &sub_content.listitem.info.plot {
-                                                p class="plot" { (plot) }
+                            (render_listing_form(&link, token_secret, &available_sort_methods, listing, filter.as_deref(), &locale))
+                            (rendered_pagination)
+
+                            @for (season, range) in &groups {
+                                @if let Some(season) = season {
+                                    h2 class="season_heading" {
+                                        (LOCALES.lookup_with_args(&locale, "season-heading", &{
+                                            let mut map = HashMap::new();
+                                            map.insert("season".into(), (*season).into());
+                                            map
+                                        }))
+                                    }
+                                }
+                                @if listing.view == ViewMode::Table {
+                                    table class="list_table" {
+                                        tr {
+                                            th { (LOCALES.lookup(&locale, "word-title")) }
+                                            th { (LOCALES.lookup(&locale, "word-episode")) }
+                                            th { (LOCALES.lookup(&locale, "word-year")) }
+                                            th { (LOCALES.lookup(&locale, "word-duration")) }
+                                            @if contain_playable_element { th {} }
+                                        }
+                                        @for (loop_nb, sub_content) in page.sub_content.iter().enumerate().skip(range.start).take(range.len()) {
+                                            tr {
+                                                td {
+                                                    a href=(get_absolute_plugin_path(&get_child_link(&link, sub_content.url.clone()), token_secret)) {
+                                                        (PreEscaped(sub_content.listitem.get_display_html()))
+                                                    }
+                                                }
+                                                td {
+                                                    @if let Some(episode_code) = sub_content.listitem.info.episode_code() {
+                                                        (episode_code)
+                                                    }
+                                                }
+                                                td { @if let Some(year) = sub_content.listitem.info.year { (year) } }
+                                                td { @if let Some(duration) = sub_content.listitem.info.duration { (format_duration(duration)) } }
+                                                @if contain_playable_element { td { (render_preview(loop_nb, sub_content)) } }
                                             }
                                         }
+                                    }
+                                } @else {
+                                    ul class=(if listing.view == ViewMode::Grid { "list_media poster_grid" } else { "list_media" }) {
+                                        @for (loop_nb, sub_content) in page.sub_content.iter().enumerate().skip(range.start).take(range.len()) {
+                                            li class="media_in_list" {
+                                                (render_sub_content_link(sub_content, &link, setting, group_by_seasons))
 
-                                        @if sub_content.listitem.is_playable() && plugin_type == "audio" {
-                                            ({contain_playable_element = true; ""})
-                                            br {}
-                                            audio class="audiopreview" audiopreview_nb=(loop_nb.to_string()) preload=(if loop_nb == 0 { "auto" } else { "none" }) controls="true" {
-                                                source src = (get_media_link_subcontent(
-                                                    &sub_content,
-                                                    &current_access_without_static,
-                                                )) {}
+                                                @if sub_content.listitem.is_playable() && plugin_type == "audio" {
+                                                    br {}
+                                                    (render_preview(loop_nb, sub_content))
+                                                }
                                             }
                                         }
                                     }
                                 }
                             }
 
-                            @if contain_playable_element && plugin_type == "audio" {
+                            (rendered_pagination)
+
+                            @if contain_playable_element {
                                 button id = "play_all" { (LOCALES.lookup(&locale, "play-all-music-sync")) }
                                 script type="text/javascript" src="/static/musicplayer.js" {}
                             }
+
+                            @if let Some(feed_url) = &feed_url {
+                                ul class="feed" {
+                                    li { a href=(feed_url) { (LOCALES.lookup(&locale, "subscribe-feed")) } }
+                                    li {
+                                        (LOCALES.lookup(&locale, "playlist-export")) " : "
+                                        a href=(get_playlist_path(PlaylistFormat::M3u8, &link, token_secret)) { "M3U8" }
+                                        " / "
+                                        a href=(get_playlist_path(PlaylistFormat::Xspf, &link, token_secret)) { "XSPF" }
+                                    }
+                                }
+                            }
                         )
-                    ).kodi_url(Some(current_access_without_static.path)).build(&locale)
+                    ).kodi_url(Some(link.access.path)).feed_url(feed_url).breadcrumb(breadcrumb).arts(background_url, logo_url).pagination_urls(previous_page_url, next_page_url).build_response(&locale)
                 }
             }
         }
@@ -268,22 +582,17 @@ pub fn render_plugin(
                     }
                     input type=(if keyboard.hidden { "password" } else { "text" }) id="additional_input" name="additional_input" {}
                     br {}
-                    input type="hidden" name="path" value=(current_access.path) {}
-                    input type="hidden" name="input" value=(encode_input(&current_access.input)) {}
-                    @if let Some(parent) = parent_access {
-                        input type="hidden" name="parent_path" value=(parent.path) {}
-                        input type="hidden" name="input_parent" value=(encode_input(&parent.input)) {}
-                    }
+                    (render_link_inputs(&link, token_secret, ""))
                 }
                 @if let Some(default) = keyboard.default {
                     p { b { (LOCALES.lookup(&locale, "default-input")) } " : " (default) }
                 }
             )
-        ).kodi_url(Some(current_access_without_static.path)).build(&locale),
+        ).kodi_url(Some(link.access.path)).breadcrumb(breadcrumb).arts(background_url, logo_url).build_response(&locale),
         Err(err) => {
             let error_args = {
                 let mut map = HashMap::new();
-                map.insert("url".into(), current_access.path.into());
+                map.insert("url".into(), current_access.path.clone().into());
                 map
             };
 
@@ -291,7 +600,10 @@ pub fn render_plugin(
                 "{}: {:?}",
                 LOCALES.lookup_with_args(&locale, "error-getting-url", &error_args), err
             );
-            generate_error_page(html!((format!("{}", err))), &locale).kodi_url(Some(current_access_without_static.path)).build(&locale)
+            generate_error_page(render_kodi_error(&err, &locale), &locale)
+                .kodi_url(Some(link.access.path))
+                .build_response(&locale)
+                .status(get_kodi_error_status(&err))
         }
     }
 }
@@ -0,0 +1,229 @@
+use crate::auth::Identity;
+use crate::listing::{render_sort_select, render_view_select, ListingPreferences, ViewMode};
+use crate::reload::CurrentSetting;
+use crate::Presentation;
+use fluent_templates::Loader;
+use kodi_rust::{AuthMode, MergeMode, OverridableVec, SortMethod, UserConfig};
+use maud::{html, Markup};
+use rocket::form::Form;
+use rocket::http::{uri::Absolute, Cookie, CookieJar};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::Redirect;
+use std::collections::HashMap;
+use unic_langid::LanguageIdentifier;
+
+use crate::{is_local_path, locale::UI_LOCALE_KEY, UiLocale, LOCALES};
+
+/// name of the cookie that store the [`UserConfig`] chosen in the preferences page, encoded with [`UserConfig::encode_to_uri`]
+pub const USER_CONFIG_COOKIE: &str = "user_config";
+
+/// number of empty field added after the existing value of each list in the preferences form
+const EMPTY_FIELD_NB: usize = 2;
+
+/// return the [`UserConfig`] stored in the preferences cookie, or an empty one if there is no such cookie
+pub fn get_preferences_from_cookie(cookies: &CookieJar<'_>) -> UserConfig {
+    UserConfig::new_from_optional_uri(
+        cookies
+            .get(USER_CONFIG_COOKIE)
+            .map(|cookie| cookie.value().to_string()),
+    )
+}
+
+#[derive(FromForm)]
+pub struct PreferencesForm {
+    lang_ord: Vec<String>,
+    #[field(name = "m-lang_ord")]
+    m_lang_ord: Option<String>,
+    res_ord: Vec<String>,
+    #[field(name = "m-res_ord")]
+    m_res_ord: Option<String>,
+    form_ord: Vec<String>,
+    #[field(name = "m-form_ord")]
+    m_form_ord: Option<String>,
+    sort: Option<String>,
+    view: Option<String>,
+}
+
+impl PreferencesForm {
+    fn get_listing_preferences(&self) -> ListingPreferences {
+        ListingPreferences {
+            sort: self.sort.as_deref().and_then(SortMethod::from_str),
+            view: self
+                .view
+                .as_deref()
+                .and_then(ViewMode::from_str)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn into_user_config(self) -> UserConfig {
+        fn to_overridable(values: Vec<String>, mode: Option<String>) -> OverridableVec<String> {
+            OverridableVec::new_with_mode(
+                values
+                    .into_iter()
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+                    .collect(),
+                mode.and_then(|mode| MergeMode::from_str(&mode))
+                    .unwrap_or_default(),
+            )
+        }
+
+        let mut result = UserConfig::new_empty();
+        result.language_order = to_overridable(self.lang_ord, self.m_lang_ord);
+        result.resolution_order = to_overridable(self.res_ord, self.m_res_ord);
+        result.format_order = to_overridable(self.form_ord, self.m_form_ord);
+        result.clean();
+        result
+    }
+}
+
+fn render_list_editor(
+    key: &str,
+    label_id: &str,
+    list: &OverridableVec<String>,
+    default: &OverridableVec<String>,
+    locale: &LanguageIdentifier,
+) -> Markup {
+    let mode_key = format!("m-{}", key);
+    html!(
+        fieldset class="preferences_list" {
+            legend { (LOCALES.lookup(locale, label_id)) }
+            p class="preferences_default" {
+                (LOCALES.lookup(locale, "preferences-server-default")) " : " (default.join(", "))
+            }
+            label for=(mode_key) { (LOCALES.lookup(locale, "preferences-merge-mode")) " " }
+            select id=(mode_key) name=(mode_key) {
+                @for mode in &[MergeMode::Prepend, MergeMode::Append, MergeMode::Replace, MergeMode::Remove] {
+                    option value=(mode.as_str()) selected[*mode == list.mode] {
+                        (LOCALES.lookup(locale, &format!("merge-mode-{}", mode.as_str())))
+                    }
+                }
+            }
+            ol class="reorderable" {
+                @for value in list.iter().map(|v| v.as_str()).chain(std::iter::repeat_n("", EMPTY_FIELD_NB)) {
+                    li {
+                        input type="text" name=(key) value=(value) {}
+                        button type="button" class="move_up" { (LOCALES.lookup(locale, "move-up")) }
+                        button type="button" class="move_down" { (LOCALES.lookup(locale, "move-down")) }
+                    }
+                }
+            }
+        }
+    )
+}
+
+#[get("/preferences")]
+pub fn render_preferences(
+    setting: &CurrentSetting,
+    identity: &Identity,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+) -> Markup {
+    let locale = ui_locale.0;
+    let mut args = HashMap::new();
+    if let Some(user) = &identity.0 {
+        args.insert("user".into(), user.clone().into());
+    };
+    let preferences = get_preferences_from_cookie(cookies);
+    let listing = ListingPreferences::from_cookies(cookies);
+    let default = &setting.default_user_config;
+
+    Presentation::new(
+        html!((LOCALES.lookup(&locale, "preferences-title"))),
+        html!(
+            @if identity.0.is_some() {
+                p {
+                    (LOCALES.lookup_with_args(&locale, "login-logged-in-as", &args))
+                    @if setting.auth_mode == AuthMode::Password {
+                        " "
+                        form method="post" action="/logout" class="inline_form" {
+                            input type="submit" value=(LOCALES.lookup(&locale, "login-logout")) {}
+                        }
+                    }
+                }
+            }
+            p { (LOCALES.lookup(&locale, "preferences-explanation")) }
+            form method="post" action="/preferences" {
+                (render_list_editor("lang_ord", "preferences-language-order", &preferences.language_order, &default.language_order, &locale))
+                (render_list_editor("res_ord", "preferences-resolution-order", &preferences.resolution_order, &default.resolution_order, &locale))
+                (render_list_editor("form_ord", "preferences-format-order", &preferences.format_order, &default.format_order, &locale))
+                fieldset class="preferences_list" {
+                    legend { (LOCALES.lookup(&locale, "preferences-listing")) }
+                    (render_sort_select(&SortMethod::ALL, listing.sort, &locale))
+                    " "
+                    (render_view_select(listing.view, &locale))
+                }
+                input type="submit" value=(LOCALES.lookup(&locale, "preferences-save")) {}
+            }
+            form method="post" action="/preferences/reset" {
+                input type="submit" value=(LOCALES.lookup(&locale, "preferences-reset")) {}
+            }
+            script type="text/javascript" src="/static/preferences.js" {}
+        ),
+    )
+    .build(&locale)
+}
+
+#[post("/preferences", data = "<form>")]
+pub fn save_preferences(cookies: &CookieJar<'_>, form: Form<PreferencesForm>) -> Redirect {
+    let form = form.into_inner();
+    form.get_listing_preferences().save(cookies);
+    let user_config = form.into_user_config();
+    if user_config.is_empty() {
+        cookies.remove(Cookie::from(USER_CONFIG_COOKIE));
+    } else {
+        cookies.add(
+            Cookie::build((USER_CONFIG_COOKIE, user_config.encode_to_uri()))
+                .path("/")
+                .permanent(),
+        );
+    };
+    Redirect::to(uri!(render_preferences))
+}
+
+#[post("/preferences/reset")]
+pub fn reset_preferences(cookies: &CookieJar<'_>) -> Redirect {
+    cookies.remove(Cookie::from(USER_CONFIG_COOKIE));
+    ListingPreferences::reset(cookies);
+    Redirect::to(uri!(render_preferences))
+}
+
+/// the path and query of the page that linked to this one, as found in the ``Referer`` header
+pub struct RefererPath(Option<String>);
+
+impl RefererPath {
+    /// redirect to the page that linked to this one, or to the index if it is unknown
+    pub fn redirect(self) -> Redirect {
+        match self.0 {
+            Some(path) if is_local_path(&path) => Redirect::to(path),
+            _ => Redirect::to(uri!(crate::index_page::render_index)),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RefererPath {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let path = request
+            .headers()
+            .get_one("Referer")
+            .and_then(|referer| Absolute::parse(referer).ok())
+            .map(|referer| match referer.query() {
+                Some(query) => format!("{}?{}", referer.path(), query),
+                None => referer.path().to_string(),
+            });
+        Outcome::Success(RefererPath(path))
+    }
+}
+
+/// set the locale of the interface to ``lang`` (if valid), then redirect to the previous page
+#[get("/set_ui_locale?<lang>")]
+pub fn set_ui_locale(cookies: &CookieJar<'_>, lang: String, referer: RefererPath) -> Redirect {
+    if lang.parse::<LanguageIdentifier>().is_ok() {
+        cookies.add(Cookie::build((UI_LOCALE_KEY, lang)).path("/").permanent());
+    };
+    referer.redirect()
+}
@@ -1,5 +1,9 @@
 use fluent_templates::Loader;
+use kodi_rust::urlencode;
 use maud::{html, Markup, DOCTYPE};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
 use unic_langid::LanguageIdentifier;
 
 use crate::LOCALES;
@@ -8,6 +12,43 @@ pub struct Presentation {
     pub title: Markup,
     pub content: Markup,
     pub kodi_url: Option<String>,
+    pub feed_url: Option<String>,
+    /// the label and link of the pages above this one, from the root
+    pub breadcrumb: Vec<(Markup, String)>,
+    /// the url of the previous page, for paginated content
+    pub previous_url: Option<String>,
+    /// the url of the next page, for paginated content
+    pub next_url: Option<String>,
+    /// the url of the image displayed behind the page
+    pub background_url: Option<String>,
+    /// the url of the logo displayed above the title
+    pub logo_url: Option<String>,
+}
+
+/// A built [`Presentation`], that also send the url of the next page in the ``Link`` header
+pub struct PresentationResponse {
+    pub content: Markup,
+    pub next_url: Option<String>,
+    pub status: Status,
+}
+
+impl PresentationResponse {
+    /// set the HTTP status of the response (``200 OK`` by default)
+    pub fn status(mut self, status: Status) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl<'r> Responder<'r, 'static> for PresentationResponse {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = self.content.respond_to(request)?;
+        response.set_status(self.status);
+        if let Some(next_url) = self.next_url {
+            response.set_raw_header("Link", format!("<{}>; rel=\"next\"", next_url));
+        };
+        Ok(response)
+    }
 }
 
 impl Presentation {
@@ -16,6 +57,12 @@ impl Presentation {
             title,
             content,
             kodi_url: None,
+            feed_url: None,
+            breadcrumb: Vec::new(),
+            previous_url: None,
+            next_url: None,
+            background_url: None,
+            logo_url: None,
         }
     }
 
@@ -24,6 +71,49 @@ impl Presentation {
         self
     }
 
+    /// set the url of the RSS feed of this page, advertised to the browser and to feed readers
+    pub fn feed_url(mut self, url: Option<String>) -> Presentation {
+        self.feed_url = url;
+        self
+    }
+
+    /// set the pages above this one, shown as a breadcrumb above the title
+    pub fn breadcrumb(mut self, breadcrumb: Vec<(Markup, String)>) -> Presentation {
+        self.breadcrumb = breadcrumb;
+        self
+    }
+
+    /// set the urls of the previous and next pages of paginated content
+    pub fn pagination_urls(
+        mut self,
+        previous_url: Option<String>,
+        next_url: Option<String>,
+    ) -> Presentation {
+        self.previous_url = previous_url;
+        self.next_url = next_url;
+        self
+    }
+
+    /// set the urls of the background image and of the logo of the page
+    pub fn arts(
+        mut self,
+        background_url: Option<String>,
+        logo_url: Option<String>,
+    ) -> Presentation {
+        self.background_url = background_url;
+        self.logo_url = logo_url;
+        self
+    }
+
+    /// build the page, keeping the url of the next page for the ``Link`` header
+    pub fn build_response(self, locale: &LanguageIdentifier) -> PresentationResponse {
+        PresentationResponse {
+            next_url: self.next_url.clone(),
+            content: self.build(locale),
+            status: Status::Ok,
+        }
+    }
+
     pub fn build(self, locale: &LanguageIdentifier) -> Markup {
         html!(
             (DOCTYPE)
@@ -31,13 +121,51 @@ impl Presentation {
                 meta charset = "utf-8" {}
                 title { (self.title.clone()) }
                 link rel="stylesheet" href="/static/kodionline.css" {}
+                @if let Some(feed_url) = &self.feed_url {
+                    link rel="alternate" type="application/rss+xml" title=(self.title.0) href=(feed_url) {}
+                }
+                @if let Some(previous_url) = &self.previous_url {
+                    link rel="prev" href=(previous_url) {}
+                }
+                @if let Some(next_url) = &self.next_url {
+                    link rel="next" href=(next_url) {}
+                }
             }
-            body {
+            body class=[self.background_url.as_ref().map(|_| "with_background")] style=[self.background_url.as_ref().map(|url| format!("background-image: url(\"{}\")", url))] {
                 div id="header" {
                     ul class="horizontallist" {
                         li {
                             a href="/" {(LOCALES.lookup(locale, "main-page"))}
                         }
+                        li {
+                            a href="/favourites" {(LOCALES.lookup(locale, "favourites"))}
+                        }
+                        li {
+                            a href="/preferences" {(LOCALES.lookup(locale, "preferences"))}
+                        }
+                    }
+                    ul class="horizontallist locale_switcher" {
+                        @for available_locale in LOCALES.available_locales() {
+                            li {
+                                @if &available_locale == locale {
+                                    (LOCALES.lookup(&available_locale, "language-name"))
+                                } @else {
+                                    a href=(format!("/set_ui_locale?lang={}", urlencode(&available_locale.to_string()))) lang=(available_locale.to_string()) {
+                                        (LOCALES.lookup(&available_locale, "language-name"))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    @if !self.breadcrumb.is_empty() {
+                        ul class="horizontallist breadcrumb" {
+                            @for (label, link) in &self.breadcrumb {
+                                li { a href=(link) { (label) } }
+                            }
+                        }
+                    }
+                    @if let Some(logo_url) = &self.logo_url {
+                        img class="clearlogo" src=(logo_url) alt="" {}
                     }
                     h1 { (self.title) }
                 }
@@ -0,0 +1,283 @@
+use crate::{error_page::generate_error_page, get_ui_locale, reload::SettingHandle, LOCALES};
+use fluent_templates::Loader;
+use kodi_rust::{get_addon_id, CallAdmission, InvocationReport, KodiError};
+use log::warn;
+use maud::html;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome},
+    tokio::{
+        runtime::{Handle, RuntimeFlavor},
+        task::block_in_place,
+    },
+    Data, Request, Response,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Cursor;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Count the calls to plugins whose result wasn't cached made by each IP address, over a sliding window
+pub struct RateLimiter {
+    max_calls: usize,
+    window: Duration,
+    /// the time of the calls of each IP address still in the window, the oldest first
+    clients: Mutex<HashMap<IpAddr, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// create a new [`RateLimiter`] allowing ``max_calls`` calls per ``window`` to each IP address
+    pub fn new(max_calls: usize, window: Duration) -> Self {
+        Self {
+            max_calls,
+            window,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// record a call from ``ip``, or return the time after which it can call again if it reached the limit
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use kodionline::rate_limit::RateLimiter;
+    /// use std::time::Duration;
+    ///
+    /// let limiter = RateLimiter::new(2, Duration::from_secs(60));
+    /// let (first, second) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+    /// assert!(limiter.try_call(first).is_ok());
+    /// assert!(limiter.try_call(first).is_ok());
+    /// assert!(limiter.try_call(first).is_err());
+    /// assert!(limiter.try_call(second).is_ok());
+    /// ```
+    pub fn try_call(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.count_call(ip, true)
+    }
+
+    /// return the time after which ``ip`` can call again if it reached the limit, without recording a call
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.count_call(ip, false)
+    }
+
+    fn count_call(&self, ip: IpAddr, record: bool) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        // forget the clients that made no call in the window, so the map doesn't grow forever
+        clients.retain(|_, calls| {
+            while matches!(calls.front(), Some(call) if now.duration_since(*call) >= self.window) {
+                calls.pop_front();
+            }
+            !calls.is_empty()
+        });
+        let calls = clients.entry(ip).or_default();
+        if calls.len() >= self.max_calls {
+            return Err(match calls.front() {
+                Some(oldest) => self.window.saturating_sub(now.duration_since(*oldest)),
+                None => self.window,
+            });
+        };
+        if record {
+            calls.push_back(now);
+        };
+        Ok(())
+    }
+}
+
+/// return the IP address of the client that made ``request``. The ``X-Real-IP`` header (or the ``ip_header`` of Rocket's configuration) is only used if the request come from one of [`kodi_rust::Setting::trusted_proxies`], as anyone could send it otherwise.
+pub fn get_client_ip(request: &Request<'_>) -> Option<IpAddr> {
+    let remote = request.remote()?.ip();
+    let trusted = request
+        .rocket()
+        .state::<SettingHandle>()
+        .is_some_and(|setting| setting.get().trusted_proxies.contains(&remote));
+    if trusted {
+        request.client_ip()
+    } else {
+        Some(remote)
+    }
+}
+
+/// run ``call``, that block the thread (while a plugin run, or while a password is hashed), without stalling the other requests handled by the same thread of the async runtime
+pub fn run_blocking<R>(call: impl FnOnce() -> R) -> R {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => block_in_place(call),
+        _ => call(),
+    }
+}
+
+/// The client that made a request, as a request guard. It is the [`CallAdmission`] to use for the main call to a plugin made on its behalf, and [`Client::secondary`] is the one to use for the other calls.
+///
+/// Only the first call whose result isn't cached is counted by the rate limit, the following calls of the same request are admitted with it. If the main call was refused, the [`RateLimit`] fairing replace the response with a page asking to retry later.
+pub struct Client {
+    pub ip: Option<IpAddr>,
+    limiter: Option<Arc<RateLimiter>>,
+    /// whether a call was already admitted for this request
+    admitted: AtomicBool,
+    /// the time after which the refused call can be retried, if one was refused
+    retry_after: Mutex<Option<Duration>>,
+    /// if set, the only addons this client can call, in addition to the [`kodi_rust::AddonPolicy`] of every client
+    allowed_addons: Mutex<Option<HashSet<String>>>,
+}
+
+impl Client {
+    fn new(request: &Request<'_>, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Client {
+            ip: get_client_ip(request),
+            limiter,
+            admitted: AtomicBool::new(false),
+            retry_after: Mutex::new(None),
+            allowed_addons: Mutex::new(None),
+        }
+    }
+
+    /// return the [`Client`] attached to ``request``. It is only rate limited if the [`RateLimit`] fairing handled the request before.
+    pub fn get<'r>(request: &'r Request<'_>) -> &'r Self {
+        request.local_cache(|| Client::new(request, None))
+    }
+
+    /// return the [`CallAdmission`] to use for the calls that aren't the main one of the request, like the lookup of the parent folder. A refusal of these calls doesn't make the whole page fail.
+    pub fn secondary(&self) -> SecondaryCall<'_> {
+        SecondaryCall(self)
+    }
+
+    /// only allow this client to call the plugins of ``addons``
+    pub fn restrict_addons(&self, addons: HashSet<String>) {
+        *self
+            .allowed_addons
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(addons);
+    }
+
+    /// return true if this client can call the plugin at ``path``, as far as the restrictions set by [`Client::restrict_addons`] are concerned
+    pub fn is_addon_allowed(&self, path: &str) -> bool {
+        match &*self
+            .allowed_addons
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            Some(allowed) => get_addon_id(path).is_some_and(|addon_id| allowed.contains(addon_id)),
+            None => true,
+        }
+    }
+
+    /// return the time after which a refused call can be retried, if a call was refused
+    pub fn retry_after(&self) -> Option<Duration> {
+        *self
+            .retry_after
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CallAdmission for Client {
+    fn admit(&self) -> Result<(), KodiError> {
+        if self.admitted.load(Ordering::Relaxed) {
+            return Ok(());
+        };
+        if let (Some(limiter), Some(ip)) = (&self.limiter, self.ip) {
+            limiter.try_call(ip).map_err(KodiError::RateLimited)?;
+        };
+        self.admitted.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn check_addon(&self, path: &str) -> Result<(), KodiError> {
+        if self.is_addon_allowed(path) {
+            Ok(())
+        } else {
+            Err(KodiError::AddonNotAllowed(
+                get_addon_id(path).unwrap_or(path).to_string(),
+            ))
+        }
+    }
+
+    fn refused(&self, err: &KodiError) {
+        let mut retry_after = self
+            .retry_after
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *retry_after = retry_after.max(err.retry_after());
+    }
+
+    fn run_blocking(&self, run: &mut dyn FnMut() -> InvocationReport) -> InvocationReport {
+        run_blocking(run)
+    }
+}
+
+/// The [`CallAdmission`] of a [`Client`] for the calls that aren't the main one of a request. See [`Client::secondary`].
+pub struct SecondaryCall<'a>(&'a Client);
+
+impl CallAdmission for SecondaryCall<'_> {
+    fn admit(&self) -> Result<(), KodiError> {
+        self.0.admit()
+    }
+
+    fn check_addon(&self, path: &str) -> Result<(), KodiError> {
+        self.0.check_addon(path)
+    }
+
+    fn run_blocking(&self, run: &mut dyn FnMut() -> InvocationReport) -> InvocationReport {
+        self.0.run_blocking(run)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r Client {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Client::get(request))
+    }
+}
+
+/// Fairing rate limiting the calls to plugins whose result isn't cached made by each IP address (if ``limiter`` is set), and sending a page with the status ``503 Service Unavailable`` when a call is refused, either by the rate limit or because too many plugins are running.
+pub struct RateLimit {
+    pub limiter: Option<Arc<RateLimiter>>,
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimit {
+    fn info(&self) -> Info {
+        Info {
+            name: "rate limit",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _data: &mut Data<'_>) {
+        let client = Client::new(request, self.limiter.clone());
+        request.local_cache(|| client);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let retry_after = match Client::get(request).retry_after() {
+            Some(value) => value,
+            None => return,
+        };
+        warn!(
+            "refused to call a plugin for {:?}, retry after {:?}",
+            Client::get(request).ip,
+            retry_after
+        );
+        response.set_status(Status::ServiceUnavailable);
+        // rounded up, and at least one second so the client doesn't retry immediately
+        response.set_header(Header::new(
+            "Retry-After",
+            (retry_after.as_secs_f64().ceil() as u64).max(1).to_string(),
+        ));
+        // the API keep its JSON error
+        if response.content_type() == Some(ContentType::JSON) {
+            return;
+        };
+        let locale = get_ui_locale(request);
+        let page = generate_error_page(html!((LOCALES.lookup(&locale, "error-busy"))), &locale)
+            .build(&locale)
+            .into_string();
+        response.set_header(ContentType::HTML);
+        response.remove_header("Link");
+        response.set_sized_body(page.len(), Cursor::new(page));
+    }
+}
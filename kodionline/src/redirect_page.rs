@@ -1,19 +1,33 @@
 use std::fs::File;
+use std::path::{Path, PathBuf};
 
 use kodi_rust::{
     data::{KodiResult, ListItem},
-    encode_utf8_url, get_sub_content_from_parent, should_serve_file, Kodi, PathAccessData, Setting,
-    UserConfig,
+    encode_utf8_url, get_sub_content_from_parent, should_serve_file, CallAdmission, Kodi,
+    PathAccessData,
 };
 
 use log::{error, info};
 use rocket::{
-    response, response::Redirect, response::Responder, Request, State,
+    http::{CookieJar, Status},
+    response,
+    response::Redirect,
+    response::Responder,
+    Request, State,
+};
+
+use crate::{
+    get_final_user_config,
+    rate_limit::{run_blocking, Client},
+    reload::CurrentSetting,
+    thumbnail::{get_thumbnail, snap_width, Thumbnail},
+    AcceptLanguage, LinkParams,
 };
 
 pub enum ServeDataFromPlugin {
     Redirect(Redirect),
     NamedFile(File),
+    Thumbnail(Thumbnail),
 }
 
 impl<'r> Responder<'r, 'r> for ServeDataFromPlugin {
@@ -21,12 +35,14 @@ impl<'r> Responder<'r, 'r> for ServeDataFromPlugin {
         match self {
             Self::Redirect(r) => r.respond_to(request),
             Self::NamedFile(f) => f.respond_to(request),
+            Self::Thumbnail(t) => t.respond_to(request),
         }
     }
 }
 
 pub fn redirect_data_generic<F>(
     kodi: &State<Kodi>,
+    client: &Client,
     access: PathAccessData,
     parent_access_option: Option<PathAccessData>,
     category_label: &str,
@@ -35,47 +51,66 @@ pub fn redirect_data_generic<F>(
 where
     F: Fn(&ListItem) -> Option<String>,
 {
-    let create_result_for_url = |data_url: String| -> Option<ServeDataFromPlugin> {
-        println!("found {:?}", data_url);
-        if should_serve_file(&data_url) {
-            //TODO: check if the file is permitted to be read
-            Some(ServeDataFromPlugin::NamedFile(
-                match File::open(data_url) {
-                    Ok(v) => v,
-                    Err(err) => {
-                        error!("failed to open the local file due to {:?}", err);
-                        return None;
-                    }
+    let data_url = find_data_url(
+        kodi,
+        client,
+        &access,
+        parent_access_option,
+        category_label,
+        get_path_function,
+    )?;
+    println!("found {:?}", data_url);
+    if should_serve_file(&data_url) {
+        //TODO: check if the file is permitted to be read
+        Some(ServeDataFromPlugin::NamedFile(
+            match File::open(data_url) {
+                Ok(v) => v,
+                Err(err) => {
+                    error!("failed to open the local file due to {:?}", err);
+                    return None;
                 }
-            ))
-        } else {
-            let encoded = encode_utf8_url(&data_url);
-            info!(
-                "redirecting the {} at {:?} to \"{}\"",
-                category_label, access, encoded
-            );
-            Some(ServeDataFromPlugin::Redirect(Redirect::to(encoded)))
-        }
-    };
+            }
+        ))
+    } else {
+        let encoded = encode_utf8_url(&data_url);
+        info!(
+            "redirecting the {} at {:?} to \"{}\"",
+            category_label, access, encoded
+        );
+        Some(ServeDataFromPlugin::Redirect(Redirect::to(encoded)))
+    }
+}
 
+/// return the url of a data (a media or an art) of the page at ``access``, found with ``get_path_function``
+pub fn find_data_url<F>(
+    kodi: &State<Kodi>,
+    client: &Client,
+    access: &PathAccessData,
+    parent_access_option: Option<PathAccessData>,
+    category_label: &str,
+    get_path_function: F,
+) -> Option<String>
+where
+    F: Fn(&ListItem) -> Option<String>,
+{
     // try the parent first, as it probably already in the cache
     if let Some(parent_access) = parent_access_option {
         if let Some(sub_content_from_parent) =
-            get_sub_content_from_parent(&kodi, &parent_access, &access.path)
+            get_sub_content_from_parent(kodi, &client.secondary(), &parent_access, &access.path)
         {
             if let Some(data_url) = get_path_function(&sub_content_from_parent.listitem) {
                 if !data_url.starts_with("plugin://") {
-                    return create_result_for_url(data_url);
+                    return Some(data_url);
                 }
             }
         }
     };
 
     // otherwise, try to get it from the child
-    match kodi.invoke_sandbox(&access) {
+    match kodi.invoke_sandbox_for(access, client) {
         Ok(KodiResult::Content(page)) => match page.resolved_listitem {
             Some(resolved_listitem) => match get_path_function(&resolved_listitem) {
-                Some(media_url) => create_result_for_url(media_url),
+                Some(media_url) => Some(media_url),
                 None => {
                     error!(
                         "can't find the searched {} for {:?}",
@@ -106,69 +141,142 @@ where
     }
 }
 
-#[get("/get_media?<path>&<input>&<parent_path>&<parent_input>&<c>")]
+#[get("/get_media?<link..>")]
 pub fn redirect_media(
     kodi: &State<Kodi>,
-    setting: &State<Setting>,
-    path: String,
-    input: Option<&str>,
-    parent_path: Option<String>,
-    parent_input: Option<&str>,
-    c: Option<String>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
+    link: LinkParams,
 ) -> Option<ServeDataFromPlugin> {
-    let config_in_url = UserConfig::new_from_optional_uri(c);
-    let final_config = setting
-        .default_user_config
-        .clone()
-        .add_config_prioritary(config_in_url);
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("invalid link to a media: {}", err);
+            return None;
+        }
+    };
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+    let link_state = link_state.with_config(final_config);
 
     redirect_data_generic(
         kodi,
-        PathAccessData::new(path, input, final_config.clone()),
-        PathAccessData::try_create_from_url(
-            parent_path,
-            parent_input,
-            final_config,
-        ),
+        client,
+        link_state.access,
+        link_state.parent,
         "media",
         |x| x.path.clone(),
     )
 }
 
-#[get("/get_art?<category>&<path>&<input>&<parent_path>&<parent_input>&<c>")]
+/// serve the ``icon`` or the ``fanart`` of the addon of the plugin ``path`` (see [`Kodi::get_addon_art`]), if the addon can be called by the client
+#[get("/get_addon_art?<path>&<category>")]
+pub fn redirect_addon_art(
+    kodi: &State<Kodi>,
+    client: &Client,
+    path: String,
+    category: String,
+) -> Result<ServeDataFromPlugin, Status> {
+    if let Err(err) = kodi
+        .check_addon(&path)
+        .and_then(|()| client.check_addon(&path))
+    {
+        info!("refused to serve the addon art of {}: {}", path, err);
+        return Err(Status::Forbidden);
+    };
+    let art_path = match kodi.get_addon_art(&path).get(&category) {
+        Some(art_path) => art_path.to_string(),
+        None => return Err(Status::NotFound),
+    };
+    match File::open(&art_path) {
+        Ok(file) => Ok(ServeDataFromPlugin::NamedFile(file)),
+        Err(err) => {
+            error!("failed to open the addon art {} due to {:?}", art_path, err);
+            Err(Status::NotFound)
+        }
+    }
+}
+
+/// serve an art. If the thumbnail proxy is enabled for the plugin (see [`kodi_rust::Setting::get_thumbnail_cache_directory`]), the art is downloaded by the server and resized to ``width`` (rounded with [`snap_width`]), otherwise the browser is redirected to it.
+#[get("/get_art?<category>&<width>&<link..>")]
 #[allow(clippy::too_many_arguments)]
 pub fn redirect_art(
     kodi: &State<Kodi>,
-    setting: &State<Setting>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    accept_language: AcceptLanguage,
     category: String,
-    path: String,
-    input: Option<&str>,
-    parent_path: Option<String>,
-    parent_input: Option<&str>,
-    c: Option<String>,
+    width: Option<u32>,
+    link: LinkParams,
 ) -> Option<ServeDataFromPlugin> {
-    let config_in_url = UserConfig::new_from_optional_uri(c);
-    let final_config = setting
-        .default_user_config
-        .clone()
-        .add_config_prioritary(config_in_url);
+    let link_state = match link.into_link_state(setting) {
+        Ok(value) => value,
+        Err(err) => {
+            error!("invalid link to a art: {}", err);
+            return None;
+        }
+    };
+    let final_config = get_final_user_config(
+        setting,
+        cookies,
+        &accept_language,
+        &link_state.access.path,
+        link_state.access.config.clone(),
+    );
+    let link_state = link_state.with_config(final_config);
 
-    redirect_data_generic(
-        kodi,
-        PathAccessData::new(path, input, final_config.clone()),
-        PathAccessData::try_create_from_url(
-            parent_path,
-            parent_input,
-            final_config,
+    let get_art_function = |x: &ListItem| x.arts.get(&category).cloned().flatten();
+
+    match setting.get_thumbnail_cache_directory(&link_state.access.path) {
+        Some(cache_directory) => {
+            let art_url = find_data_url(
+                kodi,
+                client,
+                &link_state.access,
+                link_state.parent,
+                "art",
+                get_art_function,
+            )?;
+            let width = width.map(|width| snap_width(width, setting.thumbnail_width));
+            // the arts of the addons and the files the plugins can read
+            let local_roots: Vec<PathBuf> = std::iter::once(kodi.get_kodi_config_path())
+                .chain(setting.allowed_path.iter().map(String::as_str))
+                .map(PathBuf::from)
+                .collect();
+            match run_blocking(|| {
+                get_thumbnail(
+                    Path::new(cache_directory),
+                    setting.thumbnail_cache_size * 1024 * 1024,
+                    &art_url,
+                    width,
+                    &local_roots,
+                )
+            }) {
+                Ok(thumbnail) => Some(ServeDataFromPlugin::Thumbnail(thumbnail)),
+                Err(err) => {
+                    error!(
+                        "can't create the thumbnail of {}: {} ({:?})",
+                        art_url, err, err
+                    );
+                    None
+                }
+            }
+        }
+        None => redirect_data_generic(
+            kodi,
+            client,
+            link_state.access,
+            link_state.parent,
+            "art",
+            get_art_function,
         ),
-        "art",
-        |x| match &x.arts.get(&category) {
-            //TODO: this line is anormaly long. Find how to shorten it
-            Some(art_url_option) => match *art_url_option {
-                Some(value) => Some(value.clone()),
-                None => None,
-            },
-            None => None,
-        },
-    )
+    }
 }
@@ -0,0 +1,239 @@
+use kodi_rust::{check_setting, KodiParameters, KodiReloader, Setting, SettingLoader};
+use log::{error, info, warn};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Status,
+    request::{FromRequest, Outcome},
+    tokio::{
+        self,
+        signal::unix::{signal, SignalKind},
+        time::interval,
+    },
+    Orbit, Request, Rocket,
+};
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// the settings that are only read when the server start. They are ignored when the setting is reloaded
+const RESTART_SETTINGS: &[&str] = &[
+    "kodi_path",
+    "locale_directories",
+    "database_path",
+    "admin_password",
+    "invocation_history_size",
+    "keep_plugin_output",
+    "rate_limit_calls",
+    "rate_limit_window",
+    "max_running_plugins",
+    "max_waiting_plugins",
+    "plugin_wait_timeout",
+    "listen_address",
+    "port",
+];
+
+/// how often the setting file is checked for modification
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The current [`Setting`] of the server, that can be replaced while it runs. It is managed by Rocket, and read by the handlers with the [`CurrentSetting`] request guard.
+#[derive(Clone)]
+pub struct SettingHandle(Arc<RwLock<Arc<Setting>>>);
+
+impl SettingHandle {
+    pub fn new(setting: Setting) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(setting))))
+    }
+
+    /// return the current setting
+    pub fn get(&self) -> Arc<Setting> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// replace the setting. The requests being handled keep using the previous one.
+    pub fn replace(&self, setting: Setting) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(setting);
+    }
+}
+
+/// The [`Setting`] used for a request, as a request guard. The same setting is used for the whole request, even if it is reloaded meanwhile.
+pub struct CurrentSetting(Arc<Setting>);
+
+impl Deref for CurrentSetting {
+    type Target = Setting;
+
+    fn deref(&self) -> &Setting {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r CurrentSetting {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let handle = match request.rocket().state::<SettingHandle>() {
+            Some(handle) => handle,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        Outcome::Success(request.local_cache(|| CurrentSetting(handle.get())))
+    }
+}
+
+/// Fairing reloading the setting (and its ``users_file``) when its file is modified, or when the server receive ``SIGHUP``. The new setting replace the one of ``setting``, and the parameters of the [`kodi_rust::Kodi`] of ``kodi`` are updated, keeping its cache. An invalid setting is logged and ignored.
+#[derive(Clone)]
+pub struct SettingReload {
+    pub loader: SettingLoader,
+    pub setting: SettingHandle,
+    pub kodi: KodiReloader,
+}
+
+/// return ``new_setting`` with the values of [`RESTART_SETTINGS`] of ``old_setting``, warning about each one that changed
+fn keep_restart_settings(old_setting: &Setting, new_setting: Setting) -> Setting {
+    let (old, mut new) = match (
+        serde_json::to_value(old_setting),
+        serde_json::to_value(&new_setting),
+    ) {
+        (Ok(old), Ok(Value::Object(new))) => (old, new),
+        _ => return new_setting,
+    };
+    for key in RESTART_SETTINGS {
+        if let Some(old_value) = old.get(key) {
+            if new.get(*key) != Some(old_value) {
+                warn!("{} changed, but it is only applied after a restart", key);
+                new.insert(key.to_string(), old_value.clone());
+            };
+        };
+    }
+    match serde_json::from_value::<Setting>(Value::Object(new)) {
+        // the users aren't serialized, as they are read from ``users_file``
+        Ok(setting) => Setting {
+            users: new_setting.users,
+            ..setting
+        },
+        Err(_) => new_setting,
+    }
+}
+
+/// return the time the file at ``path`` was last modified, if it has one
+fn get_modification_time(path: Option<&Path>) -> Option<SystemTime> {
+    fs::metadata(path?).ok()?.modified().ok()
+}
+
+impl SettingReload {
+    /// load the setting again, and apply it if it is valid. Return ``true`` if it was applied.
+    pub fn reload(&self) -> bool {
+        let new_setting = match self.loader.load() {
+            Ok(value) => value,
+            Err(err) => {
+                match err.source() {
+                    Some(source) => error!("the setting isn't reloaded: {}: {}", err, source),
+                    None => error!("the setting isn't reloaded: {}", err),
+                };
+                return false;
+            }
+        };
+        let problems = check_setting(&new_setting);
+        if !problems.errors.is_empty() {
+            for error in &problems.errors {
+                error!("the setting isn't reloaded: {}", error);
+            }
+            return false;
+        };
+        for warning in &problems.warnings {
+            warn!("{}", warning);
+        }
+
+        let new_setting = keep_restart_settings(&self.setting.get(), new_setting);
+
+        let evicted = self
+            .kodi
+            .reconfigure(KodiParameters::from_setting(&new_setting));
+        self.setting.replace(new_setting);
+        info!(
+            "the setting was reloaded ({} cached results were removed)",
+            evicted
+        );
+        true
+    }
+
+    /// return the time the setting file and the ``users_file`` of the current setting were last modified
+    fn get_modification_times(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        (
+            get_modification_time(self.loader.file.as_deref()),
+            get_modification_time(self.setting.get().users_file.as_deref().map(Path::new)),
+        )
+    }
+
+    /// wait for ``SIGHUP`` or a modification of the setting file (or of its ``users_file``), and reload the setting each time
+    async fn watch(self) {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                error!("can't listen to SIGHUP: {}", err);
+                None
+            }
+        };
+        let mut ticks = interval(WATCH_INTERVAL);
+        let mut last_modified = self.get_modification_times();
+        loop {
+            let hangup_received = async {
+                match &mut hangup {
+                    Some(hangup) => hangup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = hangup_received => {
+                    info!("SIGHUP received, reloading the setting");
+                    last_modified = self.get_modification_times();
+                    self.reload();
+                }
+                _ = ticks.tick() => {
+                    let modified = self.get_modification_times();
+                    if modified != last_modified {
+                        info!("the setting file was modified, reloading it");
+                        last_modified = modified;
+                        self.reload();
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for SettingReload {
+    fn info(&self) -> Info {
+        Info {
+            name: "setting reload",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, _rocket: &Rocket<Orbit>) {
+        tokio::spawn(self.clone().watch());
+    }
+}
+
+#[test]
+fn test_keep_restart_settings() {
+    let old_setting = Setting {
+        admin_password: Some("old".into()),
+        ..Setting::default()
+    };
+    let new_setting = Setting {
+        admin_password: Some("new".into()),
+        cache_time: 60,
+        ..Setting::default()
+    };
+    let setting = keep_restart_settings(&old_setting, new_setting);
+    assert_eq!(setting.admin_password.as_deref(), Some("old"));
+    assert_eq!(setting.cache_time, 60);
+}
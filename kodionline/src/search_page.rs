@@ -0,0 +1,180 @@
+use crate::{
+    get_final_user_config,
+    plugin_page::render_sub_content_link,
+    rate_limit::{run_blocking, Client},
+    reload::CurrentSetting,
+    AcceptLanguage, Presentation,
+};
+use fluent_templates::Loader;
+use kodi_rust::{
+    data::{KodiResult, SubContent},
+    Kodi, LinkState, PathAccessData, Setting, UserConfig,
+};
+use log::error;
+use maud::{html, Markup};
+use rocket::http::CookieJar;
+use rocket::State;
+use std::thread;
+use unic_langid::LanguageIdentifier;
+
+use crate::{UiLocale, LOCALES};
+
+/// The result of a search in one plugin
+pub struct SearchResult {
+    /// the label of the search entry point
+    pub label: String,
+    /// the access to the search result, with ``query`` as input
+    pub access: PathAccessData,
+    /// the found content, or the key of the error message
+    pub content: Result<Vec<SubContent>, &'static str>,
+}
+
+fn search_in_entry_point(
+    kodi: &Kodi,
+    client: &Client,
+    label: &str,
+    access: PathAccessData,
+) -> SearchResult {
+    let content = match kodi.invoke_sandbox_for(&access, client) {
+        Ok(KodiResult::Content(page)) => Ok(page.sub_content),
+        Ok(KodiResult::Keyboard(_)) => Err("error-search-more-input"),
+        Err(err) => {
+            error!("error while searching at {:?}: {:?}", access, err);
+            Err("error-search-failed")
+        }
+    };
+    SearchResult {
+        label: label.to_string(),
+        access,
+        content,
+    }
+}
+
+/// search ``query`` in every search entry point of the setting, giving it as the input of the path, with the config returned by ``get_config`` for the path. Every plugin is searched in parallel.
+pub fn search_all(
+    kodi: &Kodi,
+    client: &Client,
+    setting: &Setting,
+    query: &str,
+    get_config: &dyn Fn(&str) -> UserConfig,
+) -> Vec<SearchResult> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = setting
+            .search_entry_points
+            .iter()
+            .map(|(label, path)| {
+                let access = PathAccessData {
+                    path: path.clone(),
+                    input: vec![query.to_string()],
+                    config: get_config(path),
+                };
+                scope.spawn(move || search_in_entry_point(kodi, client, label, access))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .zip(setting.search_entry_points.iter())
+            .map(|(handle, (label, path))| {
+                handle.join().unwrap_or_else(|_| {
+                    error!("the search in {} panicked", path);
+                    SearchResult {
+                        label: label.clone(),
+                        access: PathAccessData::new(path.clone(), None, get_config(path)),
+                        content: Err("error-search-failed"),
+                    }
+                })
+            })
+            .collect()
+    })
+}
+
+/// render the search form, used on the search page and on the index page
+pub fn render_search_form(locale: &LanguageIdentifier, query: Option<&str>) -> Markup {
+    html!(
+        form method="get" action="/search" class="search" {
+            label for="search_query" { (LOCALES.lookup(locale, "search-in-plugins")) }
+            input type="search" id="search_query" name="q" value=(query.unwrap_or_default()) {}
+            input type="submit" value=(LOCALES.lookup(locale, "search")) {}
+        }
+    )
+}
+
+/// search in all the search entry points of the setting, and display the merged results, grouped by plugin
+#[allow(clippy::too_many_arguments)]
+#[get("/search?<q>&<c>")]
+pub fn render_search(
+    kodi: &State<Kodi>,
+    client: &Client,
+    setting: &CurrentSetting,
+    cookies: &CookieJar<'_>,
+    ui_locale: UiLocale,
+    accept_language: AcceptLanguage,
+    q: Option<String>,
+    c: Option<String>,
+) -> Markup {
+    let locale = ui_locale.0;
+
+    let config_in_url = UserConfig::new_from_optional_uri(c);
+    // each plugin may have its own default config
+    let get_config = |path: &str| {
+        get_final_user_config(
+            setting,
+            cookies,
+            &accept_language,
+            path,
+            config_in_url.clone(),
+        )
+    };
+
+    let query = q.filter(|query| !query.trim().is_empty());
+
+    let results = match &query {
+        Some(query) => run_blocking(|| search_all(kodi, client, setting, query, &get_config)),
+        None => Vec::new(),
+    };
+
+    Presentation::new(
+        html!((LOCALES.lookup(&locale, "search"))),
+        html!(
+            (render_search_form(&locale, query.as_deref()))
+
+            @if setting.search_entry_points.is_empty() {
+                p { (LOCALES.lookup(&locale, "search-unavailable")) }
+            }
+
+            @for result in results {
+                h2 { (result.label) }
+                @match result.content {
+                    Ok(sub_content) => {
+                        @if sub_content.is_empty() {
+                            p { (LOCALES.lookup(&locale, "search-no-result")) }
+                        } @else {
+                            ul class="list_media" {
+                                @for sub_content in &sub_content {
+                                    li class="media_in_list" {
+                                        (render_sub_content_link(
+                                            sub_content,
+                                            &LinkState::new(
+                                                PathAccessData {
+                                                    config: config_in_url.clone(),
+                                                    ..result.access.clone()
+                                                },
+                                                None,
+                                            ),
+                                            setting,
+                                            false,
+                                        ))
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    Err(message_key) => {
+                        p class="error" { (LOCALES.lookup(&locale, message_key)) }
+                    },
+                }
+            }
+        ),
+    )
+    .build(&locale)
+}
@@ -0,0 +1,469 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageReader, Limits};
+use kodi_rust::{
+    data::SubContent, get_art_link_subcontent, get_proxied_art_link_subcontent, urlencode,
+    LinkState, Setting,
+};
+use log::error;
+use maud::{html, Markup};
+use rocket::{
+    http::{ContentType, Header},
+    response::{self, Responder},
+    Request, Response,
+};
+use sha2::{Digest, Sha256};
+
+/// the pixel densities a variant of the thumbnails is offered for, in ``srcset``. The width of each variant is [`Setting::thumbnail_width`] multiplied by the density.
+pub const THUMBNAIL_DENSITIES: [u32; 3] = [1, 2, 3];
+
+/// the biggest image that will be downloaded, in bytes
+const MAX_IMAGE_SIZE: u64 = 30 * 1024 * 1024;
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// the biggest width and height of an image that will be decoded, in pixels
+const MAX_IMAGE_DIMENSION: u32 = 10000;
+
+/// the most memory that can be allocated to decode an image, in bytes
+const MAX_DECODE_ALLOCATION: u64 = 256 * 1024 * 1024;
+
+/// the minimum time between two cleanings of a thumbnail cache directory
+const CACHE_CLEANING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// when the thumbnail cache directories were last cleaned by [`clean_cache`]
+static LAST_CACHE_CLEANING: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug)]
+pub enum ThumbnailError {
+    CantDownload(Box<ureq::Error>),
+    CantRead(io::Error),
+    /// the url isn't an http or https url, or a local file in an allowed directory
+    NotAllowed(String),
+    TooBig,
+    CantDecode(image::ImageError),
+    CantEncode(image::ImageError),
+}
+
+impl fmt::Display for ThumbnailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CantDownload(_) => write!(f, "can't download the image"),
+            Self::CantRead(_) => write!(f, "can't read the image"),
+            Self::NotAllowed(url) => write!(f, "the image at {} can't be fetched", url),
+            Self::TooBig => write!(f, "the image is bigger than {} bytes", MAX_IMAGE_SIZE),
+            Self::CantDecode(_) => write!(f, "can't decode the image"),
+            Self::CantEncode(_) => write!(f, "can't encode the thumbnail"),
+        }
+    }
+}
+
+impl Error for ThumbnailError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CantDownload(err) => Some(err),
+            Self::CantRead(err) => Some(err),
+            Self::NotAllowed(_) => None,
+            Self::TooBig => None,
+            Self::CantDecode(err) => Some(err),
+            Self::CantEncode(err) => Some(err),
+        }
+    }
+}
+
+/// An image served by the thumbnail proxy. It never change for a given url, so it can be cached by the browser for a long time.
+pub struct Thumbnail {
+    pub content_type: ContentType,
+    pub data: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for Thumbnail {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .header(Header::new(
+                "Cache-Control",
+                "public, max-age=31536000, immutable",
+            ))
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+/// return the width a thumbnail is generated at for a requested ``width``: the smallest offered width that is at least as big, or the biggest offered one. This limit the number of variants stored in the cache.
+///
+/// # Example
+///
+/// ```
+/// use kodionline::thumbnail::snap_width;
+///
+/// assert_eq!(snap_width(100, 300), 300);
+/// assert_eq!(snap_width(301, 300), 600);
+/// assert_eq!(snap_width(5000, 300), 900);
+/// ```
+pub fn snap_width(width: u32, base_width: u32) -> u32 {
+    let biggest = THUMBNAIL_DENSITIES[THUMBNAIL_DENSITIES.len() - 1] * base_width;
+    THUMBNAIL_DENSITIES
+        .iter()
+        .map(|density| density * base_width)
+        .find(|offered| *offered >= width)
+        .unwrap_or(biggest)
+}
+
+/// return the url of an art of ``sub_content``. It goes through the thumbnail proxy (resized to ``width`` if set) when [`Setting::get_thumbnail_cache_directory`] return a directory for the plugin of ``parent``, and is the one of [`get_art_link_subcontent`] otherwise.
+pub fn get_art_url(
+    setting: &Setting,
+    sub_content: &SubContent,
+    category: &str,
+    width: Option<u32>,
+    parent: &LinkState,
+) -> String {
+    let token_secret = setting.state_token_secret.as_deref();
+    if setting
+        .get_thumbnail_cache_directory(&parent.access.path)
+        .is_some()
+    {
+        get_proxied_art_link_subcontent(sub_content, category, width, parent, token_secret)
+    } else {
+        get_art_link_subcontent(sub_content, category, parent, token_secret)
+    }
+}
+
+/// return the url of the ``icon`` or the ``fanart`` of the addon of the plugin ``path``, served by [`crate::redirect_page::redirect_addon_art`]
+pub fn get_addon_art_url(path: &str, category: &str) -> String {
+    format!(
+        "/get_addon_art?path={}&category={}",
+        urlencode(path),
+        urlencode(category)
+    )
+}
+
+/// render the ``img.illustration`` of ``sub_content``, if it has a thumbnail. With the thumbnail proxy, resized variants are offered for every density of [`THUMBNAIL_DENSITIES`].
+pub fn render_illustration(
+    setting: &Setting,
+    sub_content: &SubContent,
+    parent: &LinkState,
+) -> Markup {
+    let category = match sub_content.listitem.get_thumb_category() {
+        Some(category) => category,
+        None => return html!(),
+    };
+    if setting
+        .get_thumbnail_cache_directory(&parent.access.path)
+        .is_none()
+    {
+        return html!(img class="illustration" src=(get_art_url(setting, sub_content, category, None, parent)) {});
+    };
+    let get_variant_url = |density: u32| {
+        get_art_url(
+            setting,
+            sub_content,
+            category,
+            Some(setting.thumbnail_width * density),
+            parent,
+        )
+    };
+    let srcset = THUMBNAIL_DENSITIES
+        .iter()
+        .map(|density| format!("{} {}x", get_variant_url(*density), density))
+        .collect::<Vec<_>>()
+        .join(", ");
+    html!(img class="illustration" src=(get_variant_url(1)) srcset=(srcset) loading="lazy" {})
+}
+
+/// return true if ``ip`` is a public address of the internet, and not one of the local network or of the server itself
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // shared address space (RFC 6598) and "this network"
+                || (first == 100 && (second & 0b1100_0000) == 64)
+                || first == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local and link local addresses
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// resolve ``netloc`` for the downloads of images, keeping only the public addresses, so a plugin can't make the server fetch from its local network
+fn resolve_public(netloc: &str) -> io::Result<Vec<SocketAddr>> {
+    let addresses: Vec<_> = netloc
+        .to_socket_addrs()?
+        .filter(|address| is_public_address(address.ip()))
+        .collect();
+    if addresses.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} has no public address", netloc),
+        ));
+    };
+    Ok(addresses)
+}
+
+/// return the content of the image at ``url``, that is either a local file in one of ``local_roots`` or hosted on a public web site
+fn fetch_image(url: &str, local_roots: &[PathBuf]) -> Result<Vec<u8>, ThumbnailError> {
+    let mut data = Vec::new();
+    let lowercase_url = url.to_lowercase();
+    if url.starts_with('/') {
+        let path = fs::canonicalize(url).map_err(ThumbnailError::CantRead)?;
+        let allowed = local_roots
+            .iter()
+            .filter_map(|root| fs::canonicalize(root).ok())
+            .any(|root| path.starts_with(root));
+        if !allowed {
+            return Err(ThumbnailError::NotAllowed(url.to_string()));
+        };
+        fs::File::open(path)
+            .and_then(|file| file.take(MAX_IMAGE_SIZE + 1).read_to_end(&mut data))
+            .map_err(ThumbnailError::CantRead)?;
+    } else if lowercase_url.starts_with("http://") || lowercase_url.starts_with("https://") {
+        ureq::AgentBuilder::new()
+            .resolver(resolve_public)
+            .timeout(DOWNLOAD_TIMEOUT)
+            .build()
+            .get(url)
+            .call()
+            .map_err(|err| ThumbnailError::CantDownload(Box::new(err)))?
+            .into_reader()
+            .take(MAX_IMAGE_SIZE + 1)
+            .read_to_end(&mut data)
+            .map_err(ThumbnailError::CantRead)?;
+    } else {
+        return Err(ThumbnailError::NotAllowed(url.to_string()));
+    };
+    if data.len() as u64 > MAX_IMAGE_SIZE {
+        return Err(ThumbnailError::TooBig);
+    };
+    Ok(data)
+}
+
+/// return the path the thumbnail of ``url`` resized to ``width`` is stored at in ``cache_directory``, without its extension
+fn get_cache_path(cache_directory: &Path, url: &str, width: Option<u32>) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(width.unwrap_or(0).to_le_bytes());
+    hasher.update(url.as_bytes());
+    let key: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    cache_directory.join(key)
+}
+
+/// decode ``data``, refusing images too big to be decoded safely
+fn decode_image(data: &[u8]) -> Result<DynamicImage, ThumbnailError> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(ThumbnailError::CantRead)?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOCATION);
+    reader.limits(limits);
+    reader.decode().map_err(ThumbnailError::CantDecode)
+}
+
+/// remove the least recently created files of ``cache_directory`` until it is no bigger than ``max_size`` bytes
+fn clean_cache(cache_directory: &Path, max_size: u64) -> io::Result<()> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(cache_directory)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_file() {
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, metadata.len(), entry.path()));
+        };
+    }
+    let mut total_size: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort();
+    for (_, size, path) in files {
+        if total_size <= max_size {
+            break;
+        };
+        fs::remove_file(path)?;
+        total_size -= size;
+    }
+    Ok(())
+}
+
+/// return the image at ``url``, reduced to ``width`` pixels if it is bigger. The image is kept in ``cache_directory``, so it is only downloaded and resized once. The oldest thumbnails are removed when the directory is bigger than ``max_cache_size`` bytes.
+///
+/// ``url`` must be an http or https url of a public address, or a local file in one of ``local_roots``.
+///
+/// The thumbnail is stored as JPEG, or as PNG if it has transparency.
+pub fn get_thumbnail(
+    cache_directory: &Path,
+    max_cache_size: u64,
+    url: &str,
+    width: Option<u32>,
+    local_roots: &[PathBuf],
+) -> Result<Thumbnail, ThumbnailError> {
+    let cache_path = get_cache_path(cache_directory, url, width);
+    for (format, content_type) in &[
+        (ImageFormat::Jpeg, ContentType::JPEG),
+        (ImageFormat::Png, ContentType::PNG),
+    ] {
+        if let Ok(data) = fs::read(cache_path.with_extension(format.extensions_str()[0])) {
+            return Ok(Thumbnail {
+                content_type: content_type.clone(),
+                data,
+            });
+        };
+    }
+
+    let mut image = decode_image(&fetch_image(url, local_roots)?)?;
+    if let Some(width) = width {
+        if image.width() > width {
+            image = image.resize(width, u32::MAX, FilterType::Lanczos3);
+        };
+    };
+    let (image, format, content_type) = if image.color().has_alpha() {
+        (image, ImageFormat::Png, ContentType::PNG)
+    } else {
+        (
+            DynamicImage::ImageRgb8(image.to_rgb8()),
+            ImageFormat::Jpeg,
+            ContentType::JPEG,
+        )
+    };
+    let mut data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut data), format)
+        .map_err(ThumbnailError::CantEncode)?;
+
+    // written in a temporary file first, so a partial thumbnail is never served
+    let final_path = cache_path.with_extension(format.extensions_str()[0]);
+    let temporary_path = cache_path.with_extension(format!("{:08x}.tmp", rand::random::<u32>()));
+    if let Err(err) = fs::create_dir_all(cache_directory)
+        .and_then(|_| fs::write(&temporary_path, &data))
+        .and_then(|_| fs::rename(&temporary_path, &final_path))
+    {
+        error!(
+            "can't store the thumbnail of {} in the cache: {:?}",
+            url, err
+        );
+        let _ = fs::remove_file(&temporary_path);
+    };
+
+    let should_clean = {
+        let mut last_cleaning = LAST_CACHE_CLEANING
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let should_clean =
+            last_cleaning.is_none_or(|last| last.elapsed() >= CACHE_CLEANING_INTERVAL);
+        if should_clean {
+            *last_cleaning = Some(Instant::now());
+        };
+        should_clean
+    };
+    if should_clean {
+        if let Err(err) = clean_cache(cache_directory, max_cache_size) {
+            error!(
+                "can't clean the thumbnail cache {:?}: {:?}",
+                cache_directory, err
+            );
+        };
+    };
+
+    Ok(Thumbnail { content_type, data })
+}
+
+#[test]
+fn test_cache_path() {
+    let directory = Path::new("/cache");
+    let path = get_cache_path(directory, "http://example.com/a.png", Some(300));
+    assert_eq!(path.parent(), Some(directory));
+    assert_eq!(path.file_name().unwrap().len(), 64);
+    assert_eq!(
+        path,
+        get_cache_path(directory, "http://example.com/a.png", Some(300))
+    );
+    assert_ne!(
+        path,
+        get_cache_path(directory, "http://example.com/a.png", Some(600))
+    );
+    assert_ne!(
+        path,
+        get_cache_path(directory, "http://example.com/a.png", None)
+    );
+    assert_ne!(
+        path,
+        get_cache_path(directory, "http://example.com/b.png", Some(300))
+    );
+}
+
+#[test]
+fn test_thumbnail_cache_hit() {
+    let directory = tempfile::tempdir().unwrap();
+    // this url can't be fetched, so the thumbnail can only come from the cache
+    let url = "http://127.0.0.1/art.png";
+    assert!(matches!(
+        get_thumbnail(directory.path(), u64::MAX, url, Some(300), &[]),
+        Err(ThumbnailError::CantDownload(_))
+    ));
+
+    let cache_path = get_cache_path(directory.path(), url, Some(300));
+    fs::write(cache_path.with_extension("png"), b"cached").unwrap();
+    let thumbnail = get_thumbnail(directory.path(), u64::MAX, url, Some(300), &[]).unwrap();
+    assert_eq!(thumbnail.content_type, ContentType::PNG);
+    assert_eq!(thumbnail.data, b"cached");
+    assert!(get_thumbnail(directory.path(), u64::MAX, url, None, &[]).is_err());
+}
+
+#[test]
+fn test_fetch_image_restrictions() {
+    let directory = tempfile::tempdir().unwrap();
+    let image_path = directory.path().join("art.png");
+    fs::write(&image_path, b"image").unwrap();
+    let image_url = image_path.to_str().unwrap();
+
+    assert_eq!(
+        fetch_image(image_url, &[directory.path().to_path_buf()]).unwrap(),
+        b"image"
+    );
+    assert!(matches!(
+        fetch_image(image_url, &[PathBuf::from("/nonexistent")]),
+        Err(ThumbnailError::NotAllowed(_))
+    ));
+    assert!(matches!(
+        fetch_image("file:///etc/passwd", &[]),
+        Err(ThumbnailError::NotAllowed(_))
+    ));
+    assert!(is_public_address("93.184.216.34".parse().unwrap()));
+    for address in &[
+        "127.0.0.1",
+        "10.1.2.3",
+        "192.168.1.1",
+        "169.254.169.254",
+        "100.64.0.1",
+        "::1",
+        "fd00::1",
+        "::ffff:127.0.0.1",
+    ] {
+        assert!(!is_public_address(address.parse().unwrap()), "{}", address);
+    }
+}
@@ -0,0 +1,907 @@
+use crate::get_plugin_type;
+use kodi_rust::{
+    input::{decode_input, encode_input},
+    LinkState, PathAccessData, UserConfig,
+};
+
+use log::warn;
+use maud::{html, PreEscaped};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rand::RngCore;
+use rocket::http::{Cookie, CookieJar};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// name of the cookie that store the identifier of the browser profile
+pub const PROFILE_COOKIE: &str = "profile";
+
+/// a media whose position is above this fraction of its duration is considered finished
+const FINISHED_FRACTION: f64 = 0.95;
+
+/// the default number of favourites, and of entries of the history, a profile can have. The oldest entries of the history are removed above it, and new favourites are refused
+pub const DEFAULT_MAX_PROFILE_ENTRIES: usize = 1000;
+
+/// the default number of profiles having favourites or an history. The data of the least recently active profile is removed when a new one would exceed it
+pub const DEFAULT_MAX_PROFILES: usize = 10000;
+
+#[derive(Debug)]
+/// represent error that can happen while using the [`UserData`] database
+pub enum UserDataError {
+    CantOpenDatabase(rusqlite::Error),
+    QueryFailed(rusqlite::Error),
+    CantParseFavourites(quick_xml::Error),
+    /// the profile already has the maximum number of favourites
+    TooManyFavourites,
+}
+
+impl fmt::Display for UserDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CantOpenDatabase(_) => write!(f, "can't open the user data database"),
+            Self::QueryFailed(_) => write!(f, "a query to the user data database failed"),
+            Self::CantParseFavourites(_) => write!(f, "the favourites file isn't valid XML"),
+            Self::TooManyFavourites => write!(f, "there are too many favourites"),
+        }
+    }
+}
+
+impl Error for UserDataError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CantOpenDatabase(err) => Some(err),
+            Self::QueryFailed(err) => Some(err),
+            Self::CantParseFavourites(err) => Some(err),
+            Self::TooManyFavourites => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for UserDataError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::QueryFailed(err)
+    }
+}
+
+/// return the identifier of the browser profile stored in the cookie, if any
+pub fn get_profile(cookies: &CookieJar<'_>) -> Option<String> {
+    cookies
+        .get(PROFILE_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+}
+
+/// return the identifier of the browser profile, creating a new random one (stored in a cookie) if there is none
+pub fn get_or_create_profile(cookies: &CookieJar<'_>) -> String {
+    if let Some(profile) = get_profile(cookies) {
+        return profile;
+    };
+    let profile = random_token();
+    cookies.add(
+        Cookie::build((PROFILE_COOKIE, profile.clone()))
+            .path("/")
+            .permanent(),
+    );
+    profile
+}
+
+/// return a random hexadecimal string, that can't be guessed
+fn random_token() -> String {
+    let mut bytes = [0; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// A saved folder or media, either as a favourite or in the history
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedEntry {
+    /// the access to the entry, with only the config present in the url
+    pub link: LinkState,
+    pub label: String,
+    /// the link to the thumbnail
+    pub thumbnail: Option<String>,
+    pub is_folder: bool,
+}
+
+impl SavedEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let config = UserConfig::new_from_optional_uri(row.get("config")?);
+        let parent_path: Option<String> = row.get("parent_path")?;
+        let parent_input: String = row.get("parent_input")?;
+        let input: String = row.get("input")?;
+        Ok(Self {
+            link: LinkState::new(
+                PathAccessData {
+                    path: row.get("path")?,
+                    input: decode_input(&input),
+                    config: config.clone(),
+                },
+                parent_path.map(|parent_path| PathAccessData {
+                    path: parent_path,
+                    input: decode_input(&parent_input),
+                    config,
+                }),
+            ),
+            label: row.get("label")?,
+            thumbnail: row.get("thumbnail")?,
+            is_folder: row.get("is_folder")?,
+        })
+    }
+
+    fn encoded_input(&self) -> String {
+        encode_input(&self.link.access.input)
+    }
+
+    fn encoded_parent_input(&self) -> String {
+        self.link
+            .parent
+            .as_ref()
+            .map(|parent| encode_input(&parent.input))
+            .unwrap_or_default()
+    }
+}
+
+/// An entry of the history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub entry: SavedEntry,
+    /// the last time this entry was seen, as an unix timestamp
+    pub last_seen: i64,
+    /// the position reached in the media, in seconds
+    pub position: f64,
+    /// the duration of the media, in seconds, if known
+    pub duration: Option<f64>,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            entry: SavedEntry::from_row(row)?,
+            last_seen: row.get("last_seen")?,
+            position: row.get("position")?,
+            duration: row.get("duration")?,
+        })
+    }
+
+    /// return ``true`` if this media was started but not finished
+    pub fn is_in_progress(&self) -> bool {
+        self.position > 0.0
+            && match self.duration {
+                Some(duration) => self.position < duration * FINISHED_FRACTION,
+                None => true,
+            }
+    }
+}
+
+const ENTRY_COLUMNS: &str =
+    "path, input, parent_path, parent_input, config, label, thumbnail, is_folder";
+
+/// The favourites, history and resume positions of every browser profile, stored in a SQLite database, with the accounts of the users and their sessions if [`kodi_rust::Setting::auth_mode`] is set.
+///
+/// It can be used by multiple threads.
+pub struct UserData {
+    connection: Mutex<Connection>,
+    max_profile_entries: usize,
+    max_profiles: usize,
+}
+
+impl UserData {
+    /// open (or create) the database at ``path``. If ``path`` is ``None``, the database is kept in memory, and lost when the server stop.
+    ///
+    /// # Errors
+    ///
+    /// return an error if the database can't be opened or its tables created
+    pub fn open(path: Option<&str>) -> Result<Self, UserDataError> {
+        let connection = match path {
+            Some(path) => Connection::open(path),
+            None => {
+                warn!("no database_path is set. The favourites and history will be lost when the server stop");
+                Connection::open_in_memory()
+            }
+        }
+        .map_err(UserDataError::CantOpenDatabase)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS favourites (
+                    profile TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    input TEXT NOT NULL,
+                    parent_path TEXT,
+                    parent_input TEXT NOT NULL,
+                    config TEXT NOT NULL,
+                    label TEXT NOT NULL,
+                    thumbnail TEXT,
+                    is_folder INTEGER NOT NULL,
+                    added INTEGER NOT NULL,
+                    PRIMARY KEY (profile, path, input)
+                );
+                CREATE TABLE IF NOT EXISTS history (
+                    profile TEXT NOT NULL,
+                    path TEXT NOT NULL,
+                    input TEXT NOT NULL,
+                    parent_path TEXT,
+                    parent_input TEXT NOT NULL,
+                    config TEXT NOT NULL,
+                    label TEXT NOT NULL,
+                    thumbnail TEXT,
+                    is_folder INTEGER NOT NULL,
+                    last_seen INTEGER NOT NULL,
+                    position REAL NOT NULL DEFAULT 0,
+                    duration REAL,
+                    PRIMARY KEY (profile, path, input)
+                );
+                CREATE TABLE IF NOT EXISTS users (
+                    name TEXT NOT NULL PRIMARY KEY,
+                    password_hash TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS sessions (
+                    token TEXT NOT NULL PRIMARY KEY,
+                    user TEXT NOT NULL,
+                    password_hash TEXT NOT NULL,
+                    expires INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS user_cookies (
+                    user TEXT NOT NULL,
+                    name TEXT NOT NULL,
+                    value TEXT NOT NULL,
+                    PRIMARY KEY (user, name)
+                );",
+            )
+            .map_err(UserDataError::CantOpenDatabase)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+            max_profile_entries: DEFAULT_MAX_PROFILE_ENTRIES,
+            max_profiles: DEFAULT_MAX_PROFILES,
+        })
+    }
+
+    /// set the number of favourites and entries of the history of each profile, and the number of profiles, that are kept (see [`DEFAULT_MAX_PROFILE_ENTRIES`] and [`DEFAULT_MAX_PROFILES`])
+    pub fn set_limits(&mut self, max_profile_entries: usize, max_profiles: usize) {
+        self.max_profile_entries = max_profile_entries;
+        self.max_profiles = max_profiles;
+    }
+
+    /// if ``profile`` has no data yet and there are already ``max_profiles`` profiles, remove the data of the least recently active one, so new visitors can't grow the database without limit
+    fn make_room_for_profile(
+        &self,
+        connection: &Connection,
+        profile: &str,
+    ) -> Result<(), UserDataError> {
+        let is_known = connection
+            .query_row(
+                "SELECT 1 FROM history WHERE profile = ?1 UNION ALL SELECT 1 FROM favourites WHERE profile = ?1 LIMIT 1",
+                params![profile],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if is_known {
+            return Ok(());
+        };
+        let profile_count: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM (SELECT profile FROM history UNION SELECT profile FROM favourites)",
+            [],
+            |row| row.get(0),
+        )?;
+        if (profile_count as usize) < self.max_profiles {
+            return Ok(());
+        };
+        let oldest: Option<String> = connection
+            .query_row(
+                "SELECT profile FROM (
+                    SELECT profile, MAX(last_seen) AS active FROM history GROUP BY profile
+                    UNION ALL SELECT profile, MAX(added) FROM favourites GROUP BY profile
+                ) GROUP BY profile ORDER BY MAX(active) LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(oldest) = oldest {
+            warn!("too many profiles, removing the data of the least recently active one");
+            connection.execute("DELETE FROM history WHERE profile = ?1", params![oldest])?;
+            connection.execute("DELETE FROM favourites WHERE profile = ?1", params![oldest])?;
+        };
+        Ok(())
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        match self.connection.lock() {
+            Ok(connection) => connection,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// add ``entry`` to the favourites of ``profile``, replacing it if it already exist
+    ///
+    /// # Errors
+    ///
+    /// return [`UserDataError::TooManyFavourites`] if ``entry`` is a new favourite, and the profile already has the maximum number of favourites
+    pub fn add_favourite(&self, profile: &str, entry: &SavedEntry) -> Result<(), UserDataError> {
+        let connection = self.connection();
+        self.make_room_for_profile(&connection, profile)?;
+        let favourite_count: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM favourites WHERE profile = ?1 AND NOT (path = ?2 AND input = ?3)",
+            params![profile, entry.link.access.path, entry.encoded_input()],
+            |row| row.get(0),
+        )?;
+        if favourite_count as usize >= self.max_profile_entries {
+            return Err(UserDataError::TooManyFavourites);
+        };
+        connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO favourites (profile, {}, added) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                ENTRY_COLUMNS
+            ),
+            params![
+                profile,
+                entry.link.access.path,
+                entry.encoded_input(),
+                entry.link.parent.as_ref().map(|parent| &parent.path),
+                entry.encoded_parent_input(),
+                entry.link.access.config.encode_to_uri(),
+                entry.label,
+                entry.thumbnail,
+                entry.is_folder,
+                now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_favourite(
+        &self,
+        profile: &str,
+        access: &PathAccessData,
+    ) -> Result<(), UserDataError> {
+        self.connection().execute(
+            "DELETE FROM favourites WHERE profile = ?1 AND path = ?2 AND input = ?3",
+            params![profile, access.path, encode_input(&access.input)],
+        )?;
+        Ok(())
+    }
+
+    pub fn is_favourite(
+        &self,
+        profile: &str,
+        access: &PathAccessData,
+    ) -> Result<bool, UserDataError> {
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT 1 FROM favourites WHERE profile = ?1 AND path = ?2 AND input = ?3",
+                params![profile, access.path, encode_input(&access.input)],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    /// return the favourites of ``profile``, from the oldest to the newest
+    pub fn get_favourites(&self, profile: &str) -> Result<Vec<SavedEntry>, UserDataError> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM favourites WHERE profile = ?1 ORDER BY added, label",
+            ENTRY_COLUMNS
+        ))?;
+        let result = statement
+            .query_map(params![profile], SavedEntry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(result)
+    }
+
+    /// add ``entry`` to the history of ``profile``, or mark it as seen now if it is already present (keeping the position). The oldest entries are removed above the maximum number of entries.
+    pub fn record_history(&self, profile: &str, entry: &SavedEntry) -> Result<(), UserDataError> {
+        let connection = self.connection();
+        self.make_room_for_profile(&connection, profile)?;
+        connection.execute(
+            &format!(
+                "INSERT INTO history (profile, {}, last_seen) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (profile, path, input) DO UPDATE SET
+                    parent_path = excluded.parent_path,
+                    parent_input = excluded.parent_input,
+                    config = excluded.config,
+                    label = excluded.label,
+                    thumbnail = COALESCE(excluded.thumbnail, thumbnail),
+                    last_seen = excluded.last_seen",
+                ENTRY_COLUMNS
+            ),
+            params![
+                profile,
+                entry.link.access.path,
+                entry.encoded_input(),
+                entry.link.parent.as_ref().map(|parent| &parent.path),
+                entry.encoded_parent_input(),
+                entry.link.access.config.encode_to_uri(),
+                entry.label,
+                entry.thumbnail,
+                entry.is_folder,
+                now(),
+            ],
+        )?;
+        connection.execute(
+            "DELETE FROM history WHERE profile = ?1 AND rowid NOT IN (
+                SELECT rowid FROM history WHERE profile = ?1 ORDER BY last_seen DESC, rowid DESC LIMIT ?2
+            )",
+            params![profile, self.max_profile_entries as i64],
+        )?;
+        Ok(())
+    }
+
+    /// store the position reached by the player in a media of the history. The position is limited to the duration. It does nothing if the media isn't in the history.
+    pub fn update_position(
+        &self,
+        profile: &str,
+        access: &PathAccessData,
+        position: f64,
+        duration: Option<f64>,
+    ) -> Result<(), UserDataError> {
+        self.connection().execute(
+            "UPDATE history SET position = MIN(?4, COALESCE(?5, duration, ?4)), duration = COALESCE(?5, duration), last_seen = ?6
+            WHERE profile = ?1 AND path = ?2 AND input = ?3",
+            params![
+                profile,
+                access.path,
+                encode_input(&access.input),
+                position,
+                duration,
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// return the position to resume the media at, if it was started but not finished
+    pub fn get_resume_position(
+        &self,
+        profile: &str,
+        access: &PathAccessData,
+    ) -> Result<Option<f64>, UserDataError> {
+        Ok(self
+            .connection()
+            .query_row(
+                &format!(
+                    "SELECT {}, last_seen, position, duration FROM history WHERE profile = ?1 AND path = ?2 AND input = ?3",
+                    ENTRY_COLUMNS
+                ),
+                params![profile, access.path, encode_input(&access.input)],
+                HistoryEntry::from_row,
+            )
+            .optional()?
+            .filter(|entry| entry.is_in_progress())
+            .map(|entry| entry.position))
+    }
+
+    /// return the ``limit`` last seen entries of the history of ``profile``, from the newest to the oldest
+    pub fn get_history(
+        &self,
+        profile: &str,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, UserDataError> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {}, last_seen, position, duration FROM history WHERE profile = ?1 ORDER BY last_seen DESC LIMIT ?2",
+            ENTRY_COLUMNS
+        ))?;
+        let result = statement
+            .query_map(params![profile, limit as i64], HistoryEntry::from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(result)
+    }
+
+    /// return the ``limit`` last media of the history that were started but not finished
+    pub fn get_continue_watching(
+        &self,
+        profile: &str,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, UserDataError> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {}, last_seen, position, duration FROM history
+            WHERE profile = ?1 AND is_folder = 0 AND position > 0 AND (duration IS NULL OR position < duration * ?2)
+            ORDER BY last_seen DESC LIMIT ?3",
+            ENTRY_COLUMNS
+        ))?;
+        let result = statement
+            .query_map(
+                params![profile, FINISHED_FRACTION, limit as i64],
+                HistoryEntry::from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(result)
+    }
+
+    pub fn clear_history(&self, profile: &str) -> Result<(), UserDataError> {
+        self.connection()
+            .execute("DELETE FROM history WHERE profile = ?1", params![profile])?;
+        Ok(())
+    }
+
+    /// create the user ``name``, or change its password if it already exist. ``password_hash`` is the hash created by [`crate::auth::hash_password`]. The sessions of the user are closed.
+    pub fn set_user(&self, name: &str, password_hash: &str) -> Result<(), UserDataError> {
+        let connection = self.connection();
+        connection.execute(
+            "INSERT OR REPLACE INTO users (name, password_hash) VALUES (?1, ?2)",
+            params![name, password_hash],
+        )?;
+        connection.execute("DELETE FROM sessions WHERE user = ?1", params![name])?;
+        Ok(())
+    }
+
+    /// return the password hash of the user ``name``, if it exist
+    pub fn get_password_hash(&self, name: &str) -> Result<Option<String>, UserDataError> {
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT password_hash FROM users WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// open a new session for ``user``, valid for ``lifetime`` seconds, and return its token. ``password_hash`` is the hash of the password the user logged in with, so the session can be closed when it change. The expired sessions are removed.
+    pub fn create_session(
+        &self,
+        user: &str,
+        password_hash: &str,
+        lifetime: u64,
+    ) -> Result<String, UserDataError> {
+        let token = random_token();
+        let connection = self.connection();
+        connection.execute("DELETE FROM sessions WHERE expires <= ?1", params![now()])?;
+        connection.execute(
+            "INSERT INTO sessions (token, user, password_hash, expires) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                token,
+                user,
+                password_hash,
+                now().saturating_add(lifetime.min(i64::MAX as u64) as i64)
+            ],
+        )?;
+        Ok(token)
+    }
+
+    /// return the user of the session ``token`` and the password hash it was opened with, if it exist and hasn't expired
+    pub fn get_session(&self, token: &str) -> Result<Option<(String, String)>, UserDataError> {
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT user, password_hash FROM sessions WHERE token = ?1 AND expires > ?2",
+                params![token, now()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?)
+    }
+
+    pub fn remove_session(&self, token: &str) -> Result<(), UserDataError> {
+        self.connection()
+            .execute("DELETE FROM sessions WHERE token = ?1", params![token])?;
+        Ok(())
+    }
+
+    /// return the value of the cookies saved for ``user`` with [`UserData::set_user_cookie`], by name
+    pub fn get_user_cookies(&self, user: &str) -> Result<Vec<(String, String)>, UserDataError> {
+        let connection = self.connection();
+        let mut statement =
+            connection.prepare("SELECT name, value FROM user_cookies WHERE user = ?1")?;
+        let result = statement
+            .query_map(params![user], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(result)
+    }
+
+    /// save the value of the cookie ``name`` of ``user``, or remove it if ``value`` is ``None``
+    pub fn set_user_cookie(
+        &self,
+        user: &str,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<(), UserDataError> {
+        let connection = self.connection();
+        match value {
+            Some(value) => connection.execute(
+                "INSERT OR REPLACE INTO user_cookies (user, name, value) VALUES (?1, ?2, ?3)",
+                params![user, name, value],
+            )?,
+            None => connection.execute(
+                "DELETE FROM user_cookies WHERE user = ?1 AND name = ?2",
+                params![user, name],
+            )?,
+        };
+        Ok(())
+    }
+}
+
+/// render favourites in the ``favourites.xml`` format of Kodi. Folders are opened with ``ActivateWindow`` and media with ``PlayMedia``.
+///
+/// Kodi favourites can't carry inputs, so entries that need one are exported without it.
+pub fn export_kodi_favourites(favourites: &[SavedEntry]) -> String {
+    html!(
+        (PreEscaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"))
+        favourites {
+            @for favourite in favourites {
+                favourite name=(favourite.label) thumb=[&favourite.thumbnail] {
+                    @if favourite.is_folder {
+                        (format!(
+                            "ActivateWindow({},\"{}\",return)",
+                            if get_plugin_type(&favourite.link.access.path).as_deref() == Some("audio") { 10502 } else { 10025 },
+                            favourite.link.access.path
+                        ))
+                    } @else {
+                        (format!("PlayMedia(\"{}\")", favourite.link.access.path))
+                    }
+                }
+            }
+        }
+    )
+    .into_string()
+}
+
+/// extract the ``plugin://`` path of the action of a Kodi favourite
+fn get_path_from_kodi_action(action: &str) -> Option<String> {
+    let start = action.find("plugin://")?;
+    let path = &action[start..];
+    let end = path.find(['"', ',', ')']).unwrap_or(path.len());
+    Some(path[..end].to_string())
+}
+
+/// parse the favourites in the ``favourites.xml`` format of Kodi. Favourites that don't point to a plugin are ignored.
+///
+/// # Errors
+///
+/// return an error if ``xml`` isn't valid XML
+pub fn import_kodi_favourites(xml: &str) -> Result<Vec<SavedEntry>, UserDataError> {
+    let mut reader = Reader::from_str(xml);
+    let mut result = Vec::new();
+    let mut current: Option<(String, Option<String>, String)> = None; // name, thumb, action
+    loop {
+        match reader
+            .read_event()
+            .map_err(UserDataError::CantParseFavourites)?
+        {
+            Event::Start(element) if element.name().as_ref() == b"favourite" => {
+                let mut name = String::new();
+                let mut thumb = None;
+                for attribute in element.attributes().flatten() {
+                    let value = attribute
+                        .decode_and_unescape_value(reader.decoder())
+                        .map_err(UserDataError::CantParseFavourites)?
+                        .to_string();
+                    match attribute.key.as_ref() {
+                        b"name" => name = value,
+                        // local thumbnails of Kodi can't be displayed
+                        b"thumb" if value.starts_with("http") => thumb = Some(value),
+                        _ => (),
+                    }
+                }
+                current = Some((name, thumb, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, _, action)) = &mut current {
+                    action.push_str(
+                        &text
+                            .unescape()
+                            .map_err(UserDataError::CantParseFavourites)?,
+                    );
+                }
+            }
+            Event::End(element) if element.name().as_ref() == b"favourite" => {
+                if let Some((name, thumb, action)) = current.take() {
+                    if let Some(path) = get_path_from_kodi_action(&action) {
+                        result.push(SavedEntry {
+                            label: if name.is_empty() { path.clone() } else { name },
+                            link: LinkState::new(
+                                PathAccessData::new(path, None, UserConfig::new_empty()),
+                                None,
+                            ),
+                            thumbnail: thumb,
+                            is_folder: !action.trim_start().starts_with("PlayMedia"),
+                        });
+                    }
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_sessions() {
+    let user_data = UserData::open(None).unwrap();
+    user_data.set_user("alice", "hash").unwrap();
+
+    let expired = user_data.create_session("alice", "hash", 0).unwrap();
+    assert_eq!(user_data.get_session(&expired).unwrap(), None);
+
+    let token = user_data.create_session("alice", "hash", 3600).unwrap();
+    assert_eq!(
+        user_data.get_session(&token).unwrap(),
+        Some(("alice".to_string(), "hash".to_string()))
+    );
+    user_data.remove_session(&token).unwrap();
+    assert_eq!(user_data.get_session(&token).unwrap(), None);
+
+    // changing the password close the sessions of the user
+    let token = user_data.create_session("alice", "hash", 3600).unwrap();
+    user_data.set_user("alice", "new hash").unwrap();
+    assert_eq!(user_data.get_session(&token).unwrap(), None);
+}
+
+#[cfg(test)]
+fn get_test_entry(path: &str, input: &[&str], is_folder: bool) -> SavedEntry {
+    let mut config = UserConfig::new_empty();
+    *config.language_order = vec!["fr".into()];
+    SavedEntry {
+        link: LinkState::new(
+            PathAccessData {
+                path: path.into(),
+                input: input.iter().map(|input| input.to_string()).collect(),
+                config: config.clone(),
+            },
+            Some(PathAccessData {
+                path: "plugin://plugin.video.test/".into(),
+                input: vec!["parent input".into()],
+                config,
+            }),
+        ),
+        label: format!("label of {}", path),
+        thumbnail: Some("http://example.com/thumb.png".into()),
+        is_folder,
+    }
+}
+
+#[test]
+fn test_favourites_and_history() {
+    let directory = tempfile::tempdir().unwrap();
+    let database_path = directory.path().join("user_data.sqlite");
+    let database_path = database_path.to_str().unwrap();
+    let folder = get_test_entry("plugin://plugin.video.test/folder", &[], true);
+    let media = get_test_entry("plugin://plugin.video.test/media", &["a", "b:c"], false);
+
+    {
+        let user_data = UserData::open(Some(database_path)).unwrap();
+        user_data.add_favourite("profile", &folder).unwrap();
+        user_data.add_favourite("profile", &media).unwrap();
+        user_data.add_favourite("other", &media).unwrap();
+        user_data
+            .remove_favourite("other", &media.link.access)
+            .unwrap();
+        user_data.record_history("profile", &media).unwrap();
+        user_data
+            .update_position("profile", &media.link.access, 30.0, Some(100.0))
+            .unwrap();
+    }
+
+    // the data is still there after the database is opened again
+    let user_data = UserData::open(Some(database_path)).unwrap();
+    assert_eq!(
+        user_data.get_favourites("profile").unwrap(),
+        vec![folder.clone(), media.clone()]
+    );
+    assert!(user_data.get_favourites("other").unwrap().is_empty());
+    assert!(user_data
+        .is_favourite("profile", &media.link.access)
+        .unwrap());
+
+    let history = user_data.get_history("profile", 10).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].entry, media);
+    assert_eq!(history[0].position, 30.0);
+    assert_eq!(history[0].duration, Some(100.0));
+    assert_eq!(
+        user_data
+            .get_resume_position("profile", &media.link.access)
+            .unwrap(),
+        Some(30.0)
+    );
+    assert_eq!(
+        user_data.get_continue_watching("profile", 10).unwrap(),
+        history
+    );
+
+    // a finished media isn't resumed
+    user_data
+        .update_position("profile", &media.link.access, 99.0, None)
+        .unwrap();
+    assert_eq!(
+        user_data
+            .get_resume_position("profile", &media.link.access)
+            .unwrap(),
+        None
+    );
+    // the position is limited to the duration
+    user_data
+        .update_position("profile", &media.link.access, 150.0, None)
+        .unwrap();
+    assert_eq!(
+        user_data.get_history("profile", 10).unwrap()[0].position,
+        100.0
+    );
+    user_data.clear_history("profile").unwrap();
+    assert!(user_data.get_history("profile", 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_user_data_limits() {
+    let mut user_data = UserData::open(None).unwrap();
+    user_data.set_limits(2, 2);
+    let entries: Vec<SavedEntry> = (0..3)
+        .map(|nb| get_test_entry(&format!("plugin://plugin.video.test/{}", nb), &[], false))
+        .collect();
+
+    // the oldest entry of the history is removed
+    for entry in &entries {
+        user_data.record_history("profile", entry).unwrap();
+    }
+    let history = user_data.get_history("profile", 10).unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(history
+        .iter()
+        .all(|history_entry| history_entry.entry != entries[0]));
+
+    // a new favourite is refused, but an existing one can be replaced
+    user_data.add_favourite("profile", &entries[0]).unwrap();
+    user_data.add_favourite("profile", &entries[1]).unwrap();
+    assert!(matches!(
+        user_data.add_favourite("profile", &entries[2]),
+        Err(UserDataError::TooManyFavourites)
+    ));
+    user_data.add_favourite("profile", &entries[1]).unwrap();
+    assert_eq!(user_data.get_favourites("profile").unwrap().len(), 2);
+
+    // the data of the least recently active profile is removed for a new profile
+    user_data.add_favourite("other", &entries[0]).unwrap();
+    user_data
+        .connection()
+        .execute(
+            "UPDATE history SET last_seen = 0 WHERE profile = 'profile'",
+            [],
+        )
+        .unwrap();
+    user_data
+        .connection()
+        .execute(
+            "UPDATE favourites SET added = 0 WHERE profile = 'profile'",
+            [],
+        )
+        .unwrap();
+    user_data.record_history("new", &entries[0]).unwrap();
+    assert!(user_data.get_history("profile", 10).unwrap().is_empty());
+    assert!(user_data.get_favourites("profile").unwrap().is_empty());
+    assert_eq!(user_data.get_favourites("other").unwrap().len(), 1);
+    assert_eq!(user_data.get_history("new", 10).unwrap().len(), 1);
+}
+
+#[test]
+fn test_kodi_favourites() {
+    let folder = get_test_entry("plugin://plugin.audio.test/folder", &[], true);
+    let media = get_test_entry("plugin://plugin.video.test/media?a=b&c=d%2Ce", &[], false);
+    let xml = export_kodi_favourites(&[folder.clone(), media.clone()]);
+    assert!(
+        xml.contains("ActivateWindow(10502,&quot;plugin://plugin.audio.test/folder&quot;,return)")
+    );
+
+    let imported = import_kodi_favourites(&xml).unwrap();
+    assert_eq!(imported.len(), 2);
+    for (imported, original) in imported.iter().zip(&[folder, media]) {
+        assert_eq!(imported.label, original.label);
+        assert_eq!(imported.thumbnail, original.thumbnail);
+        assert_eq!(imported.is_folder, original.is_folder);
+        // the parent, the input and the config aren't kept by Kodi
+        assert_eq!(imported.link.access.path, original.link.access.path);
+        assert_eq!(imported.link.parent, None);
+    }
+
+    assert!(import_kodi_favourites("<favourites></favourite>").is_err());
+}
@@ -0,0 +1,26 @@
+// go to the next media of the folder once the video is finished, if the user enabled it
+var autoplay_checkbox = document.getElementById("autoplay_next");
+var autoplay_setting = "autoplay_next";
+var autoplay_started = "autoplay_next_started";
+
+autoplay_checkbox.checked = window.localStorage.getItem(autoplay_setting) == "true";
+autoplay_checkbox.addEventListener("change", function(e) {
+	window.localStorage.setItem(autoplay_setting, String(e.target.checked));
+});
+
+for (player of document.getElementsByClassName("tracked_media")) {
+	// this page was opened by the end of the previous media
+	if (autoplay_checkbox.checked && window.sessionStorage.getItem(autoplay_started) == "true") {
+		window.sessionStorage.removeItem(autoplay_started);
+		player.play();
+	};
+	if (player.getAttribute("data-next") == null) {
+		continue;
+	};
+	player.addEventListener("ended", function(e) {
+		if (autoplay_checkbox.checked) {
+			window.sessionStorage.setItem(autoplay_started, "true");
+			window.location.href = e.target.getAttribute("data-next");
+		};
+	});
+}
@@ -0,0 +1,36 @@
+// report the position reached in the media to the server, so it can be resumed later
+var tracked_media = document.getElementsByClassName("tracked_media");
+
+var position_url = "/history/position" + window.location.search;
+var report_interval = 10; // in seconds
+
+function report_position(player) {
+	var body = new URLSearchParams();
+	body.append("position", String(player.currentTime));
+	if (isFinite(player.duration)) {
+		body.append("duration", String(player.duration));
+	};
+	navigator.sendBeacon(position_url, body);
+}
+
+for (player of tracked_media) {
+	var resume = player.getAttribute("data-resume");
+	if (resume != null) {
+		player.addEventListener("loadedmetadata", function(e) {
+			e.target.currentTime = Number(e.target.getAttribute("data-resume"));
+		}, { once: true });
+	};
+	player.last_report = 0;
+	player.addEventListener("timeupdate", function(e) {
+		if (Math.abs(e.target.currentTime - e.target.last_report) >= report_interval) {
+			e.target.last_report = e.target.currentTime;
+			report_position(e.target);
+		};
+	});
+	player.addEventListener("pause", function(e) {
+		report_position(e.target);
+	});
+	player.addEventListener("ended", function(e) {
+		report_position(e.target);
+	});
+}
@@ -2,6 +2,28 @@ var music_players = document.getElementsByClassName("audiopreview");
 
 var can_play_multiple_music = false;
 var auto_play_next_music = true;
+var continue_on_next_page = "musicplayer_continue";
+
+// return the first player after the element number after_nb, skipping the elements that aren't playable (and so have no player). Return null if there is none.
+function find_next_player(after_nb) {
+	var next_player = null;
+	for (player of music_players) {
+		var player_nb = Number(player.getAttribute("audiopreview_nb"));
+		if (player_nb > after_nb && (next_player == null || player_nb < Number(next_player.getAttribute("audiopreview_nb")))) {
+			next_player = player;
+		};
+	};
+	return next_player;
+}
+
+// this page was opened by the end of the last music of the previous page
+if (window.sessionStorage.getItem(continue_on_next_page) == "true") {
+	window.sessionStorage.removeItem(continue_on_next_page);
+	var first_player = find_next_player(-1);
+	if (first_player != null) {
+		first_player.play();
+	};
+};
 
 for (player of music_players) {
 	player.addEventListener("mouseover", function(e) {
@@ -9,27 +31,30 @@ for (player of music_players) {
 	}); //TODO: maybe use the whole tile choose to preload
 	player.addEventListener("playing", function(e) {
 		if (!can_play_multiple_music) {
-			var next_id = Number(e.target.getAttribute("audiopreview_nb")) + 1;
+			var next_player = find_next_player(Number(e.target.getAttribute("audiopreview_nb")));
 			for (player of music_players) {
-				var player_id = player.getAttribute("audiopreview_nb");
-				if (player_id != e.target.getAttribute("audiopreview_nb")) {
+				if (player != e.target) {
 					player.pause();
 					player.fastSeek(0);
 				};
-				if (player_id == next_id) {
-					player.preload = "auto";
-				};
+			};
+			if (next_player != null) {
+				next_player.preload = "auto";
 			};
 		};
 	});
 	player.addEventListener("ended", function(e) {
 		e.target.fastSeek(0);
 		if (auto_play_next_music) {
-			var next_id = Number(e.target.getAttribute("audiopreview_nb")) + 1;
-			for (player of music_players) {
-				if (player.getAttribute("audiopreview_nb") == String(next_id)) {
-					player.play();
-				};
+			var next_player = find_next_player(Number(e.target.getAttribute("audiopreview_nb")));
+			if (next_player != null) {
+				next_player.play();
+			};
+			// continue with the first music of the next page of the folder
+			var next_page = document.getElementById("next_page");
+			if (next_player == null && next_page != null) {
+				window.sessionStorage.setItem(continue_on_next_page, "true");
+				window.location.href = next_page.getAttribute("href");
 			};
 		};
 	});
@@ -0,0 +1,23 @@
+function move_entry(entry, go_up) {
+	var other = go_up ? entry.previousElementSibling : entry.nextElementSibling;
+	if (other == null) {
+		return;
+	};
+	var input = entry.getElementsByTagName("input")[0];
+	var other_input = other.getElementsByTagName("input")[0];
+	var value = input.value;
+	input.value = other_input.value;
+	other_input.value = value;
+}
+
+for (button of document.getElementsByClassName("move_up")) {
+	button.addEventListener("click", function(e) {
+		move_entry(e.target.parentElement, true);
+	});
+}
+
+for (button of document.getElementsByClassName("move_down")) {
+	button.addEventListener("click", function(e) {
+		move_entry(e.target.parentElement, false);
+	});
+}
@@ -2,6 +2,8 @@
 
 error-cant-get-plugin-type = Can't get the type of the plugin.
 error-no-media-found-on-page = No media were found on this page.
+error-invalid-link = This link is invalid.
+error-cant-detect-media-type = Can't detect the media type. Please download the file to use it.
 
 word-title = title
 word-album = album
@@ -10,6 +12,7 @@ word-year = year
 word-plot = plot
 word-genre = genre
 word-audio-language = audio language
+avalaible-languages = avalaible languages
 
 word-video = video
 word-audio = audio
@@ -17,6 +20,9 @@ word-media = media
 word-download = download
 
 play-all-music-sync = play all music syncronously (take care, may be loud and/or laggy)
+subscribe-feed = subscribe to this folder as a podcast (RSS)
+playlist-export = playlist for an external player (VLC, mpv...)
+open-in-external-player = open in an external player (playlist)
 input-required = input required
 
 plugin-asked-value = The plugin asked for a value.
@@ -28,6 +34,7 @@ error-getting-url = error while getting the page at { $url }
 main-page = main page
 kodi-plugin-url = kodi plugin url
 footer-legal = Website programmed by marius851000. Some data displayed on this site are not mine, namely nearly all data provided by the kodi's plugins.
+language-name = English
 
 
 ## error
@@ -42,3 +49,132 @@ presentation-advantage = This may have some advantage :
 presentation-advantage-1 = it permit greater anonymity: individual user tracking are almost always done when viewing the webpage that contain the video, not the video itself
 presentation-advantage-2 = it allow to have a unified interface (while the original interface should be almost always better than this one, this one have a download button)
 presentation-advantage-3 = it have the same advantage to browsing the plugin in kodi (having a different presentation than the original, for the worst and the better)
+avalaible-plugins = avalaible plugins
+direct-plugin-path = use a direct kodi plugin page
+go-to-path = go to the path
+
+## preferences page
+preferences = preferences
+preferences-title = kodi online: preferences
+preferences-explanation = Those preferences are stored in a cookie, and are used for every page of this site. They are merged with the default settings of the server according to the merge mode. Empty fields are ignored.
+preferences-language-order = preferred languages
+preferences-resolution-order = preferred resolutions
+preferences-format-order = preferred formats
+preferences-server-default = server default
+preferences-merge-mode = merge with the server default :
+merge-mode-prepend = before the server default
+merge-mode-append = after the server default
+merge-mode-replace = replace the server default
+merge-mode-remove = remove from the server default
+move-up = up
+move-down = down
+preferences-save = save the preferences
+preferences-reset = reset the preferences
+
+## search page
+search = search
+search-in-plugins = search in all plugins
+search-unavailable = No plugin can be searched on this server.
+search-no-result = No result.
+error-search-more-input = This plugin asked for more input. Browse it directly to search in it.
+error-search-failed = The search failed in this plugin.
+
+## favourites and history
+favourites = favourites
+favourites-title = kodi online: favourites and history
+favourite-add = add to the favourites
+favourite-remove = remove from the favourites
+continue-watching = continue watching
+history = history
+history-empty = The history is empty.
+history-clear = clear the history
+history-position = stopped at
+kodi-favourites = Kodi favourites
+kodi-favourites-export = export the favourites as a Kodi favourites.xml file
+kodi-favourites-import = import the content of a Kodi favourites.xml file (only the favourites pointing to a plugin are imported)
+kodi-favourites-import-submit = import
+
+## navigation between the media of a folder
+previous-item = previous
+next-item = next
+autoplay-next = automatically play the next one
+
+## sorting, filtering and layout of folders
+sort-by = sort by
+sort-plugin-order = order of the plugin
+sort-label = label
+sort-year = year
+sort-duration = duration
+sort-episode = episode
+sort-date_added = recently added
+filter = filter
+view-mode = display as
+view-list = list
+view-grid = poster grid
+view-table = table
+listing-apply = apply
+word-episode = episode
+word-duration = duration
+preferences-listing = display of the folders (also remembered when changed on a folder)
+page-previous = previous page
+page-next = next page
+
+## episodes grouped by season
+season-heading = season { $season }
+
+## administration
+admin = administration
+admin-none = nothing for now
+admin-running = running plugins
+admin-kill = kill
+admin-plugins = plugins
+admin-plugin = plugin
+admin-successes = successes
+admin-failures = failures
+admin-success-rate = success rate
+admin-history = last invocations
+admin-path = path
+admin-started = started
+admin-exit-status = exit status
+admin-log = log
+admin-show-log = show the log
+admin-ago = { $duration } ago
+admin-cache = cache
+admin-stored = stored
+admin-result = result
+admin-result-media = media
+admin-result-folder = folder of { $count } elements
+admin-result-keyboard = asking for an input
+admin-evict = remove
+admin-evict-plugin = remove every result of this plugin
+admin-debug = debug
+
+## debug view
+debug-title = debug view
+debug-open-page = open the page
+debug-bypass-cache = call the plugin again, bypassing the cache
+debug-from-cache = from the cache
+debug-yes = yes
+debug-no = no
+debug-command-line = command line
+debug-not-invoked = python wasn't started
+
+## busy page
+error-busy = The server is too busy to run this plugin right now. Please retry in a few seconds.
+
+## addon policy
+error-not-a-plugin = { $path } isn't the path of a plugin.
+error-addon-not-allowed = The addon { $addon } isn't available on this server.
+
+## login
+login-title = kodi online: login
+login-user = user name
+login-password = password
+login-submit = log in
+login-failed = Wrong user name or password.
+login-too-many-attempts = Too many logins failed from your address. Please retry later.
+login-error = The login failed because of an error of the server. Please retry later.
+login-proxy = You should be authenticated by the server in front of this site, but it didn't tell who you are.
+login-disabled = There are no accounts on this site.
+login-logged-in-as = logged in as { $user }
+login-logout = log out
@@ -2,6 +2,8 @@
 
 error-cant-get-plugin-type = Ne peut pas obtenir le type du plugin.
 error-no-media-found-on-page = Aucun media n'est présent sur cette page.
+error-invalid-link = Ce lien est invalide.
+error-cant-detect-media-type = Impossible de détecter le type du média. Veuillez télécharger le fichier pour l'utiliser.
 
 word-title = titre
 word-album = album
@@ -10,6 +12,7 @@ word-year = année
 word-plot = résumé
 word-genre = genre
 word-audio-language = langue audio
+avalaible-languages = langues disponibles
 
 word-video = video
 word-audio = audio
@@ -17,6 +20,9 @@ word-media = média
 word-download = téléchargement
 
 play-all-music-sync = Jouer toute les musiques de manières syncronisé (attentnion, peut être bruyant/lent)
+subscribe-feed = s'abonner à ce dossier comme un podcast (RSS)
+playlist-export = liste de lecture pour un lecteur externe (VLC, mpv...)
+open-in-external-player = ouvrir dans un lecteur externe (liste de lecture)
 input-required = entrée nécessaire
 
 plugin-asked-value = Le plugin à demandé une valeur.
@@ -28,6 +34,7 @@ error-getting-url = Une erreur est survenur lors de l'obtention de l'url Kodi {
 main-page = page d'accueil
 kodi-plugin-url = url du plugin kodi
 footer-legal = Site web programmé par marius851000. Certaine données affiché sur ce site ne m'appartiennent pas, en particulier toute les données fournis par les plugins kodi.
+language-name = Français
 
 
 ## error
@@ -42,3 +49,132 @@ presentation-advantage = Cela peut présenter les avantages suivants :
 presentation-advantage-1 = Permet un meilleur anonymat : Le suivit des utilisateurs se font en quasi majoré lors du visionnage de la page web qui contient la vidéo, non par la vidéo elle-même.
 presentation-advantage-2 = Permet d'avoir une interface unifié (même si l'interface original devrait certainement être meilleur que celle-ci, celle-ci à un bouton de téléchargement)
 presentation-advantage-3 = A les même avantage que la navigation du plugin dans kodi (permet d'avoir un présentation différente de l'original, pour le meilleur et pour le pire)
+avalaible-plugins = plugins disponibles
+direct-plugin-path = utiliser directement une page de plugin kodi
+go-to-path = aller à cette page
+
+## preferences page
+preferences = préférences
+preferences-title = kodi online : préférences
+preferences-explanation = Ces préférences sont stockées dans un cookie, et sont utilisées sur toutes les pages de ce site. Elles sont fusionnées avec les paramètres par défaut du serveur selon le mode de fusion. Les champs vides sont ignorés.
+preferences-language-order = langues préférées
+preferences-resolution-order = résolutions préférées
+preferences-format-order = formats préférés
+preferences-server-default = défaut du serveur
+preferences-merge-mode = fusion avec le défaut du serveur :
+merge-mode-prepend = avant le défaut du serveur
+merge-mode-append = après le défaut du serveur
+merge-mode-replace = remplace le défaut du serveur
+merge-mode-remove = retire du défaut du serveur
+move-up = monter
+move-down = descendre
+preferences-save = enregistrer les préférences
+preferences-reset = réinitialiser les préférences
+
+## search page
+search = rechercher
+search-in-plugins = rechercher dans toutes les extensions
+search-unavailable = Aucune extension ne peut être recherchée sur ce serveur.
+search-no-result = Aucun résultat.
+error-search-more-input = Cette extension a demandé plus d'informations. Parcourez-la directement pour y rechercher.
+error-search-failed = La recherche a échoué dans cette extension.
+
+## favourites and history
+favourites = favoris
+favourites-title = kodi online: favoris et historique
+favourite-add = ajouter aux favoris
+favourite-remove = retirer des favoris
+continue-watching = reprendre la lecture
+history = historique
+history-empty = L'historique est vide.
+history-clear = effacer l'historique
+history-position = arrêté à
+kodi-favourites = favoris de Kodi
+kodi-favourites-export = exporter les favoris dans un fichier favourites.xml de Kodi
+kodi-favourites-import = importer le contenu d'un fichier favourites.xml de Kodi (seuls les favoris pointant vers une extension sont importés)
+kodi-favourites-import-submit = importer
+
+## navigation between the media of a folder
+previous-item = précédent
+next-item = suivant
+autoplay-next = lire automatiquement le suivant
+
+## sorting, filtering and layout of folders
+sort-by = trier par
+sort-plugin-order = ordre de l'extension
+sort-label = nom
+sort-year = année
+sort-duration = durée
+sort-episode = épisode
+sort-date_added = ajouts récents
+filter = filtrer
+view-mode = afficher en
+view-list = liste
+view-grid = grille d'affiches
+view-table = tableau
+listing-apply = appliquer
+word-episode = épisode
+word-duration = durée
+preferences-listing = affichage des dossiers (aussi mémorisé quand il est modifié dans un dossier)
+page-previous = page précédente
+page-next = page suivante
+
+## episodes grouped by season
+season-heading = saison { $season }
+
+## administration
+admin = administration
+admin-none = rien pour l'instant
+admin-running = plugins en cours d'exécution
+admin-kill = tuer
+admin-plugins = plugins
+admin-plugin = plugin
+admin-successes = réussites
+admin-failures = échecs
+admin-success-rate = taux de réussite
+admin-history = dernières exécutions
+admin-path = chemin
+admin-started = démarré
+admin-exit-status = code de sortie
+admin-log = journal
+admin-show-log = afficher le journal
+admin-ago = il y a { $duration }
+admin-cache = cache
+admin-stored = stocké
+admin-result = résultat
+admin-result-media = média
+admin-result-folder = dossier de { $count } éléments
+admin-result-keyboard = demande d'une saisie
+admin-evict = retirer
+admin-evict-plugin = retirer tous les résultats de ce plugin
+admin-debug = déboguer
+
+## debug view
+debug-title = vue de débogage
+debug-open-page = ouvrir la page
+debug-bypass-cache = rappeler le plugin, sans utiliser le cache
+debug-from-cache = depuis le cache
+debug-yes = oui
+debug-no = non
+debug-command-line = ligne de commande
+debug-not-invoked = python n'a pas été démarré
+
+## busy page
+error-busy = Le serveur est trop occupé pour exécuter cette extension pour l'instant. Veuillez réessayer dans quelques secondes.
+
+## addon policy
+error-not-a-plugin = { $path } n'est pas le chemin d'une extension.
+error-addon-not-allowed = L'extension { $addon } n'est pas disponible sur ce serveur.
+
+## login
+login-title = kodi online : connexion
+login-user = nom d'utilisateur
+login-password = mot de passe
+login-submit = se connecter
+login-failed = Nom d'utilisateur ou mot de passe incorrect.
+login-too-many-attempts = Trop de connexions ont échoué depuis votre adresse. Veuillez réessayer plus tard.
+login-error = La connexion a échoué à cause d'une erreur du serveur. Veuillez réessayer plus tard.
+login-proxy = Vous devriez être authentifié par le serveur placé devant ce site, mais il n'a pas indiqué qui vous êtes.
+login-disabled = Il n'y a pas de comptes sur ce site.
+login-logged-in-as = connecté en tant que { $user }
+login-logout = se déconnecter